@@ -1,10 +1,619 @@
-use mongodb::{Client, Database};
+use crate::models::settings::PASSWORDLESS_SETTINGS_ID;
+use crate::models::tenant::DEFAULT_TENANT_SLUG;
+use crate::utils::audit;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, DateTime, Document},
+    error::{ErrorKind, WriteFailure},
+    options::{IndexOptions, ReturnDocument},
+    Client, Database, IndexModel,
+};
+use futures::stream::TryStreamExt;
 use std::env;
+use std::time::Duration;
 
 pub async fn init_db() -> Result<Database, mongodb::error::Error> {
     let uri = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let db_name = env::var("DB_NAME").expect("DB_NAME must be set");
     
     let client = Client::with_uri_str(uri).await?;
-    Ok(client.database(&db_name))
+    let db = client.database(&db_name);
+    prepare(&db).await?;
+    Ok(db)
+}
+
+/// Brings a database up to date: indexes, then backfills.
+pub async fn prepare(db: &Database) -> Result<(), mongodb::error::Error> {
+    remove_legacy_tokens(db).await?;
+    rename_audit_log(db).await?;
+    // Before the unique user indexes, which compare the stored form
+    lowercase_emails(db).await?;
+    prepare_unique_user_indexes(db).await?;
+    ensure_indexes(db).await?;
+    backfill(db).await
+}
+
+/// Tokens from before hashing hold the raw `token` and no `token_hash`. They
+/// can no longer be redeemed, would collide as null keys in the unique
+/// `token_hash` index, and their string `expires_at` is invisible to the TTL
+/// index, so they are deleted before the indexes are built.
+async fn remove_legacy_tokens(db: &Database) -> Result<(), mongodb::error::Error> {
+    for collection in ["refresh_tokens", "password_resets"] {
+        db.collection::<Document>(collection)
+            .delete_many(doc! { "token_hash": { "$exists": false } })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Moves the audit log to its current collection name. Renaming keeps the
+/// stored entries, and so their hashes, exactly as they were.
+async fn rename_audit_log(db: &Database) -> Result<(), mongodb::error::Error> {
+    let names = db.list_collection_names().await?;
+    if !names.iter().any(|n| n == audit::LEGACY_COLLECTION) {
+        return Ok(());
+    }
+    if names.iter().any(|n| n == audit::COLLECTION) {
+        eprintln!(
+            "Both {} and {} exist; leaving {} for manual review",
+            audit::LEGACY_COLLECTION,
+            audit::COLLECTION,
+            audit::LEGACY_COLLECTION
+        );
+        return Ok(());
+    }
+    db.client()
+        .database("admin")
+        .run_command(doc! {
+            "renameCollection": format!("{}.{}", db.name(), audit::LEGACY_COLLECTION),
+            "to": format!("{}.{}", db.name(), audit::COLLECTION),
+        })
+        .await?;
+    Ok(())
+}
+
+/// Fields no two accounts of a tenant may share.
+const USER_UNIQUE_FIELDS: [&str; 2] = ["email", "identifier"];
+
+/// The `(tenant_id, email)` and `(tenant_id, identifier)` indexes used to be
+/// plain ones; those are dropped so `ensure_indexes` can build them unique.
+/// Accounts that already share a value are listed, since the unique index
+/// cannot be built until they are merged or changed by hand.
+async fn prepare_unique_user_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let users = db.collection::<Document>("users");
+    let indexes: Vec<IndexModel> = users.list_indexes().await?.try_collect().await?;
+    for field in USER_UNIQUE_FIELDS {
+        let name = format!("tenant_id_1_{}_1", field);
+        let plain = indexes.iter().any(|index| {
+            let options = index.options.as_ref();
+            options.and_then(|o| o.name.as_deref()) == Some(name.as_str())
+                && options.and_then(|o| o.unique) != Some(true)
+        });
+        if plain {
+            users.drop_index(name.as_str()).await?;
+        }
+
+        let mut duplicates = users
+            .aggregate(vec![
+                doc! { "$group": {
+                    "_id": { "tenant_id": "$tenant_id", "value": format!("${}", field) },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$match": { "count": { "$gt": 1 } } },
+            ])
+            .await?;
+        while let Some(duplicate) = duplicates.try_next().await? {
+            eprintln!("Accounts share a {}: {}", field, duplicate.get_document("_id").unwrap_or(&Document::new()));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a write failed on a unique index.
+pub fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        *error.kind,
+        ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000
+    ) || matches!(*error.kind, ErrorKind::Command(ref command_error) if command_error.code == 11000)
+}
+
+/// Collections whose documents belong to one tenant.
+const TENANT_COLLECTIONS: [&str; 8] = [
+    "users",
+    "attendances",
+    "offices",
+    "departments",
+    "teams",
+    "webhooks",
+    audit::COLLECTION,
+    "user_imports",
+];
+
+/// Fills in fields added after documents were first written. Each step only
+/// touches documents that still lack the field, so this is cheap once done.
+async fn backfill(db: &Database) -> Result<(), mongodb::error::Error> {
+    // Data from before tenants existed becomes the default tenant's
+    let tenant_id = default_tenant(db).await?;
+    for collection in TENANT_COLLECTIONS {
+        db.collection::<Document>(collection)
+            .update_many(
+                doc! { "tenant_id": { "$exists": false } },
+                doc! { "$set": { "tenant_id": tenant_id } },
+            )
+            .await?;
+    }
+    audit::chain_unsealed(db).await?;
+
+    convert_string_timestamps(db).await?;
+
+    db.collection::<Document>("users")
+        .update_many(
+            doc! { "face_enrolled": { "$exists": false } },
+            vec![doc! {
+                "$set": {
+                    "face_enrolled": { "$gt": [{ "$size": { "$ifNull": ["$face_landmarks", []] } }, 0] }
+                }
+            }],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Check-ins used to store `timestamp` as an RFC 3339 string, which date
+/// range filters never match. Converts those, and the revisions copied from
+/// them, to BSON dates.
+async fn convert_string_timestamps(db: &Database) -> Result<(), mongodb::error::Error> {
+    let attendances = db.collection::<Document>("attendances");
+    let mut cursor = attendances
+        .find(doc! {
+            "$or": [
+                { "timestamp": { "$type": "string" } },
+                { "revisions.changed_at": { "$type": "string" } },
+                { "revisions.timestamp": { "$type": "string" } },
+            ]
+        })
+        .await?;
+    while let Some(mut attendance) = cursor.try_next().await? {
+        let Ok(id) = attendance.get_object_id("_id") else {
+            continue;
+        };
+        let mut set = Document::new();
+        if let Some(timestamp) = string_to_date(&attendance, "timestamp") {
+            set.insert("timestamp", timestamp);
+        }
+        if let Ok(revisions) = attendance.get_array_mut("revisions") {
+            for revision in revisions.iter_mut().filter_map(Bson::as_document_mut) {
+                for key in ["changed_at", "timestamp"] {
+                    if let Some(date) = string_to_date(revision, key) {
+                        revision.insert(key, date);
+                    }
+                }
+            }
+            set.insert("revisions", revisions.clone());
+        }
+        attendances.update_one(doc! { "_id": id }, doc! { "$set": set }).await?;
+    }
+    Ok(())
+}
+
+fn string_to_date(document: &Document, key: &str) -> Option<DateTime> {
+    let value = document.get_str(key).ok()?;
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(parsed) => Some(DateTime::from_millis(parsed.timestamp_millis())),
+        Err(_) => {
+            eprintln!("Cannot convert attendance {} {:?} to a date", key, value);
+            None
+        }
+    }
+}
+
+/// Returns the default tenant, creating it on first start with the
+/// passwordless settings that used to be deployment-wide.
+async fn default_tenant(db: &Database) -> Result<ObjectId, mongodb::error::Error> {
+    let enabled_roles = db
+        .collection::<Document>("settings")
+        .find_one(doc! { "_id": PASSWORDLESS_SETTINGS_ID })
+        .await?
+        .and_then(|settings| settings.get_array("enabled_roles").ok().cloned())
+        .unwrap_or_default();
+
+    let tenant = db
+        .collection::<Document>("tenants")
+        .find_one_and_update(
+            doc! { "slug": DEFAULT_TENANT_SLUG },
+            doc! {
+                "$setOnInsert": {
+                    "name": "Default",
+                    "active": true,
+                    "settings": { "passwordless": { "enabled_roles": enabled_roles } },
+                    "created_at": DateTime::now(),
+                }
+            },
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await?
+        .expect("upsert returns the document");
+    Ok(tenant.get_object_id("_id").expect("tenants have an ObjectId"))
+}
+
+/// Creates the indexes the handlers rely on. `create_index` is idempotent, so
+/// this runs on every start.
+async fn ensure_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let users = db.collection::<Document>("users");
+    // Soft-deleted users waiting for the retention purge
+    users
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "deleted_at": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+        )
+        .await?;
+    // Admin user list: search, filters, and the default sort by name
+    users
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "name": "text", "email": "text", "identifier": "text" })
+                .options(
+                    IndexOptions::builder()
+                        .name("users_search".to_string())
+                        .weights(doc! { "name": 3, "identifier": 2, "email": 1 })
+                        .default_language("none".to_string())
+                        .build(),
+                )
+                .build(),
+        )
+        .await?;
+    // Every query on tenant-owned collections starts with the tenant
+    for keys in [
+        doc! { "tenant_id": 1, "deactivated": 1, "name": 1 },
+        doc! { "tenant_id": 1, "role": 1, "name": 1 },
+        doc! { "tenant_id": 1, "office_id": 1, "name": 1 },
+        doc! { "tenant_id": 1, "department_id": 1, "name": 1 },
+        doc! { "tenant_id": 1, "team_id": 1, "name": 1 },
+        doc! { "tenant_id": 1, "face_enrolled": 1, "name": 1 },
+    ] {
+        users.create_index(IndexModel::builder().keys(keys).build()).await?;
+    }
+    // Also the last word when two requests create the same account at once
+    for field in USER_UNIQUE_FIELDS {
+        users
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "tenant_id": 1, field: 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+    }
+
+    db.collection::<Document>("tenants")
+        .create_index(unique_index("slug"))
+        .await?;
+
+    let attendances = db.collection::<Document>("attendances");
+    for keys in [
+        doc! { "tenant_id": 1, "timestamp": -1 },
+        doc! { "tenant_id": 1, "user_id": 1, "timestamp": -1 },
+    ] {
+        attendances.create_index(IndexModel::builder().keys(keys).build()).await?;
+    }
+
+    db.collection::<Document>("offices")
+        .create_index(IndexModel::builder().keys(doc! { "tenant_id": 1, "name": 1 }).build())
+        .await?;
+
+    let departments = db.collection::<Document>("departments");
+    for keys in [doc! { "tenant_id": 1, "name": 1 }, doc! { "supervisor_id": 1 }] {
+        departments.create_index(IndexModel::builder().keys(keys).build()).await?;
+    }
+    let teams = db.collection::<Document>("teams");
+    for keys in [doc! { "tenant_id": 1, "department_id": 1, "name": 1 }, doc! { "supervisor_id": 1 }] {
+        teams.create_index(IndexModel::builder().keys(keys).build()).await?;
+    }
+
+    let audit_log = db.collection::<Document>(audit::COLLECTION);
+    // One entry per position in a tenant's hash chain. Partial, because
+    // entries from before chaining get their `seq` in `backfill`.
+    audit_log
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "tenant_id": 1, "seq": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! { "seq": { "$exists": true } })
+                        .build(),
+                )
+                .build(),
+        )
+        .await?;
+    for keys in [
+        doc! { "tenant_id": 1, "target_id": 1, "created_at": -1 },
+        doc! { "tenant_id": 1, "actor_id": 1, "created_at": -1 },
+        doc! { "tenant_id": 1, "action": 1, "created_at": -1 },
+    ] {
+        audit_log.create_index(IndexModel::builder().keys(keys).build()).await?;
+    }
+
+    let refresh_tokens = db.collection::<Document>("refresh_tokens");
+    refresh_tokens.create_index(unique_index("token_hash")).await?;
+    refresh_tokens
+        .create_index(IndexModel::builder().keys(doc! { "family_id": 1 }).build())
+        .await?;
+    refresh_tokens.create_index(expiry_index()).await?;
+
+    let verifications = db.collection::<Document>("email_verifications");
+    verifications.create_index(unique_index("token_hash")).await?;
+    verifications.create_index(expiry_index()).await?;
+
+    let resets = db.collection::<Document>("password_resets");
+    resets.create_index(unique_index("token_hash")).await?;
+    resets.create_index(expiry_index()).await?;
+
+    let login_codes = db.collection::<Document>("login_codes");
+    login_codes
+        .create_index(IndexModel::builder().keys(doc! { "user_id": 1 }).build())
+        .await?;
+    login_codes.create_index(unique_index("link_token_hash")).await?;
+    login_codes.create_index(expiry_index()).await?;
+
+    let jobs = db.collection::<Document>("jobs");
+    jobs.create_index(
+        IndexModel::builder()
+            .keys(doc! { "status": 1, "run_at": 1 })
+            .build(),
+    )
+    .await?;
+    jobs.create_index(expiry_index()).await?;
+
+    let subscriptions = db.collection::<Document>("push_subscriptions");
+    subscriptions.create_index(unique_index("endpoint")).await?;
+    subscriptions
+        .create_index(IndexModel::builder().keys(doc! { "user_id": 1 }).build())
+        .await?;
+
+    let reminders = db.collection::<Document>("sent_reminders");
+    reminders
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "kind": 1, "date": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        )
+        .await?;
+    reminders.create_index(expiry_index()).await?;
+
+    db.collection::<Document>("webhooks")
+        .create_index(IndexModel::builder().keys(doc! { "tenant_id": 1, "events": 1 }).build())
+        .await?;
+
+    let deliveries = db.collection::<Document>("webhook_deliveries");
+    deliveries
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "webhook_id": 1, "created_at": -1 })
+                .build(),
+        )
+        .await?;
+    deliveries.create_index(expiry_index()).await?;
+
+    db.collection::<Document>("oidc_states")
+        .create_index(expiry_index())
+        .await?;
+
+    db.collection::<Document>("user_imports")
+        .create_index(expiry_index())
+        .await?;
+
+    Ok(())
+}
+
+/// Addresses are matched exactly, so ones saved before they were normalized
+/// are brought to the same trimmed, lowercase form.
+async fn lowercase_emails(db: &Database) -> Result<(), mongodb::error::Error> {
+    for (collection, field) in [
+        ("users", "email"),
+        ("users", "pending_email"),
+        ("email_verifications", "email"),
+    ] {
+        db.collection::<Document>(collection)
+            .update_many(
+                doc! { field: { "$type": "string", "$regex": "[A-Z]|^\\s|\\s$" } },
+                vec![doc! {
+                    "$set": { field: { "$toLower": { "$trim": { "input": format!("${}", field) } } } }
+                }],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+fn unique_index(field: &str) -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! { field: 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build()
+}
+
+/// TTL index: MongoDB removes documents once `expires_at` has passed.
+fn expiry_index() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(
+            IndexOptions::builder()
+                .expire_after(Duration::from_secs(0))
+                .build(),
+        )
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::attendance::Attendance;
+
+    async fn test_db() -> Database {
+        let url = std::env::var("MONGODB_TEST_URL").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+        Client::with_uri_str(url)
+            .await
+            .unwrap()
+            .database(&format!("vexis_test_{}", ObjectId::new()))
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_prepare_removes_legacy_tokens() {
+        let db = test_db().await;
+        for collection in ["refresh_tokens", "password_resets"] {
+            for token in ["first", "second"] {
+                db.collection::<Document>(collection)
+                    .insert_one(doc! {
+                        "user_id": ObjectId::new(),
+                        "email": "legacy@example.com",
+                        "token": token,
+                        "expires_at": "2026-10-19T00:00:00Z",
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+
+        prepare(&db).await.unwrap();
+
+        for collection in ["refresh_tokens", "password_resets"] {
+            let left = db.collection::<Document>(collection).count_documents(doc! {}).await.unwrap();
+            assert_eq!(left, 0, "{}", collection);
+        }
+        db.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_backfill_converts_string_timestamps() {
+        let db = test_db().await;
+        let attendances = db.collection::<Document>("attendances");
+        let id = attendances
+            .insert_one(doc! {
+                "tenant_id": ObjectId::new(),
+                "user_id": ObjectId::new(),
+                "timestamp": "2026-10-19T01:02:03.456789Z",
+                "type": "In",
+                "location": { "type": "Point", "coordinates": [106.8, -6.2] },
+                "face_verified": true,
+                "revisions": [{
+                    "action": "edit",
+                    "reason": "wrong time",
+                    "changed_by": ObjectId::new(),
+                    "changed_at": "2026-10-19T02:00:00Z",
+                    "timestamp": "2026-10-19T00:00:00Z",
+                    "type": "In",
+                }],
+            })
+            .await
+            .unwrap()
+            .inserted_id;
+
+        prepare(&db).await.unwrap();
+
+        let stored = attendances.find_one(doc! { "_id": &id }).await.unwrap().unwrap();
+        assert!(matches!(stored.get("timestamp"), Some(Bson::DateTime(_))));
+        let revision = stored.get_array("revisions").unwrap()[0].as_document().unwrap();
+        assert!(matches!(revision.get("changed_at"), Some(Bson::DateTime(_))));
+        assert!(matches!(revision.get("timestamp"), Some(Bson::DateTime(_))));
+        // Date range filters now match, and the record still decodes
+        let attendance = db
+            .collection::<Attendance>("attendances")
+            .find_one(doc! { "timestamp": { "$gte": DateTime::parse_rfc3339_str("2026-10-19T00:00:00Z").unwrap() } })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(attendance.timestamp.to_rfc3339(), "2026-10-19T01:02:03.456+00:00");
+
+        db.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_backfill_lowercases_emails() {
+        let db = test_db().await;
+        let users = db.collection::<Document>("users");
+        let id = users
+            .insert_one(doc! {
+                "tenant_id": ObjectId::new(),
+                "email": " Budi.Santoso@Example.COM",
+                "pending_email": "Budi@New.Example.com",
+            })
+            .await
+            .unwrap()
+            .inserted_id;
+
+        prepare(&db).await.unwrap();
+
+        let stored = users.find_one(doc! { "_id": &id }).await.unwrap().unwrap();
+        assert_eq!(stored.get_str("email").unwrap(), "budi.santoso@example.com");
+        assert_eq!(stored.get_str("pending_email").unwrap(), "budi@new.example.com");
+        db.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_prepare_renames_the_audit_log() {
+        let db = test_db().await;
+        let tenant_id = ObjectId::new();
+        db.collection::<Document>(audit::LEGACY_COLLECTION)
+            .insert_one(doc! {
+                "tenant_id": tenant_id,
+                "action": "office.create",
+                "target_type": "office",
+                "created_at": DateTime::now(),
+            })
+            .await
+            .unwrap();
+
+        prepare(&db).await.unwrap();
+
+        let names = db.list_collection_names().await.unwrap();
+        assert!(!names.iter().any(|n| n == audit::LEGACY_COLLECTION));
+        let report = audit::verify_chain(&db, tenant_id).await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.entries, 1);
+        db.drop().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_prepare_makes_user_email_and_identifier_unique() {
+        let db = test_db().await;
+        let users = db.collection::<Document>("users");
+        // As built by earlier versions
+        users
+            .create_index(IndexModel::builder().keys(doc! { "tenant_id": 1, "email": 1 }).build())
+            .await
+            .unwrap();
+
+        prepare(&db).await.unwrap();
+
+        let tenant_id = ObjectId::new();
+        users
+            .insert_one(doc! { "tenant_id": tenant_id, "email": "budi@example.com", "identifier": "1001" })
+            .await
+            .unwrap();
+        let same_email = users
+            .insert_one(doc! { "tenant_id": tenant_id, "email": "budi@example.com", "identifier": "1002" })
+            .await
+            .unwrap_err();
+        assert!(is_duplicate_key(&same_email));
+        let same_identifier = users
+            .insert_one(doc! { "tenant_id": tenant_id, "email": "budi2@example.com", "identifier": "1001" })
+            .await
+            .unwrap_err();
+        assert!(is_duplicate_key(&same_identifier));
+        // Other tenants may reuse both
+        users
+            .insert_one(doc! { "tenant_id": ObjectId::new(), "email": "budi@example.com", "identifier": "1001" })
+            .await
+            .unwrap();
+        db.drop().await.unwrap();
+    }
 }
@@ -0,0 +1,83 @@
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (port 587).
+    StartTls,
+    /// TLS from the first byte (port 465).
+    Wrapper,
+    /// No encryption, for local relays such as MailHog.
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub enum TransportConfig {
+    Resend {
+        api_key: String,
+    },
+    Smtp {
+        host: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+        tls: SmtpTls,
+    },
+    /// Writes every message to a directory and logs it instead of sending.
+    File {
+        dir: PathBuf,
+    },
+}
+
+/// Outgoing email settings, selected by `EMAIL_TRANSPORT`.
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub from: String,
+    pub transport: TransportConfig,
+}
+
+impl EmailConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let required = |name: &str| env::var(name).map_err(|_| format!("{} must be set", name));
+        let transport = env::var("EMAIL_TRANSPORT").unwrap_or_else(|_| "resend".to_string());
+
+        let transport = match transport.as_str() {
+            "resend" => TransportConfig::Resend {
+                api_key: required("RESEND_API_KEY")
+                    .map_err(|e| format!("{} (or use EMAIL_TRANSPORT=file for development)", e))?,
+            },
+            "smtp" => {
+                let tls = match env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string()).as_str() {
+                    "starttls" => SmtpTls::StartTls,
+                    "tls" => SmtpTls::Wrapper,
+                    "none" => SmtpTls::None,
+                    other => return Err(format!("unknown SMTP_TLS mode {}", other)),
+                };
+                let default_port = match tls {
+                    SmtpTls::StartTls => 587,
+                    SmtpTls::Wrapper => 465,
+                    SmtpTls::None => 25,
+                };
+                TransportConfig::Smtp {
+                    host: required("SMTP_HOST")?,
+                    port: match env::var("SMTP_PORT") {
+                        Ok(v) => v.parse().map_err(|_| format!("invalid SMTP_PORT {}", v))?,
+                        Err(_) => default_port,
+                    },
+                    username: env::var("SMTP_USERNAME").ok(),
+                    password: env::var("SMTP_PASSWORD").ok(),
+                    tls,
+                }
+            }
+            "file" => TransportConfig::File {
+                dir: env::var("EMAIL_FILE_DIR").unwrap_or_else(|_| "mail".to_string()).into(),
+            },
+            other => return Err(format!("unknown EMAIL_TRANSPORT {}", other)),
+        };
+
+        Ok(Self {
+            from: env::var("EMAIL_FROM").unwrap_or_else(|_| "Vexis <onboarding@resend.dev>".to_string()),
+            transport,
+        })
+    }
+}
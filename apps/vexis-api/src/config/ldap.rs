@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::env;
+
+/// Settings for authenticating against LDAP / Active Directory.
+#[derive(Debug, Clone)]
+pub struct LdapConfig {
+    pub url: String,
+    pub starttls: bool,
+    /// Service account used to look up the user's DN.
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub base_dn: String,
+    /// Search filter; `{login}` is replaced with the escaped login.
+    pub user_filter: String,
+    pub name_attr: String,
+    pub email_attr: String,
+    pub identifier_attr: String,
+    pub group_attr: String,
+    /// Group CN (or full DN) to Vexis role.
+    pub role_mapping: HashMap<String, String>,
+    pub default_role: String,
+}
+
+impl LdapConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let required = |name: &str| env::var(name).map_err(|_| format!("{} must be set", name));
+        let optional = |name: &str, default: &str| env::var(name).unwrap_or_else(|_| default.to_string());
+
+        let role_mapping = env::var("LDAP_ROLE_MAPPING")
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(group, role)| (group.trim().to_lowercase(), role.trim().to_string()))
+            .collect();
+
+        Ok(Self {
+            url: required("LDAP_URL")?,
+            starttls: optional("LDAP_STARTTLS", "false") == "true",
+            bind_dn: required("LDAP_BIND_DN")?,
+            bind_password: required("LDAP_BIND_PASSWORD")?,
+            base_dn: required("LDAP_BASE_DN")?,
+            user_filter: optional(
+                "LDAP_USER_FILTER",
+                "(|(uid={login})(mail={login})(sAMAccountName={login}))",
+            ),
+            name_attr: optional("LDAP_NAME_ATTR", "cn"),
+            email_attr: optional("LDAP_EMAIL_ATTR", "mail"),
+            identifier_attr: optional("LDAP_IDENTIFIER_ATTR", "employeeNumber"),
+            group_attr: optional("LDAP_GROUP_ATTR", "memberOf"),
+            role_mapping,
+            default_role: optional("LDAP_DEFAULT_ROLE", "user"),
+        })
+    }
+}
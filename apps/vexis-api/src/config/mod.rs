@@ -1 +1,8 @@
 pub mod db;
+pub mod email;
+pub mod jwt;
+pub mod oidc;
+pub mod ldap;
+pub mod password;
+pub mod push;
+pub mod retention;
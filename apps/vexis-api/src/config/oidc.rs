@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::env;
+
+/// Settings for single sign-on against an OpenID Connect provider.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    /// ID token claim holding the user's NIP/NIM.
+    pub identifier_claim: String,
+    pub groups_claim: String,
+    /// IdP group name to Vexis role.
+    pub role_mapping: HashMap<String, String>,
+    pub default_role: String,
+    /// Also apply the mapped role to existing accounts on every login, not
+    /// only to accounts created by SSO.
+    pub role_sync: bool,
+}
+
+impl OidcConfig {
+    /// Returns `Ok(None)` when `OIDC_ISSUER_URL` is unset, i.e. SSO is disabled.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(issuer_url) = env::var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+        let required = |name: &str| env::var(name).map_err(|_| format!("{} must be set", name));
+
+        let role_mapping = env::var("OIDC_ROLE_MAPPING")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(group, role)| (group.trim().to_string(), role.trim().to_string()))
+            .collect();
+
+        Ok(Some(Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id: required("OIDC_CLIENT_ID")?,
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_uri: required("OIDC_REDIRECT_URI")?,
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid email profile".to_string()),
+            identifier_claim: env::var("OIDC_IDENTIFIER_CLAIM")
+                .unwrap_or_else(|_| "preferred_username".to_string()),
+            groups_claim: env::var("OIDC_GROUPS_CLAIM").unwrap_or_else(|_| "groups".to_string()),
+            role_mapping,
+            default_role: env::var("OIDC_DEFAULT_ROLE").unwrap_or_else(|_| "user".to_string()),
+            role_sync: env::var("OIDC_ROLE_SYNC").map(|v| v == "true").unwrap_or(false),
+        }))
+    }
+}
@@ -0,0 +1,49 @@
+use std::env;
+
+/// Rules a new password must satisfy, see `utils::password::check_policy`.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Number of previous password hashes a new password may not reuse.
+    pub history_size: usize,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_lowercase: true,
+            require_uppercase: false,
+            require_digit: true,
+            require_symbol: false,
+            history_size: 5,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Reads `PASSWORD_MIN_LENGTH`, `PASSWORD_REQUIRE_{LOWERCASE,UPPERCASE,DIGIT,SYMBOL}`
+    /// and `PASSWORD_HISTORY_SIZE`, falling back to the defaults above.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let number = |name: &str, default: usize| {
+            env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        let flag = |name: &str, default: bool| {
+            env::var(name).map(|v| v == "true").unwrap_or(default)
+        };
+
+        Self {
+            min_length: number("PASSWORD_MIN_LENGTH", defaults.min_length),
+            require_lowercase: flag("PASSWORD_REQUIRE_LOWERCASE", defaults.require_lowercase),
+            require_uppercase: flag("PASSWORD_REQUIRE_UPPERCASE", defaults.require_uppercase),
+            require_digit: flag("PASSWORD_REQUIRE_DIGIT", defaults.require_digit),
+            require_symbol: flag("PASSWORD_REQUIRE_SYMBOL", defaults.require_symbol),
+            history_size: number("PASSWORD_HISTORY_SIZE", defaults.history_size),
+        }
+    }
+}
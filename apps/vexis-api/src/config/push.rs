@@ -0,0 +1,63 @@
+use chrono::{NaiveTime, Weekday};
+use std::env;
+
+/// Web Push (VAPID) credentials.
+#[derive(Debug, Clone)]
+pub struct PushConfig {
+    /// Base64url private key as printed by `web-push generate-vapid-keys`.
+    pub vapid_private_key: String,
+    /// Contact for push services, e.g. `mailto:it@kantor.go.id`.
+    pub subject: String,
+}
+
+impl PushConfig {
+    /// Returns `None` when `VAPID_PRIVATE_KEY` is unset, which disables push.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(vapid_private_key) = env::var("VAPID_PRIVATE_KEY") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            vapid_private_key,
+            subject: env::var("VAPID_SUBJECT").map_err(|_| "VAPID_SUBJECT must be set".to_string())?,
+        }))
+    }
+}
+
+/// When attendance reminders go out. Times are WIB, like the rest of the
+/// attendance logic.
+#[derive(Debug, Clone)]
+pub struct ReminderConfig {
+    pub shift_start: NaiveTime,
+    pub shift_end: NaiveTime,
+    /// Check-in reminders are sent this many minutes before `shift_start`.
+    pub before_start_minutes: i64,
+    /// Check-out reminders are sent this many minutes after `shift_end`.
+    pub after_end_minutes: i64,
+    pub workdays: Vec<Weekday>,
+}
+
+impl ReminderConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let time = |name: &str, default: &str| {
+            let value = env::var(name).unwrap_or_else(|_| default.to_string());
+            NaiveTime::parse_from_str(&value, "%H:%M").map_err(|_| format!("invalid {} {}", name, value))
+        };
+        let minutes = |name: &str, default: i64| {
+            env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        let workdays = env::var("REMINDER_WORKDAYS")
+            .unwrap_or_else(|_| "mon,tue,wed,thu,fri".to_string())
+            .split(',')
+            .map(|day| day.trim().parse::<Weekday>().map_err(|_| format!("invalid REMINDER_WORKDAYS day {}", day)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            shift_start: time("SHIFT_START", "08:00")?,
+            shift_end: time("SHIFT_END", "17:00")?,
+            before_start_minutes: minutes("REMINDER_BEFORE_START_MINUTES", 15),
+            after_end_minutes: minutes("REMINDER_AFTER_END_MINUTES", 30),
+            workdays,
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use std::env;
+
+/// How long soft-deleted data is kept before `utils::retention` purges it.
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    pub deleted_user_days: i64,
+}
+
+/// A hundred years; larger values do not fit a `chrono::Duration`.
+const MAX_DELETED_USER_DAYS: i64 = 36_500;
+
+impl RetentionConfig {
+    /// Reads `DELETED_USER_RETENTION_DAYS` (default 365, at most 36500).
+    pub fn from_env() -> Result<Self, String> {
+        let deleted_user_days = match env::var("DELETED_USER_RETENTION_DAYS") {
+            Ok(value) => value
+                .parse()
+                .ok()
+                .filter(|days: &i64| (0..=MAX_DELETED_USER_DAYS).contains(days))
+                .ok_or_else(|| format!("invalid DELETED_USER_RETENTION_DAYS {}", value))?,
+            Err(_) => 365,
+        };
+        Ok(Self { deleted_user_days })
+    }
+}
@@ -1,12 +1,22 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Extension, Path, Query, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use crate::AppState;
-use crate::models::attendance::Attendance;
-use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::attendance::{Attendance, AttendanceRevision, DeviceInfo, GeoPoint};
+use crate::models::office::Office;
+use crate::models::user::{OfficeLocation, User};
+use crate::utils::audit::AuditEvent;
+use crate::utils::geofence::{calculate_distance, DEFAULT_FENCE};
+use crate::utils::jwt::Claims;
+use crate::utils::org::{restrict_to_users, user_ids};
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::ReturnDocument;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use futures::stream::TryStreamExt;
@@ -19,6 +29,10 @@ pub struct AttendanceQuery {
     pub start_date: Option<String>,
     pub end_date: Option<String>,
     pub user_id: Option<String>,
+    pub department_id: Option<String>,
+    pub team_id: Option<String>,
+    /// Voided records are left out unless this is `true`.
+    pub include_voided: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -36,45 +50,607 @@ pub struct AttendanceAdminDetail {
     pub user_id: ObjectId,
     pub user_name: String,
     pub user_email: String,
+    #[serde(deserialize_with = "chrono_datetime_as_bson_datetime::deserialize")]
     pub timestamp: chrono::DateTime<Utc>,
     pub r#type: String,
     pub latitude: f64,
     pub longitude: f64,
+    #[serde(default)]
+    pub manual: bool,
+    #[serde(default)]
+    pub edited: bool,
+    #[serde(default)]
+    pub voided: bool,
+}
+
+impl AttendanceAdminDetail {
+    /// How an admin changed the record, for the CSV export.
+    fn alterations(&self) -> String {
+        [(self.manual, "added"), (self.edited, "edited"), (self.voided, "voided")]
+            .iter()
+            .filter(|(altered, _)| *altered)
+            .map(|(_, label)| *label)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 pub async fn list_attendance(
     State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<AttendanceQuery>,
+) -> impl IntoResponse {
+    attendance_page(&state, tenant_id, &query, None).await
+}
+
+pub async fn export_attendance_csv(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
     Query(query): Query<AttendanceQuery>,
 ) -> impl IntoResponse {
+    let response = attendance_csv(&state, tenant_id, &query, None).await;
+    if response.status().is_success() {
+        export_event(tenant_id, &claims, "admin", &query).record(&state.db, &client).await;
+    }
+    response
+}
+
+/// Audit entry for a successful CSV export. `scope` is "admin" or "team".
+pub fn export_event(tenant_id: ObjectId, claims: &Claims, scope: &str, query: &AttendanceQuery) -> AuditEvent {
+    AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "attendance.export", "attendance", None).details(doc! {
+        "scope": scope,
+        "start_date": &query.start_date,
+        "end_date": &query.end_date,
+        "user_id": &query.user_id,
+        "department_id": &query.department_id,
+        "team_id": &query.team_id,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct CreateAttendanceRequest {
+    pub user_id: String,
+    pub timestamp: chrono::DateTime<Utc>,
+    pub r#type: String,
+    pub reason: String,
+    /// Defaults to the user's office.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAttendanceRequest {
+    pub timestamp: Option<chrono::DateTime<Utc>>,
+    pub r#type: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct VoidAttendanceRequest {
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct RevisionResponse {
+    pub action: String,
+    pub reason: String,
+    pub changed_by: String,
+    pub changed_at: chrono::DateTime<Utc>,
+    pub timestamp: Option<chrono::DateTime<Utc>>,
+    pub r#type: Option<String>,
+}
+
+/// A single attendance record with its revision history.
+#[derive(Serialize)]
+pub struct AttendanceRecordResponse {
+    pub id: String,
+    pub user_id: String,
+    pub timestamp: chrono::DateTime<Utc>,
+    pub r#type: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub face_verified: bool,
+    pub manual: bool,
+    pub voided: bool,
+    pub revisions: Vec<RevisionResponse>,
+}
+
+impl From<Attendance> for AttendanceRecordResponse {
+    fn from(attendance: Attendance) -> Self {
+        Self {
+            id: attendance.id.unwrap().to_hex(),
+            user_id: attendance.user_id.to_hex(),
+            timestamp: attendance.timestamp,
+            r#type: attendance.r#type,
+            latitude: attendance.location.coordinates.get(1).copied().unwrap_or_default(),
+            longitude: attendance.location.coordinates.first().copied().unwrap_or_default(),
+            face_verified: attendance.face_verified,
+            manual: attendance.manual,
+            voided: attendance.voided,
+            revisions: attendance
+                .revisions
+                .into_iter()
+                .map(|revision| RevisionResponse {
+                    action: revision.action,
+                    reason: revision.reason,
+                    changed_by: revision.changed_by.to_hex(),
+                    changed_at: revision.changed_at,
+                    timestamp: revision.timestamp,
+                    r#type: revision.r#type,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Checks the values an admin sets and returns the trimmed reason, which
+/// every manual change needs.
+fn validate_change(
+    timestamp: Option<&chrono::DateTime<Utc>>,
+    r#type: Option<&str>,
+    reason: &str,
+) -> Result<String, &'static str> {
+    if timestamp.is_some_and(|timestamp| *timestamp > Utc::now()) {
+        return Err("Timestamp is in the future");
+    }
+    if r#type.is_some_and(|r#type| r#type != "In" && r#type != "Out") {
+        return Err("Type must be In or Out");
+    }
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("Reason is required");
+    }
+    if reason.chars().count() > 500 {
+        return Err("Reason must be at most 500 characters");
+    }
+    Ok(reason.to_string())
+}
+
+fn audit_fields(attendance: &Attendance) -> Document {
+    doc! {
+        "timestamp": attendance.timestamp.to_rfc3339(),
+        "type": &attendance.r#type,
+        "voided": attendance.voided,
+    }
+}
+
+/// Pipeline update that applies `set` and appends a revision holding the
+/// record's values from before the update, in one atomic step.
+fn revise(set: Document, action: &str, reason: &str, admin_id: ObjectId) -> Vec<Document> {
+    let mut set = set;
+    set.insert(
+        "revisions",
+        doc! {
+            "$concatArrays": [
+                { "$ifNull": ["$revisions", []] },
+                [{
+                    "action": action,
+                    "reason": { "$literal": reason },
+                    "changed_by": admin_id,
+                    "changed_at": DateTime::now(),
+                    "timestamp": "$timestamp",
+                    "type": "$type",
+                }],
+            ]
+        },
+    );
+    vec![doc! { "$set": set }]
+}
+
+/// Where a manual entry without coordinates is placed: the user's office,
+/// or their own office location if they have none.
+async fn office_coordinates(state: &AppState, user: &User) -> mongodb::error::Result<Vec<f64>> {
+    if let Some(office_id) = user.office_id {
+        let office = state
+            .db
+            .collection::<Office>("offices")
+            .find_one(doc! { "_id": office_id, "tenant_id": user.tenant_id })
+            .await?;
+        if let Some(office) = office {
+            return Ok(office.location.coordinates);
+        }
+    }
+    Ok(user.office_location.coordinates.clone())
+}
+
+/// Adds a record for a user who could not check in themselves.
+pub async fn create_attendance(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<CreateAttendanceRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(user_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&payload.user_id)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response();
+    };
+    let reason = match validate_change(Some(&payload.timestamp), Some(&payload.r#type), &payload.reason) {
+        Ok(reason) => reason,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    // Deactivated accounts keep their history but get no new records
+    let user = match state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id, "tenant_id": tenant_id, "deactivated": { "$ne": true } })
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let coordinates = match (payload.latitude, payload.longitude) {
+        (Some(lat), Some(long)) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&long) => vec![long, lat],
+        (None, None) => match office_coordinates(&state, &user).await {
+            Ok(coordinates) => coordinates,
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        },
+        _ => return (StatusCode::BAD_REQUEST, "Invalid coordinates").into_response(),
+    };
+
+    let mut attendance = Attendance {
+        id: None,
+        tenant_id,
+        user_id,
+        timestamp: payload.timestamp,
+        r#type: payload.r#type,
+        location: GeoPoint {
+            r#type: "Point".to_string(),
+            coordinates,
+        },
+        face_verified: false,
+        office_id: None,
+        distance_m: None,
+        face_similarity: None,
+        device: None,
+        manual: true,
+        voided: false,
+        revisions: vec![AttendanceRevision {
+            action: "create".to_string(),
+            reason: reason.clone(),
+            changed_by: admin_id,
+            changed_at: Utc::now(),
+            timestamp: None,
+            r#type: None,
+        }],
+    };
+
+    match state.db.collection::<Attendance>("attendances").insert_one(&attendance).await {
+        Ok(result) => attendance.id = result.inserted_id.as_object_id(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save attendance").into_response(),
+    }
+
+    AuditEvent::new(tenant_id, Some(admin_id), "attendance.create", "attendance", attendance.id)
+        .after(audit_fields(&attendance))
+        .details(doc! { "user_id": user_id, "reason": &reason })
+        .record(&state.db, &client)
+        .await;
+
+    (StatusCode::CREATED, Json(AttendanceRecordResponse::from(attendance))).into_response()
+}
+
+/// Corrects the time or type of a record. The previous values are kept in
+/// its revisions.
+pub async fn update_attendance(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateAttendanceRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(attendance_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid attendance ID").into_response();
+    };
+    if payload.timestamp.is_none() && payload.r#type.is_none() {
+        return (StatusCode::BAD_REQUEST, "Nothing to change").into_response();
+    }
+    let reason = match validate_change(payload.timestamp.as_ref(), payload.r#type.as_deref(), &payload.reason) {
+        Ok(reason) => reason,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let mut set = Document::new();
+    if let Some(timestamp) = &payload.timestamp {
+        set.insert("timestamp", DateTime::from_chrono(*timestamp));
+    }
+    if let Some(r#type) = &payload.r#type {
+        set.insert("type", r#type);
+    }
+
+    let event = AuditEvent::new(tenant_id, Some(admin_id), "attendance.update", "attendance", Some(attendance_id));
+    let update = revise(set, "edit", &reason, admin_id);
+    revise_attendance(&state, tenant_id, &client, attendance_id, update, event, &reason).await
+}
+
+/// Takes a record out of every report while keeping it for the history.
+pub async fn void_attendance(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<VoidAttendanceRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(attendance_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid attendance ID").into_response();
+    };
+    let reason = match validate_change(None, None, &payload.reason) {
+        Ok(reason) => reason,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    let event = AuditEvent::new(tenant_id, Some(admin_id), "attendance.void", "attendance", Some(attendance_id));
+    let update = revise(doc! { "voided": true }, "void", &reason, admin_id);
+    revise_attendance(&state, tenant_id, &client, attendance_id, update, event, &reason).await
+}
+
+/// Applies a `revise` update to a record that is not voided yet, and
+/// records `event` with the fields that changed.
+async fn revise_attendance(
+    state: &AppState,
+    tenant_id: ObjectId,
+    client: &ClientInfo,
+    attendance_id: ObjectId,
+    update: Vec<Document>,
+    event: AuditEvent,
+    reason: &str,
+) -> Response {
+    let attendance_col = state.db.collection::<Attendance>("attendances");
+    let filter = doc! { "_id": attendance_id, "tenant_id": tenant_id };
+
+    let mut active = filter.clone();
+    active.insert("voided", doc! { "$ne": true });
+    let attendance = match attendance_col
+        .find_one_and_update(active, update)
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(attendance)) => attendance,
+        Ok(None) => {
+            return match attendance_col.count_documents(filter).await {
+                Ok(0) => (StatusCode::NOT_FOUND, "Attendance not found").into_response(),
+                Ok(_) => (StatusCode::CONFLICT, "Attendance is voided").into_response(),
+                Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+            };
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let revision = attendance.revisions.last().expect("the update appends a revision");
+    let previous = doc! {
+        "timestamp": revision.timestamp.map(|timestamp| timestamp.to_rfc3339()),
+        "type": &revision.r#type,
+        "voided": false,
+    };
+    event
+        .changes(&previous, &audit_fields(&attendance))
+        .details(doc! { "user_id": attendance.user_id, "reason": reason })
+        .record(&state.db, client)
+        .await;
+
+    Json(AttendanceRecordResponse::from(attendance)).into_response()
+}
+
+/// Fence a record is measured against.
+#[derive(Serialize)]
+pub struct FenceResponse {
+    /// `None` for the default fence.
+    pub office_id: Option<String>,
+    pub office_name: Option<String>,
+    pub lat: f64,
+    pub long: f64,
+    pub radius_m: f64,
+}
+
+/// Everything needed to investigate one record.
+#[derive(Serialize)]
+pub struct AttendanceDetailResponse {
+    #[serde(flatten)]
+    pub record: AttendanceRecordResponse,
+    pub user_name: String,
+    pub user_email: String,
+    pub fence: FenceResponse,
+    /// Measured at check-in, or computed now for records without evidence.
+    pub distance_m: f64,
+    pub within_fence: bool,
+    pub face_similarity: Option<f32>,
+    pub device: Option<DeviceInfo>,
+    /// The user's other records on the same day (WIB), oldest first,
+    /// voided ones included.
+    pub same_day: Vec<AttendanceRecordResponse>,
+}
+
+/// The fence used at check-in: the recorded office, or the default fence
+/// for check-ins with evidence but no office. Manual and older records have
+/// neither, so they are measured against the user's current office.
+async fn fence_for(
+    state: &AppState,
+    attendance: &Attendance,
+    user: Option<&User>,
+) -> mongodb::error::Result<FenceResponse> {
+    let office_id = match attendance.office_id {
+        Some(office_id) => Some(office_id),
+        None if attendance.distance_m.is_some() => None,
+        None => user.and_then(|user| user.office_id),
+    };
+    if let Some(office_id) = office_id {
+        let office = state
+            .db
+            .collection::<Office>("offices")
+            .find_one(doc! { "_id": office_id, "tenant_id": attendance.tenant_id })
+            .await?;
+        if let Some(office) = office {
+            return Ok(FenceResponse {
+                office_id: Some(office_id.to_hex()),
+                office_name: Some(office.name),
+                lat: office.location.coordinates[1],
+                long: office.location.coordinates[0],
+                radius_m: office.radius_m,
+            });
+        }
+    }
+    let ([long, lat], radius_m) = DEFAULT_FENCE;
+    Ok(FenceResponse { office_id: None, office_name: None, lat, long, radius_m })
+}
+
+pub async fn get_attendance(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(attendance_id) = ObjectId::parse_str(&id) else {
+        return (StatusCode::BAD_REQUEST, "Invalid attendance ID").into_response();
+    };
+    let attendance_col = state.db.collection::<Attendance>("attendances");
+
+    let mut attendance = match attendance_col.find_one(doc! { "_id": attendance_id, "tenant_id": tenant_id }).await {
+        Ok(Some(attendance)) => attendance,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Attendance not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let user = match state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": attendance.user_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let fence = match fence_for(&state, &attendance, user.as_ref()).await {
+        Ok(fence) => fence,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    let distance_m = attendance.distance_m.unwrap_or_else(|| {
+        let center = OfficeLocation {
+            r#type: "Point".to_string(),
+            coordinates: vec![fence.long, fence.lat],
+        };
+        let location = OfficeLocation {
+            r#type: "Point".to_string(),
+            coordinates: attendance.location.coordinates.clone(),
+        };
+        calculate_distance(&location, &center)
+    });
+
+    // WIB offset (UTC+7)
+    let wib = FixedOffset::east_opt(7 * 3600).unwrap();
+    let day_start = attendance
+        .timestamp
+        .with_timezone(&wib)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_local_timezone(wib)
+        .unwrap()
+        .with_timezone(&Utc);
+    let day_end = day_start + chrono::Duration::days(1);
+
+    let mut cursor = match attendance_col
+        .find(doc! {
+            "tenant_id": tenant_id,
+            "user_id": attendance.user_id,
+            "_id": { "$ne": attendance_id },
+            "timestamp": { "$gte": DateTime::from_chrono(day_start), "$lt": DateTime::from_chrono(day_end) },
+        })
+        .sort(doc! { "timestamp": 1 })
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    let mut same_day = Vec::new();
+    while let Ok(Some(neighbour)) = cursor.try_next().await {
+        same_day.push(AttendanceRecordResponse::from(neighbour));
+    }
+
+    let (user_name, user_email) = match user {
+        Some(user) => (user.name, user.email),
+        None => ("(deleted user)".to_string(), String::new()),
+    };
+
+    Json(AttendanceDetailResponse {
+        user_name,
+        user_email,
+        within_fence: distance_m <= fence.radius_m,
+        fence,
+        distance_m,
+        face_similarity: attendance.face_similarity,
+        device: attendance.device.take(),
+        same_day,
+        record: AttendanceRecordResponse::from(attendance),
+    })
+    .into_response()
+}
+
+/// Joins the attendance owner as `user_info`. The tenant is matched again on
+/// the user side so a stray `user_id` can never pull in another tenant's user.
+fn user_lookup() -> Document {
+    doc! {
+        "$lookup": {
+            "from": "users",
+            "let": { "uid": "$user_id", "tid": "$tenant_id" },
+            "pipeline": [
+                { "$match": { "$expr": { "$and": [
+                    { "$eq": ["$_id", "$$uid"] },
+                    { "$eq": ["$tenant_id", "$$tid"] },
+                ] } } },
+                { "$project": { "name": 1, "email": 1 } },
+            ],
+            "as": "user_info"
+        }
+    }
+}
+
+/// Shapes a joined attendance document into an `AttendanceAdminDetail`.
+fn detail_projection() -> Document {
+    doc! {
+        "$project": {
+            "_id": 1,
+            "user_id": 1,
+            "user_name": { "$ifNull": ["$user_info.name", "(deleted user)"] },
+            "user_email": { "$ifNull": ["$user_info.email", ""] },
+            "timestamp": 1,
+            "type": 1,
+            "latitude": { "$arrayElemAt": ["$location.coordinates", 1] },
+            "longitude": { "$arrayElemAt": ["$location.coordinates", 0] },
+            "manual": { "$ifNull": ["$manual", false] },
+            "edited": { "$in": ["edit", { "$ifNull": ["$revisions.action", []] }] },
+            "voided": { "$ifNull": ["$voided", false] },
+        }
+    }
+}
+
+/// Attendance list shared with the supervisor view, which passes a users
+/// filter for its team in `reports`.
+pub async fn attendance_page(
+    state: &AppState,
+    tenant_id: ObjectId,
+    query: &AttendanceQuery,
+    reports: Option<Document>,
+) -> Response {
     let page = query.page.unwrap_or(1);
     let limit = query.limit.unwrap_or(10);
     let skip = (page - 1) * limit;
 
-    let filter = build_attendance_filter(&query);
+    let filter = match scoped_filter(state, tenant_id, query, reports).await {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
 
     let pipeline = vec![
         doc! { "$match": filter.clone() },
-        doc! {
-            "$lookup": {
-                "from": "users",
-                "localField": "user_id",
-                "foreignField": "_id",
-                "as": "user_info"
-            }
-        },
-        doc! { "$unwind": "$user_info" },
-        doc! {
-            "$project": {
-                "_id": 1,
-                "user_id": 1,
-                "user_name": "$user_info.name",
-                "user_email": "$user_info.email",
-                "timestamp": 1,
-                "type": 1,
-                "latitude": { "$arrayElemAt": ["$location.coordinates", 1] },
-                "longitude": { "$arrayElemAt": ["$location.coordinates", 0] }
-            }
-        },
+        user_lookup(),
+        // Keep rows whose user was purged or never existed
+        doc! { "$unwind": { "path": "$user_info", "preserveNullAndEmptyArrays": true } },
+        detail_projection(),
         doc! { "$sort": { "timestamp": -1 } },
         doc! { "$skip": skip as i64 },
         doc! { "$limit": limit as i64 },
@@ -98,10 +674,7 @@ pub async fn list_attendance(
         }
     }
 
-    let total = match attendance_col.count_documents(filter).await {
-        Ok(t) => t,
-        Err(_) => 0,
-    };
+    let total = attendance_col.count_documents(filter).await.unwrap_or_default();
 
     Json(AdminAttendanceResponse {
         data,
@@ -111,35 +684,24 @@ pub async fn list_attendance(
     }).into_response()
 }
 
-pub async fn export_attendance_csv(
-    State(state): State<Arc<AppState>>,
-    Query(query): Query<AttendanceQuery>,
-) -> impl IntoResponse {
-    let filter = build_attendance_filter(&query);
+/// CSV export of the same rows as `attendance_page`.
+pub async fn attendance_csv(
+    state: &AppState,
+    tenant_id: ObjectId,
+    query: &AttendanceQuery,
+    reports: Option<Document>,
+) -> Response {
+    let filter = match scoped_filter(state, tenant_id, query, reports).await {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
 
     let pipeline = vec![
         doc! { "$match": filter },
-        doc! {
-            "$lookup": {
-                "from": "users",
-                "localField": "user_id",
-                "foreignField": "_id",
-                "as": "user_info"
-            }
-        },
-        doc! { "$unwind": "$user_info" },
-        doc! {
-            "$project": {
-                "_id": 1,
-                "user_id": 1,
-                "user_name": "$user_info.name",
-                "user_email": "$user_info.email",
-                "timestamp": 1,
-                "type": 1,
-                "latitude": { "$arrayElemAt": ["$location.coordinates", 1] },
-                "longitude": { "$arrayElemAt": ["$location.coordinates", 0] }
-            }
-        },
+        user_lookup(),
+        // Keep rows whose user was purged or never existed
+        doc! { "$unwind": { "path": "$user_info", "preserveNullAndEmptyArrays": true } },
+        detail_projection(),
         doc! { "$sort": { "timestamp": -1 } },
     ];
 
@@ -152,7 +714,7 @@ pub async fn export_attendance_csv(
     let mut wtr = csv::Writer::from_writer(vec![]);
     
     // Header
-    if let Err(_) = wtr.write_record(&["Date", "Time", "User Name", "User Email", "Type", "Location Lat", "Location Lng"]) {
+    if wtr.write_record(["Date", "Time", "User Name", "User Email", "Type", "Location Lat", "Location Lng", "Manual Change"]).is_err() {
         return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate CSV").into_response();
     }
 
@@ -162,6 +724,7 @@ pub async fn export_attendance_csv(
     while let Ok(Some(doc)) = cursor.try_next().await {
         if let Ok(detail) = mongodb::bson::from_document::<AttendanceAdminDetail>(doc) {
             let local_dt = detail.timestamp.with_timezone(&wib);
+            let alterations = detail.alterations();
             let _ = wtr.write_record(&[
                 local_dt.format("%Y-%m-%d").to_string(),
                 local_dt.format("%H:%M:%S").to_string(),
@@ -170,6 +733,7 @@ pub async fn export_attendance_csv(
                 detail.r#type,
                 detail.latitude.to_string(),
                 detail.longitude.to_string(),
+                alterations,
             ]);
         }
     }
@@ -188,9 +752,45 @@ pub async fn export_attendance_csv(
         .into_response()
 }
 
+/// Attendance only stores the user, so department and team filters (and the
+/// supervisor's team) are resolved to user ids first.
+async fn scoped_filter(
+    state: &AppState,
+    tenant_id: ObjectId,
+    query: &AttendanceQuery,
+    reports: Option<Document>,
+) -> Result<Document, Response> {
+    let mut filter = build_attendance_filter(query);
+    filter.insert("tenant_id", tenant_id);
+
+    let mut users = reports.unwrap_or_default();
+    if let Some(department_id) = &query.department_id {
+        let department_id = ObjectId::parse_str(department_id)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid department ID").into_response())?;
+        users.insert("department_id", department_id);
+    }
+    if let Some(team_id) = &query.team_id {
+        let team_id = ObjectId::parse_str(team_id)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid team ID").into_response())?;
+        users.insert("team_id", team_id);
+    }
+    if !users.is_empty() {
+        users.insert("tenant_id", tenant_id);
+        let allowed = user_ids(&state.db, users)
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())?;
+        restrict_to_users(&mut filter, allowed);
+    }
+    Ok(filter)
+}
+
 fn build_attendance_filter(query: &AttendanceQuery) -> mongodb::bson::Document {
     let mut filter = doc! {};
 
+    if query.include_voided != Some(true) {
+        filter.insert("voided", doc! { "$ne": true });
+    }
+
     if let Some(user_id_str) = &query.user_id {
         if let Ok(oid) = ObjectId::parse_str(user_id_str) {
             filter.insert("user_id", oid);
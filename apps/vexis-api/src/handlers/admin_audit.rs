@@ -0,0 +1,178 @@
+use axum::{
+    extract::{Extension, Query, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::tenant::TenantId;
+use crate::models::audit::AuditEntry;
+use crate::utils::audit::{self, verify_chain};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId, Bson, DateTime, Document};
+use futures::stream::TryStreamExt;
+use chrono::{NaiveDate, Utc};
+
+#[derive(Deserialize)]
+pub struct ListAuditQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    pub actor_id: Option<String>,
+    pub target_id: Option<String>,
+    pub target_type: Option<String>,
+    /// An exact action, or a prefix ending in `.` such as `auth.`.
+    pub action: Option<String>,
+    pub ip: Option<String>,
+    /// `YYYY-MM-DD` or RFC 3339, inclusive.
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AuditEntryResponse {
+    pub id: String,
+    pub seq: i64,
+    pub actor_id: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+    pub details: serde_json::Value,
+    pub created_at: chrono::DateTime<Utc>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        let json = |document: Document| Bson::Document(document).into_relaxed_extjson();
+        Self {
+            id: entry.id.to_hex(),
+            seq: entry.seq,
+            actor_id: entry.actor_id.map(|id| id.to_hex()),
+            ip: entry.ip,
+            user_agent: entry.user_agent,
+            action: entry.action,
+            target_type: entry.target_type,
+            target_id: entry.target_id.map(|id| id.to_hex()),
+            before: json(entry.before),
+            after: json(entry.after),
+            details: json(entry.details),
+            created_at: entry.created_at,
+            prev_hash: entry.prev_hash,
+            hash: entry.hash,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AuditListResponse {
+    pub entries: Vec<AuditEntryResponse>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+}
+
+fn parse_date(value: &str, end_of_day: bool) -> Option<DateTime> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(DateTime::from_millis(dt.timestamp_millis()));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let time = if end_of_day { date.and_hms_milli_opt(23, 59, 59, 999) } else { date.and_hms_opt(0, 0, 0) }?;
+    Some(DateTime::from_millis(time.and_utc().timestamp_millis()))
+}
+
+fn build_audit_filter(tenant_id: ObjectId, query: &ListAuditQuery) -> Result<Document, &'static str> {
+    let mut filter = doc! { "tenant_id": tenant_id, "seq": { "$exists": true } };
+
+    if let Some(actor_id) = &query.actor_id {
+        let oid = ObjectId::parse_str(actor_id).map_err(|_| "Invalid actor ID")?;
+        filter.insert("actor_id", oid);
+    }
+    if let Some(target_id) = &query.target_id {
+        let oid = ObjectId::parse_str(target_id).map_err(|_| "Invalid target ID")?;
+        filter.insert("target_id", oid);
+    }
+    if let Some(target_type) = &query.target_type {
+        filter.insert("target_type", target_type);
+    }
+    if let Some(action) = &query.action {
+        if !action.bytes().all(|b| b.is_ascii_lowercase() || b == b'_' || b == b'.') {
+            return Err("Invalid action");
+        }
+        if action.ends_with('.') {
+            filter.insert("action", doc! { "$regex": format!("^{}", action.replace('.', "\\.")) });
+        } else {
+            filter.insert("action", action);
+        }
+    }
+    if let Some(ip) = &query.ip {
+        filter.insert("ip", ip);
+    }
+
+    let mut created_at = Document::new();
+    if let Some(start) = &query.start_date {
+        created_at.insert("$gte", parse_date(start, false).ok_or("Invalid start_date")?);
+    }
+    if let Some(end) = &query.end_date {
+        created_at.insert("$lte", parse_date(end, true).ok_or("Invalid end_date")?);
+    }
+    if !created_at.is_empty() {
+        filter.insert("created_at", created_at);
+    }
+
+    Ok(filter)
+}
+
+/// The caller's tenant's audit log, newest first.
+pub async fn list_audit(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<ListAuditQuery>,
+) -> impl IntoResponse {
+    let filter = match build_audit_filter(tenant_id, &query) {
+        Ok(filter) => filter,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let audit_col = state.db.collection::<AuditEntry>(audit::COLLECTION);
+
+    let total = match audit_col.count_documents(filter.clone()).await {
+        Ok(count) => count,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut cursor = match audit_col
+        .find(filter)
+        .sort(doc! { "seq": -1 })
+        .skip((page - 1) * limit)
+        .limit(limit as i64)
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut entries = Vec::new();
+    while let Ok(Some(entry)) = cursor.try_next().await {
+        entries.push(AuditEntryResponse::from(entry));
+    }
+
+    Json(AuditListResponse { entries, total, page, limit }).into_response()
+}
+
+/// Recomputes the hash chain of the caller's tenant.
+pub async fn verify_audit(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    match verify_chain(&state.db, tenant_id).await {
+        Ok(report) => Json(report).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
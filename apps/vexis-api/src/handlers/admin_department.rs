@@ -0,0 +1,432 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::department::{Department, Team};
+use crate::models::user::User;
+use crate::utils::audit::AuditEvent;
+use crate::utils::jwt::Claims;
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct DepartmentRequest {
+    pub name: String,
+    /// Left out or `""` for no supervisor.
+    pub supervisor_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamRequest {
+    pub name: String,
+    pub department_id: String,
+    pub supervisor_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListTeamsQuery {
+    pub department_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DepartmentResponse {
+    pub id: String,
+    pub name: String,
+    pub supervisor_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Department> for DepartmentResponse {
+    fn from(department: Department) -> Self {
+        Self {
+            id: department.id.unwrap().to_hex(),
+            name: department.name,
+            supervisor_id: department.supervisor_id.map(|id| id.to_hex()),
+            created_at: department.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TeamResponse {
+    pub id: String,
+    pub name: String,
+    pub department_id: String,
+    pub supervisor_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Team> for TeamResponse {
+    fn from(team: Team) -> Self {
+        Self {
+            id: team.id.unwrap().to_hex(),
+            name: team.name,
+            department_id: team.department_id.to_hex(),
+            supervisor_id: team.supervisor_id.map(|id| id.to_hex()),
+            created_at: team.created_at,
+        }
+    }
+}
+
+fn bad_request(message: &'static str) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+fn database_error() -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+}
+
+fn validate_name(name: &str) -> Result<String, &'static str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Name is required");
+    }
+    Ok(name.to_string())
+}
+
+/// Supervisors must be active accounts. They need not belong to the unit they
+/// supervise.
+async fn resolve_supervisor(
+    state: &AppState,
+    tenant_id: ObjectId,
+    supervisor_id: Option<&str>,
+) -> Result<Option<ObjectId>, Response> {
+    let Some(supervisor_id) = supervisor_id.filter(|id| !id.is_empty()) else {
+        return Ok(None);
+    };
+    let supervisor_id = ObjectId::parse_str(supervisor_id).map_err(|_| bad_request("Invalid supervisor ID"))?;
+    match state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": supervisor_id, "tenant_id": tenant_id, "deactivated": { "$ne": true } })
+        .await
+    {
+        Ok(Some(_)) => Ok(Some(supervisor_id)),
+        Ok(None) => Err(bad_request("Supervisor not found")),
+        Err(_) => Err(database_error()),
+    }
+}
+
+async fn find_department(state: &AppState, tenant_id: ObjectId, department_id: &str) -> Result<ObjectId, Response> {
+    let department_id = ObjectId::parse_str(department_id).map_err(|_| bad_request("Invalid department ID"))?;
+    match state
+        .db
+        .collection::<Department>("departments")
+        .find_one(doc! { "_id": department_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(_)) => Ok(department_id),
+        Ok(None) => Err(bad_request("Department not found")),
+        Err(_) => Err(database_error()),
+    }
+}
+
+pub async fn list_departments(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    let mut cursor = match state
+        .db
+        .collection::<Department>("departments")
+        .find(doc! { "tenant_id": tenant_id })
+        .sort(doc! { "name": 1 })
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return database_error(),
+    };
+
+    let mut departments = Vec::new();
+    while let Ok(Some(department)) = cursor.try_next().await {
+        departments.push(DepartmentResponse::from(department));
+    }
+
+    Json(departments).into_response()
+}
+
+pub async fn create_department(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<DepartmentRequest>,
+) -> impl IntoResponse {
+    let Ok(admin_id) = ObjectId::parse_str(&claims.sub) else {
+        return bad_request("Invalid user ID");
+    };
+    let name = match validate_name(&payload.name) {
+        Ok(name) => name,
+        Err(msg) => return bad_request(msg),
+    };
+    let supervisor_id = match resolve_supervisor(&state, tenant_id, payload.supervisor_id.as_deref()).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let mut department = Department {
+        id: None,
+        tenant_id,
+        name,
+        supervisor_id,
+        created_at: Utc::now(),
+    };
+    match state.db.collection::<Department>("departments").insert_one(&department).await {
+        Ok(result) => department.id = result.inserted_id.as_object_id(),
+        Err(_) => return database_error(),
+    }
+
+    AuditEvent::new(tenant_id, Some(admin_id), "department.create", "department", department.id)
+        .after(doc! { "name": &department.name, "supervisor_id": department.supervisor_id })
+        .record(&state.db, &client)
+        .await;
+    (StatusCode::CREATED, Json(DepartmentResponse::from(department))).into_response()
+}
+
+pub async fn update_department(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<DepartmentRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(department_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid department ID");
+    };
+    let name = match validate_name(&payload.name) {
+        Ok(name) => name,
+        Err(msg) => return bad_request(msg),
+    };
+    let supervisor_id = match resolve_supervisor(&state, tenant_id, payload.supervisor_id.as_deref()).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let changes = doc! { "name": &name, "supervisor_id": supervisor_id };
+    match state
+        .db
+        .collection::<Department>("departments")
+        .find_one_and_update(doc! { "_id": department_id, "tenant_id": tenant_id }, doc! { "$set": changes.clone() })
+        .await
+    {
+        Ok(Some(previous)) => {
+            AuditEvent::new(tenant_id, Some(admin_id), "department.update", "department", Some(department_id))
+                .changes(&previous, &changes)
+                .record(&state.db, &client)
+                .await;
+            Json(DepartmentResponse::from(Department { name, supervisor_id, ..previous })).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Department not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
+
+/// Departments that still have teams or users cannot be deleted.
+pub async fn delete_department(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(department_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid department ID");
+    };
+
+    match state.db.collection::<Team>("teams").count_documents(doc! { "tenant_id": tenant_id, "department_id": department_id }).await {
+        Ok(0) => {}
+        Ok(_) => return (StatusCode::CONFLICT, "Department still has teams").into_response(),
+        Err(_) => return database_error(),
+    }
+    match state.db.collection::<User>("users").count_documents(doc! { "tenant_id": tenant_id, "department_id": department_id }).await {
+        Ok(0) => {}
+        Ok(_) => return (StatusCode::CONFLICT, "Department still has users assigned").into_response(),
+        Err(_) => return database_error(),
+    }
+
+    match state
+        .db
+        .collection::<Department>("departments")
+        .find_one_and_delete(doc! { "_id": department_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(department)) => {
+            AuditEvent::new(tenant_id, Some(admin_id), "department.delete", "department", Some(department_id))
+                .before(doc! { "name": &department.name, "supervisor_id": department.supervisor_id })
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Department deleted").into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Department not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
+
+pub async fn list_teams(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<ListTeamsQuery>,
+) -> impl IntoResponse {
+    let mut filter = doc! { "tenant_id": tenant_id };
+    if let Some(department_id) = &query.department_id {
+        match ObjectId::parse_str(department_id) {
+            Ok(oid) => filter.insert("department_id", oid),
+            Err(_) => return bad_request("Invalid department ID"),
+        };
+    }
+
+    let mut cursor = match state.db.collection::<Team>("teams").find(filter).sort(doc! { "name": 1 }).await {
+        Ok(c) => c,
+        Err(_) => return database_error(),
+    };
+
+    let mut teams = Vec::new();
+    while let Ok(Some(team)) = cursor.try_next().await {
+        teams.push(TeamResponse::from(team));
+    }
+
+    Json(teams).into_response()
+}
+
+pub async fn create_team(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<TeamRequest>,
+) -> impl IntoResponse {
+    let Ok(admin_id) = ObjectId::parse_str(&claims.sub) else {
+        return bad_request("Invalid user ID");
+    };
+    let name = match validate_name(&payload.name) {
+        Ok(name) => name,
+        Err(msg) => return bad_request(msg),
+    };
+    let department_id = match find_department(&state, tenant_id, &payload.department_id).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    let supervisor_id = match resolve_supervisor(&state, tenant_id, payload.supervisor_id.as_deref()).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let mut team = Team {
+        id: None,
+        tenant_id,
+        name,
+        department_id,
+        supervisor_id,
+        created_at: Utc::now(),
+    };
+    match state.db.collection::<Team>("teams").insert_one(&team).await {
+        Ok(result) => team.id = result.inserted_id.as_object_id(),
+        Err(_) => return database_error(),
+    }
+
+    AuditEvent::new(tenant_id, Some(admin_id), "team.create", "team", team.id)
+        .after(doc! { "name": &team.name, "department_id": department_id, "supervisor_id": supervisor_id })
+        .record(&state.db, &client)
+        .await;
+    (StatusCode::CREATED, Json(TeamResponse::from(team))).into_response()
+}
+
+/// Moving a team to another department moves its members with it.
+pub async fn update_team(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<TeamRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(team_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid team ID");
+    };
+    let name = match validate_name(&payload.name) {
+        Ok(name) => name,
+        Err(msg) => return bad_request(msg),
+    };
+    let department_id = match find_department(&state, tenant_id, &payload.department_id).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+    let supervisor_id = match resolve_supervisor(&state, tenant_id, payload.supervisor_id.as_deref()).await {
+        Ok(id) => id,
+        Err(response) => return response,
+    };
+
+    let changes = doc! { "name": &name, "department_id": department_id, "supervisor_id": supervisor_id };
+    let previous = match state
+        .db
+        .collection::<Team>("teams")
+        .find_one_and_update(doc! { "_id": team_id, "tenant_id": tenant_id }, doc! { "$set": changes.clone() })
+        .await
+    {
+        Ok(Some(team)) => team,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Team not found").into_response(),
+        Err(_) => return database_error(),
+    };
+
+    if let Err(e) = state
+        .db
+        .collection::<User>("users")
+        .update_many(
+            doc! { "tenant_id": tenant_id, "team_id": team_id, "department_id": { "$ne": department_id } },
+            doc! { "$set": { "department_id": department_id } },
+        )
+        .await
+    {
+        eprintln!("Failed to move members of team {}: {:?}", team_id, e);
+    }
+
+    AuditEvent::new(tenant_id, Some(admin_id), "team.update", "team", Some(team_id))
+        .changes(&previous, &changes)
+        .record(&state.db, &client)
+        .await;
+    Json(TeamResponse::from(Team { name, department_id, supervisor_id, ..previous })).into_response()
+}
+
+/// Teams that still have users assigned cannot be deleted.
+pub async fn delete_team(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(team_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid team ID");
+    };
+
+    match state.db.collection::<User>("users").count_documents(doc! { "tenant_id": tenant_id, "team_id": team_id }).await {
+        Ok(0) => {}
+        Ok(_) => return (StatusCode::CONFLICT, "Team still has users assigned").into_response(),
+        Err(_) => return database_error(),
+    }
+
+    match state
+        .db
+        .collection::<Team>("teams")
+        .find_one_and_delete(doc! { "_id": team_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(team)) => {
+            AuditEvent::new(tenant_id, Some(admin_id), "team.delete", "team", Some(team_id))
+                .before(doc! { "name": &team.name, "department_id": team.department_id, "supervisor_id": team.supervisor_id })
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Team deleted").into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Team not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Path, Query, State, Extension, Multipart},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::office::Office;
+use crate::models::user::User;
+use crate::models::user_import::{RowError, UserImport};
+use crate::utils::audit::AuditEvent;
+use crate::utils::jobs::{enqueue, JOB_KIND_USER_IMPORT};
+use crate::utils::jwt::Claims;
+use crate::utils::user_import::{detect_format, parse, validate, ExistingUsers, UserImportJob};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{Collation, CollationStrength};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Duration, Utc};
+
+/// Finished imports are kept this long for the progress endpoint.
+const IMPORT_RETENTION_DAYS: i64 = 7;
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Validate only; nothing is created.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct ImportPreviewResponse {
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(Serialize)]
+pub struct ImportStatusResponse {
+    pub id: String,
+    pub status: String,
+    pub total: i32,
+    pub processed: i32,
+    pub created: i32,
+    pub errors: Vec<RowError>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserImport> for ImportStatusResponse {
+    fn from(import: UserImport) -> Self {
+        Self {
+            id: import.id.unwrap().to_hex(),
+            status: import.status,
+            total: import.total,
+            processed: import.processed,
+            created: import.created,
+            errors: import.errors,
+            created_at: import.created_at,
+            finished_at: import.finished_at,
+        }
+    }
+}
+
+/// Which of the given emails (compared case-insensitively) and identifiers
+/// already belong to an account.
+async fn find_existing(
+    state: &AppState,
+    tenant_id: ObjectId,
+    emails: Vec<String>,
+    identifiers: Vec<String>,
+) -> Result<ExistingUsers, mongodb::error::Error> {
+    let users_col = state.db.collection::<User>("users");
+    let mut existing = ExistingUsers::default();
+
+    let mut cursor = users_col
+        .find(doc! { "tenant_id": tenant_id, "email": { "$in": emails } })
+        .collation(Collation::builder().locale("en").strength(CollationStrength::Secondary).build())
+        .await?;
+    while let Some(user) = cursor.try_next().await? {
+        existing.emails.insert(user.email.to_lowercase());
+    }
+    let mut cursor = users_col.find(doc! { "tenant_id": tenant_id, "identifier": { "$in": identifiers } }).await?;
+    while let Some(user) = cursor.try_next().await? {
+        existing.identifiers.insert(user.identifier);
+    }
+    Ok(existing)
+}
+
+pub async fn import_users(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let Ok(admin_id) = ObjectId::parse_str(&claims.sub) else {
+        return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response();
+    };
+
+    let mut upload = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("file") {
+            let filename = field.file_name().map(str::to_string);
+            match field.bytes().await {
+                Ok(bytes) => upload = Some((filename, bytes)),
+                Err(_) => return (StatusCode::BAD_REQUEST, "Error reading file").into_response(),
+            }
+            break;
+        }
+    }
+    let Some((filename, bytes)) = upload else {
+        return (StatusCode::BAD_REQUEST, "No file field found").into_response();
+    };
+
+    let rows = match parse(&bytes, detect_format(filename.as_deref(), &bytes)) {
+        Ok(rows) if rows.is_empty() => return (StatusCode::BAD_REQUEST, "The file has no rows").into_response(),
+        Ok(rows) => rows,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let total_rows = rows.len();
+
+    let offices: Vec<Office> = match state.db.collection::<Office>("offices").find(doc! { "tenant_id": tenant_id }).await {
+        Ok(cursor) => match cursor.try_collect().await {
+            Ok(offices) => offices,
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        },
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    let emails = rows.iter().map(|r| r.email.clone()).collect();
+    let identifiers = rows.iter().map(|r| r.identifier.clone()).collect();
+    let existing = match find_existing(&state, tenant_id, emails, identifiers).await {
+        Ok(existing) => existing,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let (valid, errors) = validate(rows, &offices, &existing);
+    let preview = ImportPreviewResponse {
+        total_rows,
+        valid_rows: valid.len(),
+        errors,
+    };
+    if query.dry_run.unwrap_or(false) {
+        return Json(preview).into_response();
+    }
+    // All or nothing, so the admin can fix the file and upload it again as is
+    if !preview.errors.is_empty() {
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(preview)).into_response();
+    }
+
+    let now = Utc::now();
+    let import = UserImport {
+        id: None,
+        tenant_id,
+        created_by: admin_id,
+        status: "pending".to_string(),
+        total: valid.len() as i32,
+        rows: valid,
+        processed: 0,
+        created: 0,
+        errors: vec![],
+        created_at: now,
+        finished_at: None,
+        expires_at: now + Duration::days(IMPORT_RETENTION_DAYS),
+    };
+    let import_id = match state.db.collection::<UserImport>("user_imports").insert_one(&import).await {
+        Ok(result) => result.inserted_id.as_object_id().unwrap(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    if enqueue(&state.db, JOB_KIND_USER_IMPORT, &UserImportJob { import_id }).await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+    }
+    // The job records the outcome as "user.import"
+    AuditEvent::new(tenant_id, Some(admin_id), "user.import_upload", "user_import", Some(import_id))
+        .details(doc! { "filename": filename, "total": import.total })
+        .record(&state.db, &client)
+        .await;
+
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "import_id": import_id.to_hex(), "total": import.total })),
+    )
+        .into_response()
+}
+
+pub async fn get_import(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let import_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid import ID").into_response(),
+    };
+
+    match state.db.collection::<UserImport>("user_imports").find_one(doc! { "_id": import_id, "tenant_id": tenant_id }).await {
+        Ok(Some(import)) => Json(ImportStatusResponse::from(import)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Import not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
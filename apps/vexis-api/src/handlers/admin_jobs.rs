@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::job::Job;
+use crate::utils::audit::AuditEvent;
+use crate::utils::jobs;
+use crate::utils::jwt::Claims;
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct ListJobsQuery {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+/// A job as shown to admins. The payload is left out because it can hold
+/// one-time tokens; email jobs expose only the recipient and template.
+#[derive(Serialize)]
+pub struct JobResponse {
+    pub id: String,
+    pub kind: String,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub recipient: Option<String>,
+    pub template: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobResponse>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+}
+
+impl From<Job> for JobResponse {
+    fn from(job: Job) -> Self {
+        let field = |name: &str| job.payload.get_str(name).ok().map(str::to_string);
+        Self {
+            id: job.id.unwrap().to_hex(),
+            recipient: field("to"),
+            template: field("template"),
+            kind: job.kind,
+            status: job.status,
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+            run_at: job.run_at,
+            last_error: job.last_error,
+            created_at: job.created_at,
+        }
+    }
+}
+
+pub async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListJobsQuery>,
+) -> impl IntoResponse {
+    let jobs_col = state.db.collection::<Job>("jobs");
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let skip = (page - 1) * limit;
+
+    let mut filter = doc! {};
+    if let Some(status) = query.status {
+        filter.insert("status", status);
+    }
+    if let Some(kind) = query.kind {
+        filter.insert("kind", kind);
+    }
+
+    let total = match jobs_col.count_documents(filter.clone()).await {
+        Ok(count) => count,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut cursor = match jobs_col
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .skip(skip)
+        .limit(limit as i64)
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut jobs = Vec::new();
+    while let Ok(Some(job)) = cursor.try_next().await {
+        jobs.push(JobResponse::from(job));
+    }
+
+    Json(JobListResponse {
+        jobs,
+        total,
+        page,
+        limit,
+    }).into_response()
+}
+
+pub async fn retry_job(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let job_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid job ID").into_response(),
+    };
+
+    match jobs::retry(&state.db, job_id).await {
+        Ok(true) => {
+            // Jobs are deployment-wide; the entry goes to the super admin's tenant
+            AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "job.retry", "job", Some(job_id))
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Job queued for retry").into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "No failed job with that ID").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
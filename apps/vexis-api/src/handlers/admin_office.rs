@@ -0,0 +1,220 @@
+use axum::{
+    extract::{Extension, Path, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::office::Office;
+use crate::models::user::{OfficeLocation, User};
+use crate::utils::audit::AuditEvent;
+use crate::utils::jwt::Claims;
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct OfficeRequest {
+    pub name: String,
+    pub lat: f64,
+    pub long: f64,
+    pub radius_m: f64,
+}
+
+#[derive(Serialize)]
+pub struct OfficeResponse {
+    pub id: String,
+    pub name: String,
+    pub lat: f64,
+    pub long: f64,
+    pub radius_m: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Office> for OfficeResponse {
+    fn from(office: Office) -> Self {
+        Self {
+            id: office.id.unwrap().to_hex(),
+            name: office.name,
+            lat: office.location.coordinates[1],
+            long: office.location.coordinates[0],
+            radius_m: office.radius_m,
+            created_at: office.created_at,
+        }
+    }
+}
+
+fn validate(payload: &OfficeRequest) -> Result<(), &'static str> {
+    if payload.name.trim().is_empty() {
+        return Err("Name is required");
+    }
+    if !(-90.0..=90.0).contains(&payload.lat) || !(-180.0..=180.0).contains(&payload.long) {
+        return Err("Invalid coordinates");
+    }
+    if !(payload.radius_m > 0.0 && payload.radius_m <= 10_000.0) {
+        return Err("Radius must be between 0 and 10000 meters");
+    }
+    Ok(())
+}
+
+fn location(payload: &OfficeRequest) -> OfficeLocation {
+    OfficeLocation {
+        r#type: "Point".to_string(),
+        coordinates: vec![payload.long, payload.lat],
+    }
+}
+
+/// The fields audit entries compare, with coordinates flattened.
+fn audit_fields(office: &Office) -> Document {
+    doc! {
+        "name": &office.name,
+        "lat": office.location.coordinates.get(1).copied(),
+        "long": office.location.coordinates.first().copied(),
+        "radius_m": office.radius_m,
+    }
+}
+
+pub async fn list_offices(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    let mut cursor = match state
+        .db
+        .collection::<Office>("offices")
+        .find(doc! { "tenant_id": tenant_id })
+        .sort(doc! { "name": 1 })
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut offices = Vec::new();
+    while let Ok(Some(office)) = cursor.try_next().await {
+        offices.push(OfficeResponse::from(office));
+    }
+
+    Json(offices).into_response()
+}
+
+pub async fn create_office(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<OfficeRequest>,
+) -> impl IntoResponse {
+    let Ok(admin_id) = ObjectId::parse_str(&claims.sub) else {
+        return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response();
+    };
+    if let Err(msg) = validate(&payload) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let mut office = Office {
+        id: None,
+        tenant_id,
+        name: payload.name.trim().to_string(),
+        location: location(&payload),
+        radius_m: payload.radius_m,
+        created_at: Utc::now(),
+    };
+
+    match state.db.collection::<Office>("offices").insert_one(&office).await {
+        Ok(result) => office.id = result.inserted_id.as_object_id(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    AuditEvent::new(tenant_id, Some(admin_id), "office.create", "office", office.id)
+        .after(audit_fields(&office))
+        .record(&state.db, &client)
+        .await;
+    (StatusCode::CREATED, Json(OfficeResponse::from(office))).into_response()
+}
+
+pub async fn update_office(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<OfficeRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(office_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid office ID").into_response();
+    };
+    if let Err(msg) = validate(&payload) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let update = doc! {
+        "$set": {
+            "name": payload.name.trim(),
+            "location": mongodb::bson::to_bson(&location(&payload)).unwrap(),
+            "radius_m": payload.radius_m,
+        }
+    };
+
+    match state
+        .db
+        .collection::<Office>("offices")
+        .find_one_and_update(doc! { "_id": office_id, "tenant_id": tenant_id }, update)
+        .await
+    {
+        Ok(Some(previous)) => {
+            let office = Office {
+                name: payload.name.trim().to_string(),
+                location: location(&payload),
+                radius_m: payload.radius_m,
+                ..previous
+            };
+            AuditEvent::new(tenant_id, Some(admin_id), "office.update", "office", Some(office_id))
+                .changes(&audit_fields(&previous), &audit_fields(&office))
+                .record(&state.db, &client)
+                .await;
+            Json(OfficeResponse::from(office)).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Office not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+/// Offices that still have users assigned cannot be deleted.
+pub async fn delete_office(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(office_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid office ID").into_response();
+    };
+
+    match state.db.collection::<User>("users").count_documents(doc! { "tenant_id": tenant_id, "office_id": office_id }).await {
+        Ok(0) => {}
+        Ok(_) => return (StatusCode::CONFLICT, "Office still has users assigned").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    match state
+        .db
+        .collection::<Office>("offices")
+        .find_one_and_delete(doc! { "_id": office_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(office)) => {
+            AuditEvent::new(tenant_id, Some(admin_id), "office.delete", "office", Some(office_id))
+                .before(audit_fields(&office))
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Office deleted").into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Office not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
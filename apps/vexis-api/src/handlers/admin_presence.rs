@@ -0,0 +1,105 @@
+use axum::{
+    extract::{Extension, Query, State},
+    Json,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::tenant::TenantId;
+use crate::utils::jwt::Claims;
+use crate::utils::presence::{current_presence, OfficePresence};
+use serde::Deserialize;
+use mongodb::bson::oid::ObjectId;
+use chrono::Utc;
+
+#[derive(Deserialize)]
+pub struct PresenceQuery {
+    pub office_id: Option<String>,
+}
+
+/// Who is on site now, per office.
+pub async fn get_presence(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<PresenceQuery>,
+) -> impl IntoResponse {
+    let Ok(office_id) = query.office_id.as_deref().map(ObjectId::parse_str).transpose() else {
+        return (StatusCode::BAD_REQUEST, "Invalid office ID").into_response();
+    };
+
+    match current_presence(&state.db, tenant_id, office_id).await {
+        Ok(presence) => Json(presence).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+/// Server-Sent Events: a `presence` event with the same body as
+/// `get_presence` on connect and whenever someone arrives or leaves. All
+/// streams of a tenant share one computation per poll. The token is checked
+/// again on every poll, so the stream ends once it is revoked or expires;
+/// clients reconnect with a fresh one.
+pub async fn presence_stream(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<PresenceQuery>,
+) -> impl IntoResponse {
+    let Ok(office_id) = query.office_id.as_deref().map(ObjectId::parse_str).transpose() else {
+        return (StatusCode::BAD_REQUEST, "Invalid office ID").into_response();
+    };
+    let Ok(user_id) = ObjectId::parse_str(&claims.sub) else {
+        return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response();
+    };
+
+    let updates = state.presence.subscribe(&state.db, tenant_id);
+    let stream = futures::stream::unfold(
+        (state, updates, None::<Vec<OfficePresence>>),
+        move |(state, mut updates, last)| {
+            let claims = claims.clone();
+            async move {
+                loop {
+                    // Errs once the poller is gone
+                    updates.changed().await.ok()?;
+                    if !still_valid(&state, user_id, &claims).await {
+                        return None;
+                    }
+                    let Some(presence) = updates.borrow_and_update().clone() else {
+                        continue;
+                    };
+                    let presence = presence.for_office(office_id);
+                    if last.as_ref() == Some(&presence.offices) {
+                        continue;
+                    }
+                    let event = Event::default()
+                        .event("presence")
+                        .json_data(&presence)
+                        .expect("presence serializes to JSON");
+                    return Some((Ok::<_, Infallible>(event), (state, updates, Some(presence.offices))));
+                }
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// The checks `require_auth` made when the stream was opened: the token has
+/// not expired, and the user's token version, role and tenant still match.
+async fn still_valid(state: &AppState, user_id: ObjectId, claims: &Claims) -> bool {
+    if Utc::now().timestamp() >= claims.exp as i64 {
+        return false;
+    }
+    match state.token_cache.get(&state.db, user_id).await {
+        Ok(current) => current.is_some_and(|current| current.matches(claims)),
+        // A database hiccup should not drop every open stream
+        Err(e) => {
+            eprintln!("Failed to check presence stream token: {:?}", e);
+            true
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use axum::{
+    extract::{Extension, Path, State},
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::handlers::passwordless::{passwordless_update, store_passwordless_settings, PasswordlessSettingsPayload};
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::{is_valid_slug, TenantId};
+use crate::models::tenant::{Tenant, TenantSettings};
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::is_valid_address;
+use crate::utils::jwt::Claims;
+use crate::utils::revocation::revoke_tenant_tokens;
+use crate::utils::user_import::{invite_user, Invitation};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct CreateTenantRequest {
+    pub name: String,
+    pub slug: String,
+    /// Invited as the tenant's first admin.
+    pub admin: Option<TenantAdminRequest>,
+}
+
+#[derive(Deserialize)]
+pub struct TenantAdminRequest {
+    pub name: String,
+    pub email: String,
+    pub identifier: String,
+}
+
+/// The slug is fixed once created, since clients send it on every login.
+#[derive(Deserialize)]
+pub struct UpdateTenantRequest {
+    pub name: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TenantSettingsPayload {
+    pub passwordless: PasswordlessSettingsPayload,
+}
+
+impl From<TenantSettings> for TenantSettingsPayload {
+    fn from(settings: TenantSettings) -> Self {
+        Self {
+            passwordless: PasswordlessSettingsPayload {
+                enabled_roles: settings.passwordless.enabled_roles,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TenantResponse {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub active: bool,
+    pub settings: TenantSettingsPayload,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Tenant> for TenantResponse {
+    fn from(tenant: Tenant) -> Self {
+        Self {
+            id: tenant.id.unwrap().to_hex(),
+            name: tenant.name,
+            slug: tenant.slug,
+            active: tenant.active,
+            settings: TenantSettingsPayload::from(tenant.settings),
+            created_at: tenant.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CreateTenantResponse {
+    pub tenant: TenantResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_id: Option<String>,
+}
+
+fn bad_request(message: &'static str) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+fn database_error() -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+}
+
+pub async fn list_tenants(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut cursor = match state.db.collection::<Tenant>("tenants").find(doc! {}).sort(doc! { "name": 1 }).await {
+        Ok(c) => c,
+        Err(_) => return database_error(),
+    };
+
+    let mut tenants = Vec::new();
+    while let Ok(Some(tenant)) = cursor.try_next().await {
+        tenants.push(TenantResponse::from(tenant));
+    }
+
+    Json(tenants).into_response()
+}
+
+/// Creates a tenant and, if given, invites its first admin to set a password.
+pub async fn create_tenant(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(own_tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<CreateTenantRequest>,
+) -> impl IntoResponse {
+    let Ok(actor_id) = ObjectId::parse_str(&claims.sub) else {
+        return bad_request("Invalid user ID");
+    };
+    let name = payload.name.trim().to_string();
+    let slug = payload.slug.trim().to_lowercase();
+    if name.is_empty() {
+        return bad_request("Name is required");
+    }
+    if !is_valid_slug(&slug) {
+        return bad_request("Slug must be 2-40 lowercase letters, digits or inner hyphens");
+    }
+    if let Some(admin) = &payload.admin {
+        if admin.name.trim().is_empty() || admin.identifier.trim().is_empty() {
+            return bad_request("Admin name and identifier are required");
+        }
+        if !is_valid_address(admin.email.trim()) {
+            return bad_request("Invalid admin email address");
+        }
+    }
+
+    let tenants_col = state.db.collection::<Tenant>("tenants");
+    match tenants_col.count_documents(doc! { "slug": &slug }).await {
+        Ok(0) => {}
+        Ok(_) => return (StatusCode::CONFLICT, "Slug is already taken").into_response(),
+        Err(_) => return database_error(),
+    }
+
+    let mut tenant = Tenant {
+        id: None,
+        name,
+        slug,
+        active: true,
+        settings: TenantSettings::default(),
+        created_at: Utc::now(),
+    };
+    let tenant_id = match tenants_col.insert_one(&tenant).await {
+        Ok(result) => result.inserted_id.as_object_id().unwrap(),
+        // Lost a race for the same slug
+        Err(_) => return (StatusCode::CONFLICT, "Slug is already taken").into_response(),
+    };
+    tenant.id = Some(tenant_id);
+
+    let mut admin_id = None;
+    if let Some(admin) = &payload.admin {
+        let invitation = Invitation {
+            tenant_id,
+            invited_by: actor_id,
+            name: admin.name.trim(),
+            email: admin.email.trim(),
+            identifier: admin.identifier.trim(),
+            role: "admin",
+            office: None,
+        };
+        match invite_user(&state, &invitation).await {
+            Ok(id) => admin_id = Some(id),
+            Err(e) => {
+                eprintln!("Failed to invite admin of tenant {}: {}", tenant_id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Tenant created, but inviting the admin failed")
+                    .into_response();
+            }
+        }
+    }
+
+    AuditEvent::new(own_tenant_id, Some(actor_id), "tenant.create", "tenant", Some(tenant_id))
+        .after(doc! { "name": &tenant.name, "slug": &tenant.slug })
+        .details(doc! { "admin_id": admin_id })
+        .record(&state.db, &client)
+        .await;
+
+    (
+        StatusCode::CREATED,
+        Json(CreateTenantResponse {
+            tenant: TenantResponse::from(tenant),
+            admin_id: admin_id.map(|id| id.to_hex()),
+        }),
+    )
+        .into_response()
+}
+
+pub async fn get_tenant(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(tenant_id) = ObjectId::parse_str(&id) else {
+        return bad_request("Invalid tenant ID");
+    };
+
+    match state.db.collection::<Tenant>("tenants").find_one(doc! { "_id": tenant_id }).await {
+        Ok(Some(tenant)) => Json(TenantResponse::from(tenant)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
+
+/// Deactivating a tenant blocks its logins and ends its sessions within the
+/// token cache TTL. Its data is kept.
+pub async fn update_tenant(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(own_tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateTenantRequest>,
+) -> impl IntoResponse {
+    let (Ok(actor_id), Ok(tenant_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid tenant ID");
+    };
+
+    let mut update = Document::new();
+    if let Some(name) = payload.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return bad_request("Name cannot be empty");
+        }
+        update.insert("name", name);
+    }
+    if let Some(active) = payload.active {
+        if !active && tenant_id == own_tenant_id {
+            return bad_request("You cannot deactivate your own tenant");
+        }
+        update.insert("active", active);
+    }
+    if update.is_empty() {
+        return bad_request("No fields to update");
+    }
+
+    match state
+        .db
+        .collection::<Tenant>("tenants")
+        .find_one_and_update(doc! { "_id": tenant_id }, doc! { "$set": update.clone() })
+        .await
+    {
+        Ok(Some(previous)) => {
+            AuditEvent::new(own_tenant_id, Some(actor_id), "tenant.update", "tenant", Some(tenant_id))
+                .changes(&previous, &update)
+                .record(&state.db, &client)
+                .await;
+            if update.get_bool("active") == Ok(false) {
+                if let Err(e) = revoke_tenant_tokens(&state, tenant_id).await {
+                    eprintln!("Failed to revoke sessions of tenant {}: {}", tenant_id, e);
+                    return database_error();
+                }
+            }
+            let tenant = Tenant {
+                name: update.get_str("name").map(str::to_string).unwrap_or(previous.name),
+                active: update.get_bool("active").unwrap_or(previous.active),
+                ..previous
+            };
+            Json(TenantResponse::from(tenant)).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
+
+pub async fn get_tenant_settings(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(tenant_id) = ObjectId::parse_str(&id) else {
+        return bad_request("Invalid tenant ID");
+    };
+
+    match state.db.collection::<Tenant>("tenants").find_one(doc! { "_id": tenant_id }).await {
+        Ok(Some(tenant)) => Json(TenantSettingsPayload::from(tenant.settings)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(_) => database_error(),
+    }
+}
+
+pub async fn update_tenant_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(own_tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<TenantSettingsPayload>,
+) -> impl IntoResponse {
+    let (Ok(actor_id), Ok(tenant_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid tenant ID");
+    };
+
+    match store_passwordless_settings(&state, tenant_id, &payload.passwordless).await {
+        Ok(Some(previous)) => {
+            AuditEvent::new(own_tenant_id, Some(actor_id), "tenant.settings", "tenant", Some(tenant_id))
+                .changes(&previous, &passwordless_update(&payload.passwordless))
+                .record(&state.db, &client)
+                .await;
+            Json(payload).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Tenant not found").into_response(),
+        Err(error) => error.into_response(),
+    }
+}
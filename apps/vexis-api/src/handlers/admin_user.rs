@@ -2,14 +2,26 @@ use axum::{
     extract::{State, Query, Path, Extension},
     Json,
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use std::sync::Arc;
 use crate::AppState;
-use crate::models::user::User;
+use crate::config::db::is_duplicate_key;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::department::{Department, Team};
+use crate::models::office::Office;
+use crate::models::user::{OfficeLocation, User};
+use crate::handlers::auth::{send_password_reset, start_email_verification};
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::{is_valid_address, normalize_address};
+use crate::utils::email_templates::{DEFAULT_LOCALE, LOCALES};
 use crate::utils::jwt::Claims;
+use crate::utils::password::{check_policy, generate_temporary_password, hash_password, violations_response};
+use crate::utils::revocation::revoke_user_tokens;
+use crate::utils::webhook::{emit, EVENT_USER_DELETED, EVENT_USER_REGISTERED};
 use serde::{Deserialize, Serialize};
-use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
 use futures::stream::TryStreamExt;
 use crate::handlers::user::UserProfileResponse;
 
@@ -17,6 +29,55 @@ use crate::handlers::user::UserProfileResponse;
 pub struct ListUsersQuery {
     pub page: Option<u64>,
     pub limit: Option<u64>,
+    /// Words matched against name, email and identifier.
+    pub q: Option<String>,
+    pub role: Option<String>,
+    pub office_id: Option<String>,
+    pub department_id: Option<String>,
+    pub team_id: Option<String>,
+    pub face_enrolled: Option<bool>,
+    /// "active" (default) | "deactivated" | "all"
+    pub status: Option<String>,
+    /// "name" (default, or relevance when searching) | "email" | "identifier" | "created"
+    pub sort: Option<String>,
+    /// "asc" (default) | "desc"
+    pub order: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
+    pub email: String,
+    pub identifier: String,
+    pub role: String,
+    pub office_id: Option<String>,
+    pub department_id: Option<String>,
+    pub team_id: Option<String>,
+    pub locale: Option<String>,
+    /// Generated when left out. Either way the user must change it.
+    pub password: Option<String>,
+}
+
+/// Only the given fields change; `""` removes an office, department or team
+/// assignment. See `resolve_org` for how department and team interact.
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub identifier: Option<String>,
+    pub role: Option<String>,
+    pub office_id: Option<String>,
+    pub department_id: Option<String>,
+    pub team_id: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateUserResponse {
+    pub user: UserProfileResponse,
+    /// Only returned when generated, and only here.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporary_password: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -29,22 +90,29 @@ pub struct UserListResponse {
 
 pub async fn list_users(
     State(state): State<Arc<AppState>>,
-    Extension(_claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
     Query(query): Query<ListUsersQuery>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
     
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(10);
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
     let skip = (page - 1) * limit;
 
-    let total = match users_col.count_documents(doc! {}).await {
+    let filter = build_user_filter(tenant_id, &query);
+    let (filter, sort) = match filter.and_then(|filter| Ok((filter, build_user_sort(&query)?))) {
+        Ok(parts) => parts,
+        Err(msg) => return bad_request(msg),
+    };
+
+    let total = match users_col.count_documents(filter.clone()).await {
         Ok(count) => count,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
     };
 
     let mut cursor = match users_col
-        .find(doc! {})
+        .find(filter)
+        .sort(sort)
         .skip(skip)
         .limit(limit as i64)
         .await
@@ -55,16 +123,7 @@ pub async fn list_users(
 
     let mut users = Vec::new();
     while let Ok(Some(user)) = cursor.try_next().await {
-        users.push(UserProfileResponse {
-            id: user.id.expect("User should have an ID").to_hex(),
-            name: user.name,
-            email: user.email,
-            identifier: user.identifier,
-            role: user.role,
-            photo_url: user.photo_url,
-            has_face_landmarks: !user.face_landmarks.is_empty(),
-            office_location: user.office_location,
-        });
+        users.push(UserProfileResponse::from(user));
     }
 
     Json(UserListResponse {
@@ -75,9 +134,91 @@ pub async fn list_users(
     }).into_response()
 }
 
+/// Every filter maps to an indexed field; see `config::db::ensure_indexes`.
+fn build_user_filter(tenant_id: ObjectId, query: &ListUsersQuery) -> Result<Document, &'static str> {
+    let mut filter = doc! { "tenant_id": tenant_id };
+
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        filter.insert("$text", doc! { "$search": q });
+    }
+    if let Some(role) = &query.role {
+        if !matches!(role.as_str(), "user" | "admin" | "super_admin") {
+            return Err("Role must be user, admin or super_admin");
+        }
+        filter.insert("role", role);
+    }
+    if let Some(office_id) = &query.office_id {
+        let office_id = ObjectId::parse_str(office_id).map_err(|_| "Invalid office ID")?;
+        filter.insert("office_id", office_id);
+    }
+    if let Some(department_id) = &query.department_id {
+        let department_id = ObjectId::parse_str(department_id).map_err(|_| "Invalid department ID")?;
+        filter.insert("department_id", department_id);
+    }
+    if let Some(team_id) = &query.team_id {
+        let team_id = ObjectId::parse_str(team_id).map_err(|_| "Invalid team ID")?;
+        filter.insert("team_id", team_id);
+    }
+    match query.face_enrolled {
+        Some(true) => {
+            filter.insert("face_enrolled", true);
+        }
+        Some(false) => {
+            filter.insert("face_enrolled", doc! { "$ne": true });
+        }
+        None => {}
+    }
+    match query.status.as_deref().unwrap_or("active") {
+        "active" => {
+            filter.insert("deactivated", doc! { "$ne": true });
+        }
+        "deactivated" => {
+            filter.insert("deactivated", true);
+        }
+        "all" => {}
+        _ => return Err("Status must be active, deactivated or all"),
+    }
+
+    Ok(filter)
+}
+
+fn build_user_sort(query: &ListUsersQuery) -> Result<Document, &'static str> {
+    let direction = match query.order.as_deref().unwrap_or("asc") {
+        "asc" => 1,
+        "desc" => -1,
+        _ => return Err("Order must be asc or desc"),
+    };
+    let searching = query.q.as_deref().is_some_and(|q| !q.trim().is_empty());
+
+    // `_id` breaks ties so pages do not overlap
+    Ok(match query.sort.as_deref() {
+        None if searching => doc! { "score": { "$meta": "textScore" }, "_id": 1 },
+        None | Some("name") => doc! { "name": direction, "_id": 1 },
+        Some("email") => doc! { "email": direction, "_id": 1 },
+        Some("identifier") => doc! { "identifier": direction, "_id": 1 },
+        Some("created") => doc! { "_id": direction },
+        Some(_) => return Err("Sort must be name, email, identifier or created"),
+    })
+}
+
+/// Matches the user an admin acts on: in the admin's tenant, and not a super
+/// admin unless the admin is one too.
+fn target_filter(tenant_id: ObjectId, user_id: ObjectId, claims: &Claims) -> Document {
+    let mut filter = doc! { "_id": user_id, "tenant_id": tenant_id };
+    if claims.role != "super_admin" {
+        filter.insert("role", doc! { "$ne": "super_admin" });
+    }
+    filter
+}
+
+/// Soft-deletes a user: the account is deactivated and its sessions and
+/// pending one-time credentials are removed, while attendance history stays
+/// reportable until `utils::retention` purges it.
 pub async fn delete_user(
     State(state): State<Arc<AppState>>,
-    Extension(_claims): Extension<Claims>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
@@ -85,12 +226,521 @@ pub async fn delete_user(
         Ok(oid) => oid,
         Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
     };
+    let admin_id = ObjectId::parse_str(&claims.sub).ok();
+    if admin_id == Some(user_id) {
+        return (StatusCode::BAD_REQUEST, "You cannot delete your own account").into_response();
+    }
 
-    match users_col.delete_one(doc! { "_id": user_id }).await {
-        Ok(result) if result.deleted_count > 0 => {
+    let mut filter = target_filter(tenant_id, user_id, &claims);
+    filter.insert("deactivated", doc! { "$ne": true });
+    let deactivated = users_col
+        .find_one_and_update(
+            filter,
+            doc! {
+                "$set": {
+                    "deactivated": true,
+                    "deleted_at": DateTime::now(),
+                    "deleted_by": admin_id,
+                }
+            },
+        )
+        .await;
+
+    match deactivated {
+        Ok(Some(user)) => {
+            AuditEvent::new(tenant_id, admin_id, "user.deactivate", "user", Some(user_id))
+                .before(doc! { "deactivated": false })
+                .after(doc! { "deactivated": true })
+                .details(doc! { "name": &user.name, "email": &user.email, "identifier": &user.identifier })
+                .record(&state.db, &client)
+                .await;
+            if let Err(e) = revoke_user_tokens(&state, user_id).await {
+                eprintln!("Failed to revoke tokens of deleted user: {:?}", e);
+            }
+            for collection in ["password_resets", "email_verifications", "login_codes", "push_subscriptions"] {
+                if let Err(e) = state
+                    .db
+                    .collection::<Document>(collection)
+                    .delete_many(doc! { "user_id": user_id })
+                    .await
+                {
+                    eprintln!("Failed to clear {} of deleted user: {:?}", collection, e);
+                }
+            }
+            let data = serde_json::json!({
+                "user_id": user_id.to_hex(),
+                "name": user.name,
+                "email": user.email,
+                "identifier": user.identifier,
+            });
+            if let Err(e) = emit(&state, tenant_id, EVENT_USER_DELETED, data).await {
+                eprintln!("Failed to queue user deletion webhook: {:?}", e);
+            }
             (StatusCode::OK, "User deleted successfully").into_response()
         }
-        Ok(_) => (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+fn bad_request(message: &'static str) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+/// Resolves an office assignment; the empty string means no office.
+async fn resolve_office(state: &AppState, tenant_id: ObjectId, office_id: &str) -> Result<Option<Office>, Response> {
+    if office_id.is_empty() {
+        return Ok(None);
+    }
+    let office_id = ObjectId::parse_str(office_id).map_err(|_| bad_request("Invalid office ID"))?;
+    match state.db.collection::<Office>("offices").find_one(doc! { "_id": office_id, "tenant_id": tenant_id }).await {
+        Ok(Some(office)) => Ok(Some(office)),
+        Ok(None) => Err(bad_request("Office not found")),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()),
+    }
+}
+
+/// Resolves the department and team of a create or update request into the
+/// user fields to set. A team implies its department, so giving a team sets
+/// both, and changing only the department takes the user out of their team.
+async fn resolve_org(
+    state: &AppState,
+    tenant_id: ObjectId,
+    department_id: Option<&str>,
+    team_id: Option<&str>,
+) -> Result<Document, Response> {
+    let mut fields = Document::new();
+    if let Some(department_id) = department_id {
+        let department_id = if department_id.is_empty() {
+            None
+        } else {
+            let id = ObjectId::parse_str(department_id).map_err(|_| bad_request("Invalid department ID"))?;
+            match state
+                .db
+                .collection::<Department>("departments")
+                .find_one(doc! { "_id": id, "tenant_id": tenant_id })
+                .await
+            {
+                Ok(Some(_)) => Some(id),
+                Ok(None) => return Err(bad_request("Department not found")),
+                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()),
+            }
+        };
+        fields.insert("department_id", department_id);
+        fields.insert("team_id", None::<ObjectId>);
+    }
+    if let Some(team_id) = team_id.filter(|id| !id.is_empty()) {
+        let id = ObjectId::parse_str(team_id).map_err(|_| bad_request("Invalid team ID"))?;
+        let team = match state.db.collection::<Team>("teams").find_one(doc! { "_id": id, "tenant_id": tenant_id }).await {
+            Ok(Some(team)) => team,
+            Ok(None) => return Err(bad_request("Team not found")),
+            Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()),
+        };
+        if department_id.is_some() && fields.get_object_id("department_id").ok() != Some(team.department_id) {
+            return Err(bad_request("Team belongs to another department"));
+        }
+        fields.insert("department_id", team.department_id);
+        fields.insert("team_id", id);
+    } else if team_id.is_some() {
+        fields.insert("team_id", None::<ObjectId>);
+    }
+    Ok(fields)
+}
+
+/// Rejects an email or identifier that belongs to another account, including
+/// deactivated ones that have not been purged yet.
+async fn check_unique(
+    state: &AppState,
+    tenant_id: ObjectId,
+    email: Option<&str>,
+    identifier: Option<&str>,
+    exclude: Option<ObjectId>,
+) -> Result<(), Response> {
+    let mut candidates = Vec::new();
+    if let Some(email) = email {
+        candidates.push(doc! { "email": email });
+    }
+    if let Some(identifier) = identifier {
+        candidates.push(doc! { "identifier": identifier });
+    }
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let mut filter = doc! { "tenant_id": tenant_id, "$or": candidates };
+    if let Some(id) = exclude {
+        filter.insert("_id", doc! { "$ne": id });
+    }
+    match state.db.collection::<User>("users").find_one(filter).await {
+        Ok(None) => Ok(()),
+        Ok(Some(_)) => Err((StatusCode::CONFLICT, "User with this email or ID already exists").into_response()),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()),
+    }
+}
+
+pub async fn get_user(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let user_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return bad_request("Invalid user ID"),
+    };
+
+    match state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(user)) => Json(UserProfileResponse::from(user)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+/// Creates an account with a temporary password. The user has to verify the
+/// email address and choose a new password after the first login.
+pub async fn create_user(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<CreateUserRequest>,
+) -> impl IntoResponse {
+    let Ok(admin_id) = ObjectId::parse_str(&claims.sub) else {
+        return bad_request("Invalid user ID");
+    };
+
+    let name = payload.name.trim().to_string();
+    let email = normalize_address(&payload.email);
+    let identifier = payload.identifier.trim().to_string();
+    if name.is_empty() || identifier.is_empty() {
+        return bad_request("Name and identifier are required");
+    }
+    if !is_valid_address(&email) {
+        return bad_request("Invalid email address");
+    }
+    if !matches!(payload.role.as_str(), "user" | "admin") {
+        return bad_request("Role must be user or admin");
+    }
+    let locale = payload.locale.unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+    if !LOCALES.contains(&locale.as_str()) {
+        return bad_request("Unsupported locale");
+    }
+    if let Err(response) = check_unique(&state, tenant_id, Some(&email), Some(&identifier), None).await {
+        return response;
+    }
+    let office = match resolve_office(&state, tenant_id, payload.office_id.as_deref().unwrap_or_default()).await {
+        Ok(office) => office,
+        Err(response) => return response,
+    };
+    let org = match resolve_org(&state, tenant_id, payload.department_id.as_deref(), payload.team_id.as_deref()).await {
+        Ok(org) => org,
+        Err(response) => return response,
+    };
+
+    let (password, generated) = match payload.password {
+        Some(password) => {
+            let violations = check_policy(&state.password_policy, &password, &email, &identifier);
+            if !violations.is_empty() {
+                return violations_response(violations);
+            }
+            (password, false)
+        }
+        None => (generate_temporary_password(&state.password_policy), true),
+    };
+    let password_hash = match hash_password(&password) {
+        Ok(h) => h,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error hashing password").into_response(),
+    };
+
+    let mut new_user = User {
+        id: None,
+        tenant_id,
+        name,
+        email: email.clone(),
+        email_verified: false,
+        pending_email: None,
+        identifier,
+        password_hash,
+        password_history: vec![],
+        role: payload.role,
+        office_location: OfficeLocation {
+            r#type: "Point".to_string(),
+            coordinates: office
+                .as_ref()
+                .map(|o| o.location.coordinates.clone())
+                .unwrap_or_else(|| vec![0.0, 0.0]),
+        },
+        face_landmarks: vec![],
+        face_enrolled: false,
+        photo_url: None,
+        token_version: 0,
+        office_id: office.and_then(|o| o.id),
+        department_id: org.get_object_id("department_id").ok(),
+        team_id: org.get_object_id("team_id").ok(),
+        oidc_subject: None,
+        ldap_dn: None,
+        locale,
+        must_change_password: true,
+        deactivated: false,
+        deleted_at: None,
+        deleted_by: None,
+    };
+
+    match state.db.collection::<User>("users").insert_one(&new_user).await {
+        Ok(result) => new_user.id = result.inserted_id.as_object_id(),
+        Err(e) if is_duplicate_key(&e) => {
+            return (StatusCode::CONFLICT, "User with this email or ID already exists").into_response()
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving user").into_response(),
+    }
+    let user_id = new_user.id.unwrap();
+
+    AuditEvent::new(tenant_id, Some(admin_id), "user.create", "user", Some(user_id))
+        .after(doc! {
+            "name": &new_user.name,
+            "email": &new_user.email,
+            "identifier": &new_user.identifier,
+            "role": &new_user.role,
+            "office_id": new_user.office_id,
+            "department_id": new_user.department_id,
+            "team_id": new_user.team_id,
+        })
+        .record(&state.db, &client)
+        .await;
+
+    let data = serde_json::json!({
+        "user_id": user_id.to_hex(),
+        "name": &new_user.name,
+        "email": &new_user.email,
+        "identifier": &new_user.identifier,
+        "role": &new_user.role,
+        "created_by": admin_id.to_hex(),
+    });
+    if let Err(e) = emit(&state, tenant_id, EVENT_USER_REGISTERED, data).await {
+        eprintln!("Failed to queue registration webhook: {:?}", e);
+    }
+    if let Err(e) = start_email_verification(&state, &new_user, &email, "signup").await {
+        eprintln!("Email verification error: {:?}", e);
+    }
+
+    (
+        StatusCode::CREATED,
+        Json(CreateUserResponse {
+            user: UserProfileResponse::from(new_user),
+            temporary_password: generated.then_some(password),
+        }),
+    )
+        .into_response()
+}
+
+pub async fn update_user(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateUserRequest>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(user_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid user ID");
+    };
+
+    let mut update = Document::new();
+    if let Some(name) = payload.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return bad_request("Name cannot be empty");
+        }
+        update.insert("name", name);
+    }
+    let email = payload.email.as_deref().map(normalize_address);
+    if let Some(email) = &email {
+        if !is_valid_address(email) {
+            return bad_request("Invalid email address");
+        }
+        // A new address has to be verified again
+        update.insert("email", email);
+        update.insert("email_verified", false);
+    }
+    let identifier = payload.identifier.map(|i| i.trim().to_string());
+    if let Some(identifier) = &identifier {
+        if identifier.is_empty() {
+            return bad_request("Identifier cannot be empty");
+        }
+        update.insert("identifier", identifier);
+    }
+    if let Some(role) = payload.role {
+        let allowed = match claims.role.as_str() {
+            "super_admin" => matches!(role.as_str(), "user" | "admin" | "super_admin"),
+            _ => matches!(role.as_str(), "user" | "admin"),
+        };
+        if !allowed {
+            return bad_request("Role must be user or admin");
+        }
+        // Stops the last admin from locking everyone out by accident
+        if user_id == admin_id && role != claims.role {
+            return bad_request("You cannot remove your own admin role");
+        }
+        update.insert("role", role);
+    }
+    if let Some(office_id) = payload.office_id {
+        match resolve_office(&state, tenant_id, &office_id).await {
+            Ok(office) => update.insert("office_id", office.and_then(|o| o.id)),
+            Err(response) => return response,
+        };
+    }
+    match resolve_org(&state, tenant_id, payload.department_id.as_deref(), payload.team_id.as_deref()).await {
+        Ok(org) => update.extend(org),
+        Err(response) => return response,
+    }
+    if let Some(locale) = payload.locale {
+        if !LOCALES.contains(&locale.as_str()) {
+            return bad_request("Unsupported locale");
+        }
+        update.insert("locale", locale);
+    }
+    if update.is_empty() {
+        return bad_request("No fields to update");
+    }
+    if let Err(response) = check_unique(&state, tenant_id, email.as_deref(), identifier.as_deref(), Some(user_id)).await {
+        return response;
+    }
+
+    let changes = update.clone();
+    let mut update = doc! { "$set": update };
+    if email.is_some() {
+        update.insert("$unset", doc! { "pending_email": "" });
+    }
+
+    // Returns the user as it was, for the audit diff
+    let users_col = state.db.collection::<User>("users");
+    let previous = match users_col.find_one_and_update(target_filter(tenant_id, user_id, &claims), update).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) if is_duplicate_key(&e) => {
+            return (StatusCode::CONFLICT, "User with this email or ID already exists").into_response()
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    // Tokens carry the role; make the change take effect right away
+    state.token_cache.invalidate(&user_id);
+    AuditEvent::new(tenant_id, Some(admin_id), "user.update", "user", Some(user_id))
+        .changes(&previous, &changes)
+        .record(&state.db, &client)
+        .await;
+
+    let user = match users_col.find_one(doc! { "_id": user_id }).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    if let Some(email) = &email {
+        if let Err(e) = start_email_verification(&state, &user, email, "signup").await {
+            eprintln!("Email verification error: {:?}", e);
+        }
+    }
+
+    Json(UserProfileResponse::from(user)).into_response()
+}
+
+/// Invalidates the current password and sessions and emails the user a reset
+/// link.
+pub async fn force_password_reset(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(user_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid user ID");
+    };
+    let users_col = state.db.collection::<User>("users");
+
+    let mut filter = target_filter(tenant_id, user_id, &claims);
+    filter.insert("deactivated", doc! { "$ne": true });
+    let user = match users_col.find_one(filter).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    // An empty hash never verifies, so only the emailed link can set a new one
+    let cleared = users_col
+        .update_one(
+            doc! { "_id": user_id },
+            doc! {
+                "$set": { "password_hash": "" },
+                "$push": {
+                    "password_history": {
+                        "$each": [&user.password_hash],
+                        "$slice": -(state.password_policy.history_size as i64),
+                    }
+                }
+            },
+        )
+        .await;
+    if cleared.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+    }
+    if let Err(e) = revoke_user_tokens(&state, user_id).await {
+        eprintln!("Failed to revoke sessions for password reset: {:?}", e);
+    }
+    AuditEvent::new(tenant_id, Some(admin_id), "user.force_password_reset", "user", Some(user_id))
+        .record(&state.db, &client)
+        .await;
+
+    match send_password_reset(&state, &user).await {
+        Ok(_) => (StatusCode::OK, "Password reset link sent").into_response(),
+        Err(e) => {
+            eprintln!("Email error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error queueing email").into_response()
+        }
+    }
+}
+
+pub async fn reactivate_user(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (Ok(admin_id), Ok(user_id)) = (ObjectId::parse_str(&claims.sub), ObjectId::parse_str(&id)) else {
+        return bad_request("Invalid user ID");
+    };
+
+    let mut filter = target_filter(tenant_id, user_id, &claims);
+    filter.insert("deactivated", true);
+    let result = state
+        .db
+        .collection::<User>("users")
+        .update_one(
+            filter,
+            doc! {
+                "$set": { "deactivated": false },
+                "$unset": { "deleted_at": "", "deleted_by": "" },
+            },
+        )
+        .await;
+
+    match result {
+        Ok(result) if result.matched_count > 0 => {
+            state.token_cache.invalidate(&user_id);
+            AuditEvent::new(tenant_id, Some(admin_id), "user.reactivate", "user", Some(user_id))
+                .before(doc! { "deactivated": true })
+                .after(doc! { "deactivated": false })
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "User reactivated").into_response()
+        }
+        Ok(_) => (StatusCode::NOT_FOUND, "No deactivated user with that ID").into_response(),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
     }
 }
@@ -0,0 +1,345 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::models::webhook::{Webhook, WebhookDelivery};
+use crate::utils::audit::AuditEvent;
+use crate::utils::jwt::Claims;
+use crate::utils::token::generate_token;
+use crate::utils::webhook::{check_host, send_test, WEBHOOK_EVENTS};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    /// Generated when left out.
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub description: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ListDeliveriesQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+/// The secret is only returned when the webhook is created.
+#[derive(Serialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<Webhook> for WebhookResponse {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id.unwrap().to_hex(),
+            url: webhook.url,
+            events: webhook.events,
+            active: webhook.active,
+            description: webhook.description,
+            created_at: webhook.created_at,
+            secret: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeliveryResponse {
+    pub id: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryListResponse {
+    pub deliveries: Vec<DeliveryResponse>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+}
+
+fn validate_url(url: &str) -> Result<(), &'static str> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => check_host(&parsed),
+        _ => Err("URL must be an absolute http(s) URL"),
+    }
+}
+
+fn validate_events(events: &[String]) -> Result<(), &'static str> {
+    if events.is_empty() {
+        return Err("Subscribe to at least one event");
+    }
+    if !events.iter().all(|e| WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err("Unknown event type");
+    }
+    Ok(())
+}
+
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    let webhooks_col = state.db.collection::<Webhook>("webhooks");
+
+    let mut cursor = match webhooks_col.find(doc! { "tenant_id": tenant_id }).sort(doc! { "created_at": -1 }).await {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut webhooks = Vec::new();
+    while let Ok(Some(webhook)) = cursor.try_next().await {
+        webhooks.push(WebhookResponse::from(webhook));
+    }
+
+    Json(webhooks).into_response()
+}
+
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> impl IntoResponse {
+    if let Err(msg) = validate_url(&payload.url).and_then(|_| validate_events(&payload.events)) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let secret = payload.secret.unwrap_or_else(generate_token);
+    if secret.len() < 16 {
+        return (StatusCode::BAD_REQUEST, "Secret must be at least 16 characters").into_response();
+    }
+
+    let mut webhook = Webhook {
+        id: None,
+        tenant_id,
+        url: payload.url,
+        secret: secret.clone(),
+        events: payload.events,
+        active: true,
+        description: payload.description,
+        created_at: Utc::now(),
+    };
+
+    match state.db.collection::<Webhook>("webhooks").insert_one(&webhook).await {
+        Ok(result) => webhook.id = result.inserted_id.as_object_id(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "webhook.create", "webhook", webhook.id)
+        .after(doc! { "url": &webhook.url, "events": &webhook.events, "description": &webhook.description })
+        .record(&state.db, &client)
+        .await;
+
+    let mut response = WebhookResponse::from(webhook);
+    response.secret = Some(secret);
+    (StatusCode::CREATED, Json(response)).into_response()
+}
+
+pub async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> impl IntoResponse {
+    let webhook_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid webhook ID").into_response(),
+    };
+
+    let mut update = Document::new();
+    if let Some(url) = payload.url {
+        if let Err(msg) = validate_url(&url) {
+            return (StatusCode::BAD_REQUEST, msg).into_response();
+        }
+        update.insert("url", url);
+    }
+    if let Some(events) = payload.events {
+        if let Err(msg) = validate_events(&events) {
+            return (StatusCode::BAD_REQUEST, msg).into_response();
+        }
+        update.insert("events", events);
+    }
+    if let Some(description) = payload.description {
+        update.insert("description", description);
+    }
+    if let Some(active) = payload.active {
+        update.insert("active", active);
+    }
+    if update.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No fields to update").into_response();
+    }
+
+    match state
+        .db
+        .collection::<Webhook>("webhooks")
+        .find_one_and_update(doc! { "_id": webhook_id, "tenant_id": tenant_id }, doc! { "$set": update.clone() })
+        .await
+    {
+        Ok(Some(previous)) => {
+            AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "webhook.update", "webhook", Some(webhook_id))
+                .changes(&previous, &update)
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Webhook updated").into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Webhook not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let webhook_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid webhook ID").into_response(),
+    };
+
+    // Queued deliveries notice the webhook is gone and are marked failed
+    match state
+        .db
+        .collection::<Webhook>("webhooks")
+        .find_one_and_delete(doc! { "_id": webhook_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(Some(webhook)) => {
+            AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "webhook.delete", "webhook", Some(webhook_id))
+                .before(doc! { "url": &webhook.url, "events": &webhook.events, "description": &webhook.description })
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Webhook deleted").into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Webhook not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+pub async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Path(id): Path<String>,
+    Query(query): Query<ListDeliveriesQuery>,
+) -> impl IntoResponse {
+    let webhook_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid webhook ID").into_response(),
+    };
+    let deliveries_col = state.db.collection::<WebhookDelivery>("webhook_deliveries");
+
+    // Deliveries carry no tenant of their own
+    match state
+        .db
+        .collection::<Webhook>("webhooks")
+        .count_documents(doc! { "_id": webhook_id, "tenant_id": tenant_id })
+        .await
+    {
+        Ok(0) => return (StatusCode::NOT_FOUND, "Webhook not found").into_response(),
+        Ok(_) => {}
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let skip = (page - 1) * limit;
+    let filter = doc! { "webhook_id": webhook_id };
+
+    let total = match deliveries_col.count_documents(filter.clone()).await {
+        Ok(count) => count,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut cursor = match deliveries_col
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .skip(skip)
+        .limit(limit as i64)
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut deliveries = Vec::new();
+    while let Ok(Some(delivery)) = cursor.try_next().await {
+        deliveries.push(DeliveryResponse {
+            id: delivery.id.unwrap().to_hex(),
+            event: delivery.event,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            response_status: delivery.response_status,
+            last_error: delivery.last_error,
+            created_at: delivery.created_at,
+            delivered_at: delivery.delivered_at,
+        });
+    }
+
+    Json(DeliveryListResponse {
+        deliveries,
+        total,
+        page,
+        limit,
+    }).into_response()
+}
+
+pub async fn test_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let webhook_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid webhook ID").into_response(),
+    };
+
+    let webhook = match state.db.collection::<Webhook>("webhooks").find_one(doc! { "_id": webhook_id, "tenant_id": tenant_id }).await {
+        Ok(Some(w)) => w,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Webhook not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    match send_test(&state.db, &webhook).await {
+        Ok(delivery_id) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "delivery_id": delivery_id.to_hex() })),
+        )
+            .into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
@@ -1,8 +1,11 @@
-use crate::models::attendance::{Attendance, GeoPoint};
+use crate::middleware::client::ClientInfo;
+use crate::models::attendance::{Attendance, DeviceInfo, GeoPoint};
+use crate::models::office::Office;
 use crate::models::user::{OfficeLocation, User};
 use crate::utils::face::compare_landmarks;
-use crate::utils::geofence::is_within_geofence;
+use crate::utils::geofence::{calculate_distance, check_in_fence};
 use crate::utils::jwt::Claims;
+use crate::utils::webhook::{emit, EVENT_CHECK_IN, EVENT_CHECK_OUT};
 use crate::AppState;
 use axum::{
     extract::{Extension, State},
@@ -33,6 +36,7 @@ pub struct AttendanceResponse {
 pub async fn check_in_out(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<AttendanceRequest>,
 ) -> impl IntoResponse {
     let user_col = state.db.collection::<User>("users");
@@ -50,7 +54,19 @@ pub async fn check_in_out(
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
     };
 
-    // 2. Check if user has registered face
+    // 2. Only active accounts with a proven email may check in
+    if user.deactivated {
+        return (StatusCode::FORBIDDEN, "Account is deactivated").into_response();
+    }
+    if !user.email_verified {
+        return (
+            StatusCode::FORBIDDEN,
+            "Please verify your email before checking in.",
+        )
+            .into_response();
+    }
+
+    // 3. Check if user has registered face
     if user.face_landmarks.is_empty() {
         return (
             StatusCode::BAD_REQUEST,
@@ -59,27 +75,36 @@ pub async fn check_in_out(
             .into_response();
     }
 
-    // 3. Validate Geofence (Monas)
+    // 4. Validate Geofence: the assigned office, or the default fence
     let user_loc = OfficeLocation {
         r#type: "Point".to_string(),
         coordinates: vec![payload.longitude, payload.latitude],
     };
 
-    // Monas Coordinates: -6.175392, 106.827153
-    let monas_loc = OfficeLocation {
-        r#type: "Point".to_string(),
-        coordinates: vec![106.58955212564815, -6.288265750819264],
+    let office = match user.office_id {
+        Some(office_id) => match state
+            .db
+            .collection::<Office>("offices")
+            .find_one(doc! { "_id": office_id, "tenant_id": user.tenant_id })
+            .await
+        {
+            Ok(office) => office,
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        },
+        None => None,
     };
+    let fence = check_in_fence(office);
 
-    if !is_within_geofence(&user_loc, &monas_loc, 200.0) {
+    let distance_m = calculate_distance(&user_loc, &fence.center);
+    if distance_m > fence.radius_m {
         return (
             StatusCode::BAD_REQUEST,
-            "You are outside the office radius (Monas)",
+            "You are outside the office radius",
         )
             .into_response();
     }
 
-    // 4. Validate Face
+    // 5. Validate Face
     let similarity = compare_landmarks(&payload.landmarks, &user.face_landmarks);
     if similarity < 0.8 {
         return (
@@ -89,7 +114,7 @@ pub async fn check_in_out(
             .into_response();
     }
 
-    // 5. Determine In/Out
+    // 6. Determine In/Out
     let now_utc = Utc::now();
 
     // WIB Timezone (UTC+7)
@@ -98,7 +123,7 @@ pub async fn check_in_out(
     let today_date = now_wib.date_naive();
 
     // Query all today's logs for this user (same approach as dashboard.rs)
-    let filter = doc! { "user_id": user_id };
+    let filter = doc! { "tenant_id": user.tenant_id, "user_id": user_id, "voided": { "$ne": true } };
 
     let mut cursor = match attendance_col
         .find(filter)
@@ -107,7 +132,7 @@ pub async fn check_in_out(
         .await
     {
         Ok(c) => c,
-        Err(e) => {
+        Err(_) => {
             return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
         }
     };
@@ -138,9 +163,10 @@ pub async fn check_in_out(
         }
     };
 
-    // 6. Insert Attendance
+    // 7. Insert Attendance
     let new_attendance = Attendance {
         id: None,
+        tenant_id: user.tenant_id,
         user_id,
         timestamp: now_utc,
         r#type: attendance_type.clone(),
@@ -149,10 +175,20 @@ pub async fn check_in_out(
             coordinates: vec![payload.longitude, payload.latitude],
         },
         face_verified: true,
+        office_id: fence.office_id,
+        distance_m: Some(distance_m),
+        face_similarity: Some(similarity),
+        device: Some(DeviceInfo {
+            ip: client.ip,
+            user_agent: client.user_agent,
+        }),
+        manual: false,
+        voided: false,
+        revisions: Vec::new(),
     };
 
-    match attendance_col.insert_one(new_attendance).await {
-        Ok(_) => (),
+    let attendance_id = match attendance_col.insert_one(new_attendance).await {
+        Ok(result) => result.inserted_id.as_object_id(),
         Err(_) => {
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -160,6 +196,21 @@ pub async fn check_in_out(
             )
                 .into_response()
         }
+    };
+
+    let event = if attendance_type == "In" { EVENT_CHECK_IN } else { EVENT_CHECK_OUT };
+    let data = serde_json::json!({
+        "attendance_id": attendance_id.map(|id| id.to_hex()),
+        "user_id": user_id.to_hex(),
+        "identifier": &user.identifier,
+        "name": &user.name,
+        "type": &attendance_type,
+        "timestamp": now_utc.to_rfc3339(),
+        "latitude": payload.latitude,
+        "longitude": payload.longitude,
+    });
+    if let Err(e) = emit(&state, user.tenant_id, event, data).await {
+        eprintln!("Failed to queue attendance webhook: {:?}", e);
     }
 
     Json(AttendanceResponse {
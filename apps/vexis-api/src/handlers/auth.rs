@@ -1,17 +1,28 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, Extension, State},
     Json,
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
+use std::net::SocketAddr;
 use std::sync::Arc;
 use crate::AppState;
+use crate::config::db::is_duplicate_key;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
 use crate::models::user::{User, OfficeLocation};
-use crate::models::auth::{PasswordReset, RefreshToken};
+use crate::models::auth::{EmailVerification, PasswordReset, RefreshToken, SecurityEvent};
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::normalize_address;
+use crate::utils::jobs::enqueue_email;
 use crate::utils::jwt::create_access_token;
-use crate::utils::email::send_reset_email;
+use crate::utils::email_templates::{DEFAULT_LOCALE, LOCALES};
+use crate::utils::password::{check_policy, hash_password, is_reused, violations_response, PolicyViolation};
+use crate::utils::revocation::revoke_user_tokens;
+use crate::utils::token::{generate_token, hash_token};
+use crate::utils::webhook::{emit, EVENT_USER_REGISTERED};
 use serde::{Deserialize, Serialize};
-use bcrypt::{hash, verify, DEFAULT_COST};
+use serde_json::json;
 use mongodb::bson::doc;
 use chrono::{Utc, Duration};
 use uuid::Uuid;
@@ -27,15 +38,25 @@ pub struct ResetPasswordRequest {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
 #[derive(Deserialize)]
 pub struct RegisterRequest {
     pub name: String,
     pub email: String,
     pub identifier: String,
     pub password: String,
-    pub role: String,
     pub lat: f64,
     pub long: f64,
+    pub locale: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -69,18 +90,24 @@ pub struct UserResponse {
     pub email: String,
     pub identifier: String,
     pub role: String,
+    /// The account still has an admin-issued temporary password.
+    pub must_change_password: bool,
 }
 
 pub async fn register(
     State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<RegisterRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
+    let email = normalize_address(&payload.email);
 
     // Check if user already exists
     let filter = doc! { 
+        "tenant_id": tenant_id,
         "$or": [
-            { "email": &payload.email },
+            { "email": &email },
             { "identifier": &payload.identifier }
         ]
     };
@@ -88,73 +115,287 @@ pub async fn register(
         return (StatusCode::BAD_REQUEST, "User with this email or ID already exists").into_response();
     }
 
+    let violations = check_policy(&state.password_policy, &payload.password, &email, &payload.identifier);
+    if !violations.is_empty() {
+        return violations_response(violations);
+    }
+
+    let locale = payload.locale.unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+    if !LOCALES.contains(&locale.as_str()) {
+        return (StatusCode::BAD_REQUEST, "Unsupported locale").into_response();
+    }
+
     // Hash password
-    let password_hash = match hash(payload.password, DEFAULT_COST) {
+    let password_hash = match hash_password(&payload.password) {
         Ok(h) => h,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error hashing password").into_response(),
     };
 
-    let new_user = User {
+    let mut new_user = User {
         id: None,
+        tenant_id,
         name: payload.name,
-        email: payload.email,
+        email,
+        email_verified: false,
+        pending_email: None,
         identifier: payload.identifier,
         password_hash,
-        role: payload.role,
+        password_history: vec![],
+        // Admins are only ever created or promoted by another admin
+        role: "user".to_string(),
         office_location: OfficeLocation {
             r#type: "Point".to_string(),
             coordinates: vec![payload.long, payload.lat],
         },
         face_landmarks: vec![],
+        face_enrolled: false,
         photo_url: None,
+        token_version: 0,
+        office_id: None,
+        department_id: None,
+        team_id: None,
+        oidc_subject: None,
+        ldap_dn: None,
+        locale,
+        must_change_password: false,
+        deactivated: false,
+        deleted_at: None,
+        deleted_by: None,
     };
 
-    match users_col.insert_one(new_user).await {
-        Ok(_) => (StatusCode::CREATED, "User registered successfully").into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error saving user").into_response(),
+    match users_col.insert_one(&new_user).await {
+        Ok(result) => new_user.id = result.inserted_id.as_object_id(),
+        // Registered by a concurrent request since the check above
+        Err(e) if is_duplicate_key(&e) => {
+            return (StatusCode::CONFLICT, "User with this email or ID already exists").into_response()
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving user").into_response(),
+    };
+    AuditEvent::new(tenant_id, new_user.id, "user.register", "user", new_user.id)
+        .after(doc! {
+            "name": &new_user.name,
+            "email": &new_user.email,
+            "identifier": &new_user.identifier,
+            "role": &new_user.role,
+        })
+        .record(&state.db, &client)
+        .await;
+
+    let data = serde_json::json!({
+        "user_id": new_user.id.map(|id| id.to_hex()),
+        "name": &new_user.name,
+        "email": &new_user.email,
+        "identifier": &new_user.identifier,
+        "role": &new_user.role,
+    });
+    if let Err(e) = emit(&state, tenant_id, EVENT_USER_REGISTERED, data).await {
+        eprintln!("Failed to queue registration webhook: {:?}", e);
     }
+
+    // The account exists either way; a failed email can be retried via resend
+    if let Err(e) = start_email_verification(&state, &new_user, &new_user.email, "signup").await {
+        eprintln!("Email verification error: {:?}", e);
+    }
+
+    (StatusCode::CREATED, "User registered successfully. Please verify your email.").into_response()
 }
 
-pub async fn login(
+/// Stores a verification token for `email` and queues the link to that address.
+pub async fn start_email_verification(
+    state: &AppState,
+    user: &User,
+    email: &str,
+    purpose: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let verifications_col = state.db.collection::<EmailVerification>("email_verifications");
+    let user_id = user.id.ok_or("user has no id")?;
+
+    let token = generate_token();
+    let verification = EmailVerification {
+        user_id,
+        email: email.to_string(),
+        token_hash: hash_token(&token),
+        purpose: purpose.to_string(),
+        expires_at: Utc::now() + Duration::hours(24),
+    };
+
+    // Only the latest link for the same purpose stays valid
+    verifications_col
+        .delete_many(doc! { "user_id": user_id, "purpose": purpose })
+        .await?;
+    verifications_col.insert_one(verification).await?;
+    enqueue_email(
+        &state.db,
+        email,
+        &user.locale,
+        "email_verification",
+        json!({ "name": &user.name, "token": token }),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn verify_email(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<LoginRequest>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<VerifyEmailRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
-    let refresh_col = state.db.collection::<RefreshToken>("refresh_tokens");
+    let verifications_col = state.db.collection::<EmailVerification>("email_verifications");
+
+    let verification = match verifications_col
+        .find_one_and_delete(doc! { "token_hash": hash_token(&payload.token) })
+        .await
+    {
+        Ok(Some(v)) if v.expires_at > Utc::now() => v,
+        Ok(_) => return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
 
-    let filter = doc! {
-        "$or": [
-            { "email": &payload.email_or_id },
-            { "identifier": &payload.email_or_id }
-        ]
+    let set = if verification.purpose == "change" {
+        let tenant_id = match users_col.find_one(doc! { "_id": verification.user_id }).await {
+            Ok(Some(user)) => user.tenant_id,
+            Ok(None) => return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        };
+        // The address may have been taken since the change was requested
+        match users_col
+            .find_one(doc! {
+                "tenant_id": tenant_id,
+                "email": &verification.email,
+                "_id": { "$ne": verification.user_id },
+            })
+            .await
+        {
+            Ok(None) => {}
+            Ok(Some(_)) => return (StatusCode::CONFLICT, "Email is already in use").into_response(),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        }
+        doc! { "email": &verification.email, "email_verified": true }
+    } else {
+        doc! { "email_verified": true }
+    };
+    let mut update = doc! { "$set": set.clone() };
+    if verification.purpose == "change" {
+        update.insert("$unset", doc! { "pending_email": "" });
+    }
+
+    // The link only proves the address it was sent to
+    let filter = if verification.purpose == "change" {
+        doc! { "_id": verification.user_id, "pending_email": &verification.email }
+    } else {
+        doc! { "_id": verification.user_id, "email": &verification.email }
     };
 
-    let user = match users_col.find_one(filter).await {
+    match users_col.find_one_and_update(filter, update).await {
+        Err(e) if is_duplicate_key(&e) => (StatusCode::CONFLICT, "Email is already in use").into_response(),
+        Ok(Some(previous)) => {
+            let action = if verification.purpose == "change" { "user.email_change" } else { "user.email_verify" };
+            AuditEvent::new(previous.tenant_id, previous.id, action, "user", previous.id)
+                .changes(&previous, &set)
+                .record(&state.db, &client)
+                .await;
+            (StatusCode::OK, "Email verified").into_response()
+        }
+        Ok(None) => (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error verifying email").into_response(),
+    }
+}
+
+pub async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Json(payload): Json<ResendVerificationRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let generic = (StatusCode::OK, "If that account needs verification, a new link has been sent.");
+
+    let user = match users_col
+        .find_one(doc! {
+            "tenant_id": tenant_id,
+            "email": normalize_address(&payload.email),
+            "email_verified": false,
+            "deactivated": { "$ne": true },
+        })
+        .await
+    {
         Ok(Some(u)) => u,
-        Ok(None) => return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response(),
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+        _ => return generic.into_response(),
     };
 
-    if !verify(payload.password, &user.password_hash).unwrap_or(false) {
+    match start_email_verification(&state, &user, &user.email, "signup").await {
+        Ok(_) => generic.into_response(),
+        Err(e) => {
+            eprintln!("Email verification error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error queueing email").into_response()
+        }
+    }
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<LoginRequest>,
+) -> impl IntoResponse {
+    let mut authenticated = None;
+    for provider in &state.auth_providers {
+        match provider.authenticate(&state.db, tenant_id, &payload.email_or_id, &payload.password).await {
+            Ok(Some(user)) => {
+                authenticated = Some(user);
+                break;
+            }
+            Ok(None) => continue,
+            Err(e) => {
+                // One provider being down must not block the others
+                eprintln!("Auth provider {} failed: {}", provider.name(), e);
+            }
+        }
+    }
+
+    let Some(user) = authenticated else {
+        AuditEvent::new(tenant_id, None, "auth.login_failed", "user", None)
+            .details(doc! { "method": "password", "login": &payload.email_or_id })
+            .record(&state.db, &client)
+            .await;
         return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response();
+    };
+
+    complete_login(&state, &client, user, "password").await
+}
+
+/// Records a login by `method` in the audit log and issues the session.
+pub async fn complete_login(state: &AppState, client: &ClientInfo, user: User, method: &str) -> Response {
+    let event = if user.deactivated {
+        AuditEvent::new(user.tenant_id, None, "auth.login_failed", "user", user.id)
+            .details(doc! { "method": method, "reason": "deactivated" })
+    } else {
+        AuditEvent::new(user.tenant_id, user.id, "auth.login", "user", user.id).details(doc! { "method": method })
+    };
+    event.record(&state.db, client).await;
+
+    issue_session(state, user).await
+}
+
+/// Issues an access token and a refresh token in a new family for an
+/// authenticated user. Shared by every login method.
+pub async fn issue_session(state: &AppState, user: User) -> Response {
+    if user.deactivated {
+        return (StatusCode::FORBIDDEN, "Account is deactivated").into_response();
     }
 
-    let access_token = match create_access_token(&user.id.unwrap().to_hex(), &user.role) {
+    let access_token = match create_access_token(&state.jwt, &user) {
         Ok(t) => t,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Token generation error").into_response(),
     };
 
-    let refresh_token = Uuid::new_v4().to_string();
-    let refresh_token_doc = RefreshToken {
-        user_id: user.id.unwrap(),
-        token: refresh_token.clone(),
-        expires_at: Utc::now() + Duration::days(7),
+    // Each login starts a new token family
+    let family_id = Uuid::new_v4().to_string();
+    let refresh_token = match issue_refresh_token(state, &user, &family_id).await {
+        Ok(t) => t,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving refresh token").into_response(),
     };
 
-    if let Err(_) = refresh_col.insert_one(refresh_token_doc).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving refresh token").into_response();
-    }
-
     Json(AuthResponse {
         access_token,
         refresh_token,
@@ -164,124 +405,302 @@ pub async fn login(
             email: user.email,
             identifier: user.identifier,
             role: user.role,
+            must_change_password: user.must_change_password,
         },
     }).into_response()
 }
 
 pub async fn refresh_token(
     State(state): State<Arc<AppState>>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<RefreshRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
     let refresh_col = state.db.collection::<RefreshToken>("refresh_tokens");
+    let token_hash = hash_token(&payload.refresh_token);
 
-    let stored_token = match refresh_col.find_one(doc! { "token": &payload.refresh_token }).await {
+    // Claim the token atomically so two concurrent refreshes cannot both rotate it
+    let claimed = refresh_col
+        .find_one_and_update(
+            doc! { "token_hash": &token_hash, "rotated_at": null },
+            doc! { "$set": { "rotated_at": mongodb::bson::DateTime::now() } },
+        )
+        .await;
+
+    let stored_token = match claimed {
         Ok(Some(t)) => t,
-        _ => return (StatusCode::UNAUTHORIZED, "Invalid refresh token").into_response(),
+        Ok(None) => {
+            // Either unknown, or already rotated: the latter means the token was replayed
+            if let Ok(Some(reused)) = refresh_col.find_one(doc! { "token_hash": &token_hash }).await {
+                revoke_token_family(&state, &client, &reused).await;
+                return (StatusCode::UNAUTHORIZED, "Refresh token reuse detected").into_response();
+            }
+            return (StatusCode::UNAUTHORIZED, "Invalid refresh token").into_response();
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
     };
 
     if stored_token.expires_at < Utc::now() {
-        let _ = refresh_col.delete_one(doc! { "token": &payload.refresh_token }).await;
+        let _ = refresh_col.delete_one(doc! { "token_hash": &token_hash }).await;
         return (StatusCode::UNAUTHORIZED, "Refresh token expired").into_response();
     }
 
-    let user = match users_col.find_one(doc! { "_id": stored_token.user_id }).await {
+    let user = match users_col.find_one(doc! { "_id": stored_token.user_id, "deactivated": { "$ne": true } }).await {
         Ok(Some(u)) => u,
         _ => return (StatusCode::UNAUTHORIZED, "User not found").into_response(),
     };
 
     // Rotate tokens
-    let new_access_token = match create_access_token(&user.id.unwrap().to_hex(), &user.role) {
+    let new_access_token = match create_access_token(&state.jwt, &user) {
         Ok(t) => t,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Token generation error").into_response(),
     };
 
-    let new_refresh_token = Uuid::new_v4().to_string();
-    let new_refresh_token_doc = RefreshToken {
-        user_id: user.id.unwrap(),
-        token: new_refresh_token.clone(),
-        expires_at: Utc::now() + Duration::days(7),
+    let new_refresh_token = match issue_refresh_token(&state, &user, &stored_token.family_id).await {
+        Ok(t) => t,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving new refresh token").into_response(),
     };
 
-    // Delete old token and insert new one
-    let _ = refresh_col.delete_one(doc! { "token": &payload.refresh_token }).await;
-    
-    if let Err(_) = refresh_col.insert_one(new_refresh_token_doc).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving new refresh token").into_response();
-    }
-
     Json(RefreshResponse {
         access_token: new_access_token,
         refresh_token: new_refresh_token,
     }).into_response()
 }
 
+/// Stores a new refresh token in `family_id` and returns the raw token. Only
+/// its hash is persisted.
+async fn issue_refresh_token(
+    state: &AppState,
+    user: &User,
+    family_id: &str,
+) -> Result<String, mongodb::error::Error> {
+    let refresh_col = state.db.collection::<RefreshToken>("refresh_tokens");
+
+    let token = generate_token();
+    let lifetime = state.jwt.lifetimes.refresh_for(&user.role);
+    let token_doc = RefreshToken {
+        user_id: user.id.unwrap(),
+        token_hash: hash_token(&token),
+        family_id: family_id.to_string(),
+        expires_at: Utc::now() + lifetime,
+        rotated_at: None,
+    };
+
+    refresh_col.insert_one(token_doc).await?;
+    Ok(token)
+}
+
+/// A rotated token was presented again, so either the client or an attacker
+/// holds a stolen copy. Revoke every token in the family and record it.
+async fn revoke_token_family(state: &AppState, client: &ClientInfo, reused: &RefreshToken) {
+    let refresh_col = state.db.collection::<RefreshToken>("refresh_tokens");
+    let events_col = state.db.collection::<SecurityEvent>("security_events");
+
+    let revoked = refresh_col
+        .delete_many(doc! { "family_id": &reused.family_id })
+        .await
+        .map(|r| r.deleted_count)
+        .unwrap_or(0);
+
+    let event = SecurityEvent {
+        user_id: reused.user_id,
+        event: "refresh_token_reuse".to_string(),
+        details: doc! {
+            "family_id": &reused.family_id,
+            "revoked_tokens": revoked as i64,
+        },
+        created_at: Utc::now(),
+    };
+
+    if let Err(e) = events_col.insert_one(event).await {
+        eprintln!("Failed to record security event: {:?}", e);
+    }
+
+    let owner = state.db.collection::<User>("users").find_one(doc! { "_id": reused.user_id }).await;
+    if let Ok(Some(owner)) = owner {
+        AuditEvent::new(owner.tenant_id, None, "auth.refresh_reuse", "user", owner.id)
+            .details(doc! { "family_id": &reused.family_id, "revoked_tokens": revoked as i64 })
+            .record(&state.db, client)
+            .await;
+    }
+}
+
 pub async fn forgot_password(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<ForgotPasswordRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
-    let resets_col = state.db.collection::<PasswordReset>("password_resets");
+    let email = normalize_address(&payload.email);
 
-    let user = match users_col.find_one(doc! { "email": &payload.email }).await {
+    // Checked before the lookup so the limit does not reveal whether the email exists
+    let email_allowed = state.reset_email_limiter.check(&format!("email:{}", email));
+    let ip_allowed = state.reset_ip_limiter.check(&format!("ip:{}", addr.ip()));
+    if !email_allowed || !ip_allowed {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many reset requests. Please try again later.").into_response();
+    }
+
+    let user = match users_col
+        .find_one(doc! { "tenant_id": tenant_id, "email": &email, "deactivated": { "$ne": true } })
+        .await
+    {
         Ok(Some(u)) => u,
         _ => return (StatusCode::OK, "If that email exists, a reset link has been sent.").into_response(),
     };
+    // Anyone can ask, so there is no actor
+    AuditEvent::new(tenant_id, None, "auth.password_reset_request", "user", user.id)
+        .record(&state.db, &client)
+        .await;
+
+    match send_password_reset(&state, &user).await {
+        Ok(_) => (StatusCode::OK, "If that email exists, a reset link has been sent.").into_response(),
+        Err(e) => {
+            eprintln!("Email error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error queueing email").into_response()
+        }
+    }
+}
 
-    let token = Uuid::new_v4().to_string();
-    let expires_at = Utc::now() + Duration::hours(1);
+/// Stores a one-hour reset token and queues the link to the user's address.
+/// Only the latest link stays valid.
+pub async fn send_password_reset(state: &AppState, user: &User) -> Result<(), mongodb::error::Error> {
+    let resets_col = state.db.collection::<PasswordReset>("password_resets");
+    let user_id = user.id.unwrap();
 
+    let token = generate_token();
     let reset_doc = PasswordReset {
-        user_id: user.id.unwrap(),
-        email: payload.email.clone(),
-        token: token.clone(),
-        expires_at,
+        user_id,
+        email: user.email.clone(),
+        token_hash: hash_token(&token),
+        expires_at: Utc::now() + Duration::hours(1),
     };
 
-    // Clean up old tokens for this user
-    let _ = resets_col.delete_many(doc! { "user_id": user.id.unwrap() }).await;
+    resets_col.delete_many(doc! { "user_id": user_id }).await?;
+    resets_col.insert_one(reset_doc).await?;
+    enqueue_email(
+        &state.db,
+        &user.email,
+        &user.locale,
+        "password_reset",
+        json!({ "name": &user.name, "token": token }),
+    )
+    .await?;
+    Ok(())
+}
 
-    if let Err(_) = resets_col.insert_one(reset_doc).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving reset token").into_response();
+/// Policy violations for a new password, including reuse of the current or
+/// recent passwords.
+pub fn new_password_violations(state: &AppState, user: &User, password: &str) -> Vec<PolicyViolation> {
+    let mut violations = check_policy(&state.password_policy, password, &user.email, &user.identifier);
+    let mut previous = user.password_history.clone();
+    previous.push(user.password_hash.clone());
+    let recent = &previous[previous.len().saturating_sub(state.password_policy.history_size + 1)..];
+    if state.password_policy.history_size > 0 && is_reused(password, recent) {
+        violations.push(PolicyViolation {
+            code: "password_reused",
+            message: "Password was used recently".to_string(),
+        });
     }
+    violations
+}
 
-    match send_reset_email(&payload.email, &token).await {
-        Ok(_) => (StatusCode::OK, "Reset link sent").into_response(),
-        Err(e) => {
-            eprintln!("Email error: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Error sending email").into_response()
-        }
-    }
+/// Replaces the password hash, keeping the old one in the history, and clears
+/// `must_change_password`.
+pub async fn store_password(
+    state: &AppState,
+    user: &User,
+    password_hash: String,
+) -> Result<(), mongodb::error::Error> {
+    state
+        .db
+        .collection::<User>("users")
+        .update_one(
+            doc! { "_id": user.id },
+            doc! {
+                "$set": { "password_hash": password_hash, "must_change_password": false },
+                "$push": {
+                    "password_history": {
+                        "$each": [&user.password_hash],
+                        "$slice": -(state.password_policy.history_size as i64),
+                    }
+                }
+            },
+        )
+        .await?;
+    Ok(())
 }
 
 pub async fn reset_password(
     State(state): State<Arc<AppState>>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<ResetPasswordRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
     let resets_col = state.db.collection::<PasswordReset>("password_resets");
 
-    let reset = match resets_col.find_one(doc! { "token": &payload.token }).await {
+    let token_hash = hash_token(&payload.token);
+    let reset = match resets_col.find_one(doc! { "token_hash": &token_hash }).await {
         Ok(Some(r)) => r,
         _ => return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
     };
 
+    // The TTL monitor only runs once a minute
     if reset.expires_at < Utc::now() {
-        let _ = resets_col.delete_one(doc! { "token": &payload.token }).await;
+        let _ = resets_col.delete_one(doc! { "token_hash": &token_hash }).await;
         return (StatusCode::BAD_REQUEST, "Token expired").into_response();
     }
 
-    let password_hash = match hash(payload.password, DEFAULT_COST) {
+    let user = match users_col.find_one(doc! { "_id": reset.user_id }).await {
+        Ok(Some(u)) => u,
+        _ => return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
+    };
+
+    let violations = new_password_violations(&state, &user, &payload.password);
+    if !violations.is_empty() {
+        return violations_response(violations);
+    }
+
+    let password_hash = match hash_password(&payload.password) {
         Ok(h) => h,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error hashing password").into_response(),
     };
 
-    match users_col.update_one(
-        doc! { "_id": reset.user_id },
-        doc! { "$set": { "password_hash": password_hash } }
-    ).await {
+    // Claim the token before changing anything so it can only be used once
+    match resets_col.delete_one(doc! { "token_hash": &token_hash }).await {
+        Ok(result) if result.deleted_count == 1 => {}
+        Ok(_) => return (StatusCode::BAD_REQUEST, "Invalid or expired token").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    match store_password(&state, &user, password_hash).await {
         Ok(_) => {
-            let _ = resets_col.delete_one(doc! { "token": &payload.token }).await;
+            AuditEvent::new(user.tenant_id, user.id, "auth.password_reset", "user", user.id)
+                .record(&state.db, &client)
+                .await;
+            // Sessions opened with the old password must not survive the reset
+            if let Err(e) = revoke_user_tokens(&state, reset.user_id).await {
+                eprintln!("Failed to revoke sessions after reset: {:?}", e);
+            }
+            // The link was mailed to this address, which proves the inbox
+            // (and is how imported accounts get verified)
+            if reset.email == user.email && !user.email_verified {
+                let _ = users_col
+                    .update_one(doc! { "_id": reset.user_id }, doc! { "$set": { "email_verified": true } })
+                    .await;
+            }
+            if let Err(e) = enqueue_email(
+                &state.db,
+                &user.email,
+                &user.locale,
+                "password_changed",
+                json!({ "name": &user.name }),
+            )
+            .await
+            {
+                eprintln!("Email error: {:?}", e);
+            }
             (StatusCode::OK, "Password updated successfully").into_response()
         },
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error updating password").into_response(),
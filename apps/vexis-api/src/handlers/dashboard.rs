@@ -6,6 +6,7 @@ use axum::{
 };
 use std::sync::Arc;
 use crate::AppState;
+use crate::middleware::tenant::TenantId;
 use crate::models::attendance::Attendance;
 use crate::utils::jwt::Claims;
 use serde::Serialize;
@@ -32,6 +33,7 @@ pub struct AttendanceLog {
 pub async fn get_dashboard_stats(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
 ) -> impl IntoResponse {
     let attendance_col = state.db.collection::<Attendance>("attendances");
     let user_id = match ObjectId::parse_str(&claims.sub) {
@@ -48,7 +50,7 @@ pub async fn get_dashboard_stats(
     let today_date_str = now_wib.format("%Y-%m-%d").to_string();
 
     // Get Recent Logs (fetch all attendance for this user, sorted by timestamp desc)
-    let recent_filter = doc! { "user_id": user_id };
+    let recent_filter = doc! { "tenant_id": tenant_id, "user_id": user_id, "voided": { "$ne": true } };
     let mut recent_cursor = match attendance_col
         .find(recent_filter)
         .sort(doc! { "timestamp": -1 })
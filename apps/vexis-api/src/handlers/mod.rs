@@ -4,3 +4,16 @@ pub mod user;
 pub mod attendance;
 pub mod admin_user;
 pub mod admin_attendance;
+pub mod admin_jobs;
+pub mod well_known;
+pub mod oidc;
+pub mod passwordless;
+pub mod push;
+pub mod admin_webhooks;
+pub mod admin_office;
+pub mod admin_import;
+pub mod admin_department;
+pub mod team;
+pub mod admin_tenants;
+pub mod admin_audit;
+pub mod admin_presence;
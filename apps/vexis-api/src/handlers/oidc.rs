@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Extension, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::config::db::is_duplicate_key;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::handlers::auth::complete_login;
+use crate::models::auth::OidcLoginState;
+use crate::models::user::{User, OfficeLocation};
+use crate::utils::email::normalize_address;
+use crate::utils::email_templates::DEFAULT_LOCALE;
+use crate::utils::oidc::IdTokenClaims;
+use crate::utils::token::generate_token;
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId};
+use chrono::{Utc, Duration};
+use futures::stream::TryStreamExt;
+
+#[derive(Serialize)]
+pub struct OidcAuthorizeResponse {
+    pub authorization_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+/// Starts an SSO login: stores state, nonce and PKCE verifier and returns the
+/// identity provider URL the browser should be sent to.
+pub async fn oidc_authorize(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    let Some(oidc) = &state.oidc else {
+        return (StatusCode::NOT_FOUND, "SSO is not configured").into_response();
+    };
+    let states_col = state.db.collection::<OidcLoginState>("oidc_states");
+
+    let login_state = OidcLoginState {
+        state: generate_token(),
+        tenant_id,
+        nonce: generate_token(),
+        code_verifier: generate_token(),
+        expires_at: Utc::now() + Duration::minutes(10),
+    };
+
+    let authorization_url = match oidc
+        .authorization_url(&login_state.state, &login_state.nonce, &login_state.code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("OIDC error: {}", e);
+            return (StatusCode::BAD_GATEWAY, "Identity provider unavailable").into_response();
+        }
+    };
+
+    if states_col.insert_one(login_state).await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving login state").into_response();
+    }
+
+    Json(OidcAuthorizeResponse { authorization_url }).into_response()
+}
+
+/// Completes an SSO login with the code the identity provider redirected back
+/// with, and issues the normal Vexis tokens. The tenant is the one the login
+/// started in, whatever the callback request says.
+pub async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<OidcCallbackRequest>,
+) -> impl IntoResponse {
+    let Some(oidc) = &state.oidc else {
+        return (StatusCode::NOT_FOUND, "SSO is not configured").into_response();
+    };
+    let states_col = state.db.collection::<OidcLoginState>("oidc_states");
+
+    // Single use: the state document is consumed whether or not the exchange succeeds
+    let login_state = match states_col.find_one_and_delete(doc! { "state": &payload.state }).await {
+        Ok(Some(s)) if s.expires_at > Utc::now() => s,
+        Ok(_) => return (StatusCode::BAD_REQUEST, "Invalid or expired login state").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let claims = match oidc.exchange_code(&payload.code, &login_state.code_verifier).await {
+        Ok(tokens) => oidc.validate_id_token(&tokens.id_token, &login_state.nonce).await,
+        Err(e) => Err(e),
+    };
+    let claims = match claims {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("OIDC error: {}", e);
+            return (StatusCode::UNAUTHORIZED, "SSO login failed").into_response();
+        }
+    };
+
+    let role = oidc.map_role(&claims);
+    let identifier = oidc.identifier(&claims);
+    let role_sync = oidc.config.role_sync.then_some(role.as_str());
+    let user = match provision_user(&state, login_state.tenant_id, &claims, identifier, &role, role_sync).await {
+        Ok(u) => u,
+        Err(response) => return response.into_response(),
+    };
+
+    complete_login(&state, &client, user, "oidc").await
+}
+
+/// Finds the Vexis account for an IdP identity, linking an existing account by
+/// verified email or identifier, or creating a new one with `role`. Existing
+/// accounts only take the IdP role when `role_sync` is given.
+async fn provision_user(
+    state: &AppState,
+    tenant_id: ObjectId,
+    claims: &IdTokenClaims,
+    identifier: Option<String>,
+    role: &str,
+    role_sync: Option<&str>,
+) -> Result<User, (StatusCode, &'static str)> {
+    let users_col = state.db.collection::<User>("users");
+    let db_error = (StatusCode::INTERNAL_SERVER_ERROR, "Database error");
+
+    let linked = users_col
+        .find_one(doc! { "tenant_id": tenant_id, "oidc_subject": &claims.sub })
+        .await
+        .map_err(|_| db_error)?;
+
+    let existing = match linked {
+        Some(user) => Some(user),
+        None => {
+            let mut fallbacks = vec![];
+            // Only trust the email for linking when the IdP has verified it
+            if let (Some(email), true) = (&claims.email, claims.email_verified) {
+                fallbacks.push(doc! { "email": normalize_address(email) });
+            }
+            if let Some(identifier) = &identifier {
+                fallbacks.push(doc! { "identifier": identifier });
+            }
+            if fallbacks.is_empty() {
+                None
+            } else {
+                let mut matches: Vec<User> = users_col
+                    .find(doc! { "tenant_id": tenant_id, "$or": fallbacks })
+                    .limit(2)
+                    .await
+                    .map_err(|_| db_error)?
+                    .try_collect()
+                    .await
+                    .map_err(|_| db_error)?;
+                if matches.len() > 1 {
+                    return Err((StatusCode::CONFLICT, "SSO identity matches more than one account"));
+                }
+                matches.pop()
+            }
+        }
+    };
+
+    if let Some(user) = existing {
+        if user.oidc_subject.as_deref().is_some_and(|s| s != claims.sub) {
+            return Err((StatusCode::CONFLICT, "Account is linked to another SSO identity"));
+        }
+        let mut synced = doc! { "oidc_subject": &claims.sub };
+        // IdP groups never grant or take away super admin
+        if let Some(role) = role_sync.filter(|_| user.role != "super_admin") {
+            synced.insert("role", role);
+        }
+        return users_col
+            .find_one_and_update(doc! { "_id": user.id }, doc! { "$set": synced })
+            .return_document(mongodb::options::ReturnDocument::After)
+            .await
+            .map_err(|_| db_error)?
+            .ok_or(db_error);
+    }
+
+    let Some(email) = claims.email.as_deref().map(normalize_address) else {
+        return Err((StatusCode::BAD_REQUEST, "Identity provider did not return an email"));
+    };
+    let identifier = identifier.unwrap_or_else(|| claims.sub.clone());
+
+    // An unverified email was not used for linking, but it still must not
+    // end up on a second account
+    let taken = (StatusCode::CONFLICT, "Email or identifier belongs to another account");
+    let existing = users_col
+        .find_one(doc! {
+            "tenant_id": tenant_id,
+            "$or": [{ "email": &email }, { "identifier": &identifier }],
+        })
+        .await
+        .map_err(|_| db_error)?;
+    if existing.is_some() {
+        return Err(taken);
+    }
+
+    let mut new_user = User {
+        id: None,
+        tenant_id,
+        name: claims.name.clone().unwrap_or_else(|| email.clone()),
+        email,
+        email_verified: claims.email_verified,
+        pending_email: None,
+        identifier,
+        // SSO accounts have no local password; an empty hash never verifies
+        password_hash: String::new(),
+        password_history: vec![],
+        role: role.to_string(),
+        office_location: OfficeLocation {
+            r#type: "Point".to_string(),
+            coordinates: vec![0.0, 0.0],
+        },
+        face_landmarks: vec![],
+        face_enrolled: false,
+        photo_url: None,
+        token_version: 0,
+        office_id: None,
+        department_id: None,
+        team_id: None,
+        oidc_subject: Some(claims.sub.clone()),
+        ldap_dn: None,
+        locale: DEFAULT_LOCALE.to_string(),
+        must_change_password: false,
+        deactivated: false,
+        deleted_at: None,
+        deleted_by: None,
+    };
+
+    let result = users_col.insert_one(&new_user).await.map_err(|e| {
+        if is_duplicate_key(&e) {
+            taken
+        } else {
+            db_error
+        }
+    })?;
+    new_user.id = result.inserted_id.as_object_id();
+    Ok(new_user)
+}
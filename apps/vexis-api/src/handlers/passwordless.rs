@@ -0,0 +1,286 @@
+use axum::{
+    extract::{Extension, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::handlers::auth::complete_login;
+use crate::models::auth::LoginCode;
+use crate::models::settings::PasswordlessSettings;
+use crate::models::tenant::Tenant;
+use crate::models::user::User;
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::normalize_address;
+use crate::utils::jobs::enqueue_email;
+use crate::utils::jwt::Claims;
+use crate::utils::token::{generate_code, generate_token, hash_token};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::ReturnDocument;
+use chrono::{Utc, Duration};
+
+const CODE_TTL_MINUTES: i64 = 10;
+const MAX_CODE_ATTEMPTS: i32 = 5;
+
+#[derive(Deserialize)]
+pub struct LoginCodeRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyLoginCodeRequest {
+    pub email: String,
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordlessSettingsPayload {
+    pub enabled_roles: Vec<String>,
+}
+
+pub async fn load_passwordless_settings(
+    state: &AppState,
+    tenant_id: ObjectId,
+) -> Result<PasswordlessSettings, mongodb::error::Error> {
+    let tenant = state
+        .db
+        .collection::<Tenant>("tenants")
+        .find_one(doc! { "_id": tenant_id })
+        .await?;
+    // Passwordless login is off until an admin enables it
+    Ok(tenant.map(|t| t.settings.passwordless).unwrap_or_default())
+}
+
+/// Checks `payload` and stores it as the tenant's passwordless settings.
+/// Returns the tenant as it was before, or `None` if it does not exist.
+pub async fn store_passwordless_settings(
+    state: &AppState,
+    tenant_id: ObjectId,
+    payload: &PasswordlessSettingsPayload,
+) -> Result<Option<Tenant>, (StatusCode, String)> {
+    if let Some(role) = payload.enabled_roles.iter().find(|r| !matches!(r.as_str(), "user" | "admin")) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown role {}", role)));
+    }
+
+    state
+        .db
+        .collection::<Tenant>("tenants")
+        .find_one_and_update(doc! { "_id": tenant_id }, doc! { "$set": passwordless_update(payload) })
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Error updating settings".to_string()))
+}
+
+/// Emails a one-time code and magic link. The response is the same whether or
+/// not the account exists or may use passwordless login.
+pub async fn request_login_code(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Json(payload): Json<LoginCodeRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let codes_col = state.db.collection::<LoginCode>("login_codes");
+    let generic = (StatusCode::OK, "If passwordless login is available for that account, a code has been sent.");
+    let email = normalize_address(&payload.email);
+
+    if !state.login_code_limiter.check(&format!("email:{}", email)) {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many login code requests. Please try again later.").into_response();
+    }
+
+    let user = match users_col
+        .find_one(doc! { "tenant_id": tenant_id, "email": &email, "deactivated": { "$ne": true } })
+        .await
+    {
+        Ok(Some(u)) => u,
+        Ok(None) => return generic.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    match load_passwordless_settings(&state, tenant_id).await {
+        Ok(settings) if settings.is_enabled_for(&user.role) => {}
+        Ok(_) => return generic.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    let user_id = user.id.unwrap();
+    let code = generate_code();
+    let link_token = generate_token();
+
+    // Only the latest code stays valid
+    let _ = codes_col.delete_many(doc! { "user_id": user_id }).await;
+
+    let login_code = LoginCode {
+        user_id,
+        code_hash: hash_token(&code),
+        link_token_hash: hash_token(&link_token),
+        attempts: 0,
+        expires_at: Utc::now() + Duration::minutes(CODE_TTL_MINUTES),
+    };
+    if codes_col.insert_one(login_code).await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving login code").into_response();
+    }
+
+    match enqueue_email(
+        &state.db,
+        &user.email,
+        &user.locale,
+        "login_code",
+        json!({ "name": &user.name, "code": code, "token": link_token }),
+    )
+    .await
+    {
+        Ok(_) => generic.into_response(),
+        Err(e) => {
+            eprintln!("Email error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error queueing email").into_response()
+        }
+    }
+}
+
+/// Exchanges an emailed code for a session. Each guess counts against the
+/// code, which stops working after `MAX_CODE_ATTEMPTS`.
+pub async fn verify_login_code(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<VerifyLoginCodeRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let codes_col = state.db.collection::<LoginCode>("login_codes");
+    let invalid = (StatusCode::UNAUTHORIZED, "Invalid or expired code");
+
+    let email = normalize_address(&payload.email);
+    let user = match users_col.find_one(doc! { "tenant_id": tenant_id, "email": &email }).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return invalid.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    let user_id = user.id.unwrap();
+
+    // Count the attempt atomically so parallel guesses cannot exceed the limit
+    let login_code = match codes_col
+        .find_one_and_update(
+            doc! {
+                "user_id": user_id,
+                "expires_at": { "$gt": DateTime::now() },
+                "attempts": { "$lt": MAX_CODE_ATTEMPTS },
+            },
+            doc! { "$inc": { "attempts": 1 } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(c)) => c,
+        Ok(None) => return invalid.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    if login_code.code_hash != hash_token(payload.code.trim()) {
+        AuditEvent::new(tenant_id, None, "auth.login_failed", "user", Some(user_id))
+            .details(doc! { "method": "passwordless", "reason": "wrong code", "attempts": login_code.attempts })
+            .record(&state.db, &client)
+            .await;
+        return invalid.into_response();
+    }
+
+    match codes_col
+        .delete_one(doc! { "user_id": user_id, "code_hash": &login_code.code_hash })
+        .await
+    {
+        Ok(result) if result.deleted_count == 1 => {}
+        Ok(_) => return invalid.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    finish_passwordless_login(&state, &client, user, "passwordless").await
+}
+
+/// Exchanges the magic link token from the same email for a session.
+pub async fn verify_magic_link(
+    State(state): State<Arc<AppState>>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<MagicLinkRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let codes_col = state.db.collection::<LoginCode>("login_codes");
+    let invalid = (StatusCode::UNAUTHORIZED, "Invalid or expired link");
+
+    let login_code = match codes_col
+        .find_one_and_delete(doc! { "link_token_hash": hash_token(&payload.token) })
+        .await
+    {
+        Ok(Some(c)) if c.expires_at > Utc::now() => c,
+        Ok(_) => return invalid.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let user = match users_col.find_one(doc! { "_id": login_code.user_id }).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return invalid.into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    finish_passwordless_login(&state, &client, user, "magic_link").await
+}
+
+async fn finish_passwordless_login(
+    state: &AppState,
+    client: &ClientInfo,
+    user: User,
+    method: &str,
+) -> axum::response::Response {
+    // The role may have been disabled since the code was sent
+    match load_passwordless_settings(state, user.tenant_id).await {
+        Ok(settings) if settings.is_enabled_for(&user.role) => complete_login(state, client, user, method).await,
+        Ok(_) => (StatusCode::FORBIDDEN, "Passwordless login is disabled for this account").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+pub async fn get_passwordless_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    match load_passwordless_settings(&state, tenant_id).await {
+        Ok(settings) => Json(PasswordlessSettingsPayload {
+            enabled_roles: settings.enabled_roles,
+        })
+        .into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+/// The `$set` for `payload`, also what audit entries diff against.
+pub fn passwordless_update(payload: &PasswordlessSettingsPayload) -> Document {
+    doc! { "settings.passwordless.enabled_roles": &payload.enabled_roles }
+}
+
+pub async fn update_passwordless_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<PasswordlessSettingsPayload>,
+) -> impl IntoResponse {
+    match store_passwordless_settings(&state, tenant_id, &payload).await {
+        Ok(previous) => {
+            if let Some(previous) = previous {
+                AuditEvent::new(tenant_id, ObjectId::parse_str(&claims.sub).ok(), "tenant.settings", "tenant", Some(tenant_id))
+                    .changes(&previous, &passwordless_update(&payload))
+                    .record(&state.db, &client)
+                    .await;
+            }
+            Json(payload).into_response()
+        }
+        Err(error) => error.into_response(),
+    }
+}
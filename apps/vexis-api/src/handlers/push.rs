@@ -0,0 +1,136 @@
+use axum::{
+    extract::{Extension, State},
+    Json,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::models::push::PushSubscription;
+use crate::utils::jwt::Claims;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use mongodb::bson::{doc, oid::ObjectId};
+use futures::stream::TryStreamExt;
+use chrono::{DateTime, Utc};
+
+/// Body of `PushSubscription.toJSON()` in the browser, plus an optional label.
+#[derive(Deserialize)]
+pub struct SubscribeRequest {
+    pub endpoint: String,
+    pub keys: SubscriptionKeys,
+    pub device: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeRequest {
+    pub endpoint: String,
+}
+
+#[derive(Serialize)]
+pub struct SubscriptionResponse {
+    pub id: String,
+    pub device: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn vapid_public_key(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.push {
+        Some(push) => Json(json!({ "public_key": push.application_server_key() })).into_response(),
+        None => (StatusCode::NOT_FOUND, "Push notifications are not configured").into_response(),
+    }
+}
+
+pub async fn subscribe(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<SubscribeRequest>,
+) -> impl IntoResponse {
+    if state.push.is_none() {
+        return (StatusCode::NOT_FOUND, "Push notifications are not configured").into_response();
+    }
+    let subscriptions_col = state.db.collection::<PushSubscription>("push_subscriptions");
+    let user_id = match ObjectId::parse_str(&claims.sub) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
+    };
+
+    if !payload.endpoint.starts_with("https://") {
+        return (StatusCode::BAD_REQUEST, "Push endpoint must use HTTPS").into_response();
+    }
+
+    // Re-subscribing the same browser refreshes its keys and owner
+    match subscriptions_col
+        .update_one(
+            doc! { "endpoint": &payload.endpoint },
+            doc! {
+                "$set": {
+                    "user_id": user_id,
+                    "p256dh": &payload.keys.p256dh,
+                    "auth": &payload.keys.auth,
+                    "device": &payload.device,
+                },
+                "$setOnInsert": { "created_at": mongodb::bson::DateTime::now() },
+            },
+        )
+        .upsert(true)
+        .await
+    {
+        Ok(_) => (StatusCode::CREATED, "Subscribed").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error saving subscription").into_response(),
+    }
+}
+
+pub async fn unsubscribe(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<UnsubscribeRequest>,
+) -> impl IntoResponse {
+    let subscriptions_col = state.db.collection::<PushSubscription>("push_subscriptions");
+    let user_id = match ObjectId::parse_str(&claims.sub) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
+    };
+
+    match subscriptions_col
+        .delete_one(doc! { "endpoint": &payload.endpoint, "user_id": user_id })
+        .await
+    {
+        Ok(result) if result.deleted_count > 0 => (StatusCode::OK, "Unsubscribed").into_response(),
+        Ok(_) => (StatusCode::NOT_FOUND, "Subscription not found").into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
+pub async fn list_subscriptions(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let subscriptions_col = state.db.collection::<PushSubscription>("push_subscriptions");
+    let user_id = match ObjectId::parse_str(&claims.sub) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
+    };
+
+    let mut cursor = match subscriptions_col.find(doc! { "user_id": user_id }).await {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut subscriptions = Vec::new();
+    while let Ok(Some(sub)) = cursor.try_next().await {
+        subscriptions.push(SubscriptionResponse {
+            id: sub.id.unwrap().to_hex(),
+            device: sub.device,
+            created_at: sub.created_at,
+        });
+    }
+
+    Json(subscriptions).into_response()
+}
@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Extension, Query, State},
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
+use crate::handlers::admin_attendance::{attendance_csv, attendance_page, export_event, AttendanceQuery};
+use crate::handlers::user::UserProfileResponse;
+use crate::models::user::User;
+use crate::utils::jwt::Claims;
+use crate::utils::org::reports_filter;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use futures::stream::TryStreamExt;
+
+/// Users filter for the caller's team, or 403 if they supervise nothing.
+async fn caller_reports(state: &AppState, tenant_id: ObjectId, claims: &Claims) -> Result<Document, Response> {
+    let Ok(user_id) = ObjectId::parse_str(&claims.sub) else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid user ID").into_response());
+    };
+    match reports_filter(&state.db, tenant_id, user_id).await {
+        Ok(Some(filter)) => Ok(filter),
+        Ok(None) => Err((StatusCode::FORBIDDEN, "Only supervisors can view team data").into_response()),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()),
+    }
+}
+
+/// Members of the teams and departments the caller supervises.
+pub async fn list_members(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+) -> impl IntoResponse {
+    let filter = match caller_reports(&state, tenant_id, &claims).await {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+
+    let mut cursor = match state.db.collection::<User>("users").find(filter).sort(doc! { "name": 1 }).await {
+        Ok(c) => c,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+
+    let mut members = Vec::new();
+    while let Ok(Some(user)) = cursor.try_next().await {
+        members.push(UserProfileResponse::from(user));
+    }
+
+    Json(members).into_response()
+}
+
+/// Same as the admin attendance list, limited to the caller's team.
+pub async fn list_team_attendance(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Query(query): Query<AttendanceQuery>,
+) -> impl IntoResponse {
+    match caller_reports(&state, tenant_id, &claims).await {
+        Ok(filter) => attendance_page(&state, tenant_id, &query, Some(filter)).await,
+        Err(response) => response,
+    }
+}
+
+pub async fn export_team_attendance_csv(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Query(query): Query<AttendanceQuery>,
+) -> impl IntoResponse {
+    let filter = match caller_reports(&state, tenant_id, &claims).await {
+        Ok(filter) => filter,
+        Err(response) => return response,
+    };
+    let response = attendance_csv(&state, tenant_id, &query, Some(filter)).await;
+    if response.status().is_success() {
+        export_event(tenant_id, &claims, "team", &query).record(&state.db, &client).await;
+    }
+    response
+}
@@ -6,32 +6,90 @@ use axum::{
 };
 use std::sync::Arc;
 use crate::AppState;
+use crate::middleware::client::ClientInfo;
+use crate::middleware::tenant::TenantId;
 use crate::models::user::{User, OfficeLocation};
+use crate::handlers::auth::{issue_session, new_password_violations, start_email_verification, store_password};
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::normalize_address;
+use crate::utils::email_templates::LOCALES;
+use crate::utils::jobs::enqueue_email;
 use crate::utils::jwt::Claims;
+use crate::utils::org::supervisor_of;
+use crate::utils::password::{hash_password, verify_password, violations_response, PasswordCheck};
+use crate::utils::revocation::revoke_user_tokens;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use mongodb::bson::{doc, oid::ObjectId};
-use mongodb::options::FindOneAndUpdateOptions;
 use tokio::fs;
 use std::path::Path;
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Serialize)]
 pub struct UserProfileResponse {
     pub id: String,
     pub name: String,
     pub email: String,
+    pub email_verified: bool,
+    pub pending_email: Option<String>,
     pub identifier: String,
     pub role: String,
+    pub locale: String,
     pub photo_url: Option<String>,
     pub has_face_landmarks: bool,
     pub office_location: crate::models::user::OfficeLocation,
+    pub office_id: Option<String>,
+    pub department_id: Option<String>,
+    pub team_id: Option<String>,
+    /// Only resolved for `GET /api/users/me`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supervisor_id: Option<String>,
+    pub must_change_password: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl From<User> for UserProfileResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.expect("User should have an ID").to_hex(),
+            name: user.name,
+            email: user.email,
+            email_verified: user.email_verified,
+            pending_email: user.pending_email,
+            identifier: user.identifier,
+            role: user.role,
+            locale: user.locale,
+            photo_url: user.photo_url,
+            has_face_landmarks: !user.face_landmarks.is_empty(),
+            office_location: user.office_location,
+            office_id: user.office_id.map(|id| id.to_hex()),
+            department_id: user.department_id.map(|id| id.to_hex()),
+            team_id: user.team_id.map(|id| id.to_hex()),
+            supervisor_id: None,
+            must_change_password: user.must_change_password,
+            deleted_at: user.deleted_at,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct UpdateProfileRequest {
     pub name: Option<String>,
     pub identifier: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
 }
 
 #[derive(Deserialize)]
@@ -60,16 +118,13 @@ pub async fn get_me(
         _ => return (StatusCode::NOT_FOUND, "User not found").into_response(),
     };
 
-    Json(UserProfileResponse {
-        id: user.id.unwrap().to_hex(),
-        name: user.name,
-        email: user.email,
-        identifier: user.identifier,
-        role: user.role,
-        photo_url: user.photo_url,
-        has_face_landmarks: !user.face_landmarks.is_empty(),
-        office_location: user.office_location,
-    }).into_response()
+    let supervisor_id = match supervisor_of(&state.db, &user).await {
+        Ok(id) => id,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    let mut profile = UserProfileResponse::from(user);
+    profile.supervisor_id = supervisor_id.map(|id| id.to_hex());
+    Json(profile).into_response()
 }
 
 pub async fn update_me(
@@ -90,6 +145,12 @@ pub async fn update_me(
     if let Some(identifier) = payload.identifier {
         update_doc.insert("identifier", identifier);
     }
+    if let Some(locale) = payload.locale {
+        if !LOCALES.contains(&locale.as_str()) {
+            return (StatusCode::BAD_REQUEST, "Unsupported locale").into_response();
+        }
+        update_doc.insert("locale", locale);
+    }
 
     if update_doc.is_empty() {
         return (StatusCode::BAD_REQUEST, "No changes provided").into_response();
@@ -103,20 +164,111 @@ pub async fn update_me(
         .return_document(mongodb::options::ReturnDocument::After)
         .await
     {
-        Ok(Some(user)) => Json(UserProfileResponse {
-            id: user.id.unwrap().to_hex(),
-            name: user.name,
-            email: user.email,
-            identifier: user.identifier,
-            role: user.role,
-            photo_url: user.photo_url,
-            has_face_landmarks: !user.face_landmarks.is_empty(),
-            office_location: user.office_location,
-        }).into_response(),
+        Ok(Some(user)) => Json(UserProfileResponse::from(user)).into_response(),
         _ => (StatusCode::INTERNAL_SERVER_ERROR, "Error updating profile").into_response(),
     }
 }
 
+/// Requests an email change. The new address only replaces the current one
+/// after the link sent to it is confirmed.
+pub async fn change_email(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<ChangeEmailRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let user_id = match ObjectId::parse_str(&claims.sub) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
+    };
+    let email = normalize_address(&payload.email);
+
+    match users_col.find_one(doc! { "tenant_id": tenant_id, "email": &email }).await {
+        Ok(None) => {}
+        Ok(Some(_)) => return (StatusCode::CONFLICT, "Email is already in use").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+
+    let user = match users_col
+        .find_one_and_update(
+            doc! { "_id": user_id },
+            doc! { "$set": { "pending_email": &email } },
+        )
+        .await
+    {
+        Ok(Some(u)) => u,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error updating email").into_response(),
+    };
+    AuditEvent::new(tenant_id, Some(user_id), "user.email_change_request", "user", Some(user_id))
+        .changes(&user, &doc! { "pending_email": &email })
+        .record(&state.db, &client)
+        .await;
+
+    match start_email_verification(&state, &user, &email, "change").await {
+        Ok(_) => (StatusCode::ACCEPTED, "Verification link sent to the new address").into_response(),
+        Err(e) => {
+            eprintln!("Email verification error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error queueing email").into_response()
+        }
+    }
+}
+
+/// Changes the caller's password, e.g. to replace an admin-issued temporary
+/// one. Every other session is ended; the response carries a fresh one.
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<Claims>,
+    Extension(client): Extension<ClientInfo>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> impl IntoResponse {
+    let users_col = state.db.collection::<User>("users");
+    let user_id = match ObjectId::parse_str(&claims.sub) {
+        Ok(oid) => oid,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid user ID").into_response(),
+    };
+
+    let user = match users_col.find_one(doc! { "_id": user_id }).await {
+        Ok(Some(u)) => u,
+        _ => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+    };
+
+    if verify_password(&payload.current_password, &user.password_hash) == PasswordCheck::Invalid {
+        return (StatusCode::UNAUTHORIZED, "Current password is incorrect").into_response();
+    }
+
+    let violations = new_password_violations(&state, &user, &payload.new_password);
+    if !violations.is_empty() {
+        return violations_response(violations);
+    }
+
+    let password_hash = match hash_password(&payload.new_password) {
+        Ok(h) => h,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Error hashing password").into_response(),
+    };
+
+    if store_password(&state, &user, password_hash).await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error updating password").into_response();
+    }
+    AuditEvent::new(user.tenant_id, Some(user_id), "auth.password_change", "user", Some(user_id))
+        .record(&state.db, &client)
+        .await;
+    if let Err(e) = revoke_user_tokens(&state, user_id).await {
+        eprintln!("Failed to revoke sessions after password change: {:?}", e);
+    }
+    if let Err(e) = enqueue_email(&state.db, &user.email, &user.locale, "password_changed", json!({ "name": &user.name })).await {
+        eprintln!("Email error: {:?}", e);
+    }
+
+    // Reload so the new session carries the bumped token version
+    match users_col.find_one(doc! { "_id": user_id }).await {
+        Ok(Some(user)) => issue_session(&state, user).await,
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    }
+}
+
 pub async fn upload_photo(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
@@ -140,16 +292,14 @@ pub async fn upload_photo(
 
             // Create uploads directory if it doesn't exist
             let upload_dir = "uploads";
-            if !Path::new(upload_dir).exists() {
-                if let Err(_) = fs::create_dir(upload_dir).await {
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Error creating upload directory").into_response();
-                }
+            if !Path::new(upload_dir).exists() && fs::create_dir(upload_dir).await.is_err() {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error creating upload directory").into_response();
             }
 
             let file_name = format!("{}_{}.jpg", user_id.to_hex(), Uuid::new_v4());
             let file_path = format!("{}/{}", upload_dir, file_name);
 
-            if let Err(_) = fs::write(&file_path, data).await {
+            if fs::write(&file_path, data).await.is_err() {
                 return (StatusCode::INTERNAL_SERVER_ERROR, "Error saving image").into_response();
             }
 
@@ -206,6 +356,8 @@ pub async fn update_location(
 pub async fn register_face(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
+    Extension(TenantId(tenant_id)): Extension<TenantId>,
+    Extension(client): Extension<ClientInfo>,
     Json(payload): Json<RegisterFaceRequest>,
 ) -> impl IntoResponse {
     let users_col = state.db.collection::<User>("users");
@@ -220,13 +372,22 @@ pub async fn register_face(
     }
 
     match users_col
-        .update_one(
+        .find_one_and_update(
             doc! { "_id": user_id },
-            doc! { "$set": { "face_landmarks": payload.landmarks } },
+            doc! { "$set": { "face_landmarks": payload.landmarks, "face_enrolled": true } },
         )
         .await
     {
-        Ok(_) => Json(json!({ "status": "success" })).into_response(),
+        Ok(Some(previous)) => {
+            // The landmarks themselves are biometric data and stay out of the log
+            AuditEvent::new(tenant_id, Some(user_id), "user.face_enroll", "user", Some(user_id))
+                .changes(&previous, &doc! { "face_enrolled": true })
+                .details(doc! { "replaced": !previous.face_landmarks.is_empty() })
+                .record(&state.db, &client)
+                .await;
+            Json(json!({ "status": "success" })).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "User not found").into_response(),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error registering face").into_response(),
     }
 }
@@ -0,0 +1,17 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+use crate::AppState;
+
+/// Public verification keys, so other services can validate Vexis tokens
+/// without sharing a secret.
+pub async fn jwks(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(state.jwt.jwks().clone()),
+    )
+}
@@ -5,41 +5,163 @@ mod routes;
 mod utils;
 mod middleware;
 
-use axum::{routing::get, Router};
+use axum::{middleware::from_fn, routing::get, Router};
 use tower_http::services::ServeDir;
 use std::net::SocketAddr;
 use dotenvy::dotenv;
 use mongodb::Database;
 use std::sync::Arc;
+use std::time::Duration;
+use config::email::EmailConfig;
+use config::jwt::JwtConfig;
+use config::ldap::LdapConfig;
+use config::oidc::OidcConfig;
+use config::password::PasswordPolicy;
+use config::push::{PushConfig, ReminderConfig};
+use config::retention::RetentionConfig;
+use utils::auth_provider::{AuthProvider, PasswordProvider};
+use utils::email::{transport_from_config, Mailer};
+use utils::email_templates::EmailTemplates;
+use utils::{jobs, reminders, retention};
+use utils::ldap::LdapProvider;
+use utils::oidc::OidcClient;
+use utils::push::{PushTransport, WebPushTransport};
+use utils::rate_limit::RateLimiter;
+use utils::presence::PresenceHub;
+use utils::revocation::TokenStateCache;
 
 pub struct AppState {
     pub db: Database,
+    pub jwt: JwtConfig,
+    /// `None` when SSO is not configured.
+    pub oidc: Option<OidcClient>,
+    pub password_policy: PasswordPolicy,
+    /// Tried in order by `login`.
+    pub auth_providers: Vec<Box<dyn AuthProvider>>,
+    pub token_cache: TokenStateCache,
+    /// Presence shared by the open presence streams of each tenant.
+    pub presence: PresenceHub,
+    /// Shared client for outgoing requests to the IdP and push services.
+    pub http: reqwest::Client,
+    /// Client for webhook requests, which go to tenant-supplied URLs; see
+    /// `webhook::client`.
+    pub webhook_http: reqwest::Client,
+    pub email: Mailer,
+    /// `None` when VAPID keys are not configured.
+    pub push: Option<Box<dyn PushTransport>>,
+    /// Password reset requests, keyed by email and by client IP.
+    pub reset_email_limiter: RateLimiter,
+    pub reset_ip_limiter: RateLimiter,
+    /// Passwordless login code requests, keyed by email.
+    pub login_code_limiter: RateLimiter,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
     
+    // Refuse to start without signing keys rather than fall back to a default
+    let jwt = JwtConfig::from_env()?;
+    let http = reqwest::Client::new();
+    let oidc = OidcConfig::from_env()?.map(|config| OidcClient::new(config, http.clone()));
+    let push = match PushConfig::from_env()? {
+        Some(config) => Some(Box::new(WebPushTransport::new(config, http.clone())?) as Box<dyn PushTransport>),
+        None => None,
+    };
+    let reminders = ReminderConfig::from_env()?;
+    let retention = RetentionConfig::from_env()?;
+    let auth_providers = auth_providers_from_env()?;
+    let email = Mailer::new(transport_from_config(EmailConfig::from_env()?)?, EmailTemplates::from_env());
     let db = config::db::init_db().await?;
-    let state = Arc::new(AppState { db });
-
-    let app = Router::new()
-        .nest("/api/auth", routes::auth::auth_routes())
-        .nest("/api/users", routes::user::user_routes())
-        .nest("/api/users", routes::admin_user::admin_user_routes())
-        .nest("/api/admin/attendance", routes::admin_attendance::admin_attendance_routes())
-        .nest("/api/dashboard", routes::dashboard::routes())
-        .nest("/api/attendance", routes::attendance::routes())
-        .nest_service("/api/uploads", ServeDir::new("uploads"))
-        .route("/", get(|| async { "Hello, Vexis API with MongoDB!" }))
-        .with_state(state);
+    let token_cache_ttl = std::env::var("TOKEN_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+    let state = Arc::new(AppState {
+        db,
+        jwt,
+        oidc,
+        auth_providers,
+        password_policy: PasswordPolicy::from_env(),
+        token_cache: TokenStateCache::new(Duration::from_secs(token_cache_ttl)),
+        presence: PresenceHub::new(),
+        http,
+        webhook_http: utils::webhook::client(),
+        email,
+        push,
+        reset_email_limiter: RateLimiter::new(env_u32("RESET_RATE_LIMIT_PER_EMAIL", 3), Duration::from_secs(3600)),
+        reset_ip_limiter: RateLimiter::new(env_u32("RESET_RATE_LIMIT_PER_IP", 10), Duration::from_secs(3600)),
+        login_code_limiter: RateLimiter::new(env_u32("LOGIN_CODE_RATE_LIMIT_PER_EMAIL", 5), Duration::from_secs(900)),
+    });
+
+    jobs::spawn_workers(state.clone(), env_u32("JOB_WORKERS", 2) as usize);
+    retention::spawn_scheduler(state.clone(), retention);
+    if state.push.is_some() {
+        reminders::spawn_scheduler(state.clone(), reminders);
+    }
+
+    let app = app(state);
 
     let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
     let addr: SocketAddr = format!("127.0.0.1:{}", port).parse()?;
     
     println!("listening on http://{}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Client addresses are needed for per-IP rate limits
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     
     Ok(())
 }
+
+/// All routes, with `state` attached.
+pub fn app(state: Arc<AppState>) -> Router {
+    Router::new()
+        .nest("/api/auth", routes::auth::auth_routes(state.clone()))
+        .nest("/api/users", routes::user::user_routes(state.clone()))
+        .nest("/api/users", routes::admin_user::admin_user_routes(state.clone()))
+        .nest("/api/admin/tenants", routes::admin_tenants::admin_tenants_routes(state.clone()))
+        .nest("/api/admin/offices", routes::admin_office::admin_office_routes(state.clone()))
+        .nest("/api/admin/departments", routes::admin_department::admin_department_routes(state.clone()))
+        .nest("/api/admin/teams", routes::admin_department::admin_team_routes(state.clone()))
+        .nest("/api/admin/jobs", routes::admin_jobs::admin_jobs_routes(state.clone()))
+        .nest("/api/admin/webhooks", routes::admin_webhooks::admin_webhooks_routes(state.clone()))
+        .nest("/api/admin/settings", routes::admin_settings::admin_settings_routes(state.clone()))
+        .nest("/api/admin/attendance", routes::admin_attendance::admin_attendance_routes(state.clone()))
+        .nest("/api/admin/audit", routes::admin_audit::admin_audit_routes(state.clone()))
+        .nest("/api/admin/presence", routes::admin_presence::admin_presence_routes(state.clone()))
+        .nest("/api/dashboard", routes::dashboard::routes(state.clone()))
+        .nest("/api/team", routes::team::routes(state.clone()))
+        .nest("/api/push", routes::push::routes(state.clone()))
+        .nest("/api/attendance", routes::attendance::routes(state.clone()))
+        .nest("/.well-known", routes::well_known::routes())
+        .nest_service("/api/uploads", ServeDir::new("uploads"))
+        .route("/", get(|| async { "Hello, Vexis API with MongoDB!" }))
+        .layer(from_fn(middleware::client::client_info))
+        .with_state(state)
+}
+
+fn env_u32(key: &str, default: u32) -> u32 {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Builds the login providers listed in `AUTH_PROVIDERS` (default `password`),
+/// e.g. `ldap,password` to try the directory first.
+fn auth_providers_from_env() -> Result<Vec<Box<dyn AuthProvider>>, String> {
+    let names = std::env::var("AUTH_PROVIDERS").unwrap_or_else(|_| "password".to_string());
+
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| -> Result<Box<dyn AuthProvider>, String> {
+            match name {
+                "password" => Ok(Box::new(PasswordProvider)),
+                "ldap" => Ok(Box::new(LdapProvider::new(LdapConfig::from_env()?))),
+                other => Err(format!("unknown auth provider {}", other)),
+            }
+        })
+        .collect()
+}
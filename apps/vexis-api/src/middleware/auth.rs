@@ -1,14 +1,19 @@
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::{header, StatusCode},
     middleware::Next,
     response::IntoResponse,
     Json,
 };
+use mongodb::bson::oid::ObjectId;
 use serde_json::json;
-use crate::utils::jwt;
+use std::sync::Arc;
+use crate::middleware::tenant::TenantId;
+use crate::utils::jwt::{self, TokenError};
+use crate::AppState;
 
 pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
     mut req: Request,
     next: Next,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
@@ -29,14 +34,53 @@ pub async fn require_auth(
         }
     };
 
-    match jwt::decode_jwt(token) {
-        Ok(claims) => {
+    let claims = match jwt::decode_jwt(&state.jwt, token) {
+        Ok(claims) => claims,
+        Err(TokenError::Expired) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Token expired", "code": "token_expired" })),
+            ))
+        }
+        Err(TokenError::Invalid) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Invalid token", "code": "token_invalid" })),
+            ))
+        }
+    };
+
+    // The signature only proves the token was issued; make sure it was not
+    // revoked since (user deleted, role changed, password reset, tenant
+    // deactivated).
+    let user_id = ObjectId::parse_str(&claims.sub).map_err(|_| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Invalid token", "code": "token_invalid" })),
+        )
+    })?;
+
+    let current = state
+        .token_cache
+        .get(&state.db, user_id)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Database error" })),
+            )
+        })?;
+
+    match current {
+        Some(current) if current.matches(&claims) => {
+            // The token decides the tenant; an `X-Tenant` header is ignored
+            req.extensions_mut().insert(TenantId(current.tenant_id));
             req.extensions_mut().insert(claims);
             Ok(next.run(req).await)
         }
-        Err(_) => Err((
+        _ => Err((
             StatusCode::UNAUTHORIZED,
-            Json(json!({ "error": "Invalid or expired token" })),
+            Json(json!({ "error": "Token has been revoked", "code": "token_revoked" })),
         )),
     }
 }
@@ -0,0 +1,33 @@
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::header::USER_AGENT,
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+
+/// Where a request came from, for the audit log. Inserted on every request
+/// by `client_info`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Records the peer address and user agent. Like the rate limits, this uses
+/// the socket address rather than `X-Forwarded-For`, which clients can forge.
+pub async fn client_info(mut req: Request, next: Next) -> Response {
+    let client = ClientInfo {
+        ip: req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string()),
+        user_agent: req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(|ua| ua.chars().take(512).collect()),
+    };
+    req.extensions_mut().insert(client);
+    next.run(req).await
+}
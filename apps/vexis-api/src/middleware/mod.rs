@@ -1,2 +1,4 @@
 pub mod auth;
+pub mod client;
 pub mod rbac;
+pub mod tenant;
@@ -17,7 +17,8 @@ pub async fn require_admin(
         Json(json!({ "error": "Unauthorized" })),
     ))?;
 
-    if claims.role != "admin" {
+    // Super admins also administer the tenant they belong to
+    if !matches!(claims.role.as_str(), "admin" | "super_admin") {
         return Err((
             StatusCode::FORBIDDEN,
             Json(json!({ "error": "Admin access required" })),
@@ -26,3 +27,23 @@ pub async fn require_admin(
 
     Ok(next.run(req).await)
 }
+
+/// For deployment-wide operations: managing tenants and the job queue.
+pub async fn require_super_admin(
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let claims = req.extensions().get::<Claims>().ok_or((
+        StatusCode::UNAUTHORIZED,
+        Json(json!({ "error": "Unauthorized" })),
+    ))?;
+
+    if claims.role != "super_admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Super admin access required" })),
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,413 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::IntoResponse,
+    Json,
+};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde_json::json;
+use std::sync::Arc;
+use crate::models::tenant::{Tenant, DEFAULT_TENANT_SLUG};
+use crate::AppState;
+
+/// Names the tenant on requests made before login.
+pub const TENANT_HEADER: &str = "x-tenant";
+
+/// The tenant a request acts in. Inserted by `require_auth` from the token,
+/// or by `resolve_tenant` on public routes. Handlers add it to every query on
+/// tenant-owned collections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TenantId(pub ObjectId);
+
+/// Resolves the `X-Tenant` slug (or the default tenant when it is missing)
+/// for the login, registration and recovery routes.
+pub async fn resolve_tenant(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let slug = req
+        .headers()
+        .get(TENANT_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_TENANT_SLUG.to_string());
+
+    let tenant = state
+        .db
+        .collection::<Tenant>("tenants")
+        .find_one(doc! { "slug": &slug, "active": true })
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Database error" }))))?;
+
+    match tenant.and_then(|t| t.id) {
+        Some(tenant_id) => {
+            req.extensions_mut().insert(TenantId(tenant_id));
+            Ok(next.run(req).await)
+        }
+        None => Err((StatusCode::NOT_FOUND, Json(json!({ "error": "Unknown tenant" })))),
+    }
+}
+
+/// Slugs go in a header and possibly a subdomain later: lowercase letters,
+/// digits and inner hyphens.
+pub fn is_valid_slug(slug: &str) -> bool {
+    (2..=40).contains(&slug.len())
+        && slug.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::email::{EmailConfig, TransportConfig};
+    use crate::config::jwt::test_keys::{NEW_PRIVATE, NEW_PUBLIC};
+    use crate::config::jwt::JwtConfig;
+    use crate::config::password::PasswordPolicy;
+    use crate::utils::auth_provider::PasswordProvider;
+    use crate::utils::email::{transport_from_config, Mailer};
+    use crate::utils::email_templates::EmailTemplates;
+    use crate::utils::password::hash_password;
+    use crate::utils::rate_limit::RateLimiter;
+    use crate::utils::revocation::TokenStateCache;
+    use crate::utils::token::hash_token;
+    use jsonwebtoken::Algorithm;
+    use mongodb::bson::{DateTime, Document};
+    use mongodb::Database;
+    use reqwest::StatusCode as Status;
+    use serde_json::Value;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    const PASSWORD: &str = "rahasia123";
+
+    #[test]
+    fn test_is_valid_slug() {
+        assert!(is_valid_slug("acme"));
+        assert!(is_valid_slug("pt-maju-jaya-2"));
+        assert!(!is_valid_slug("a"));
+        assert!(!is_valid_slug("Acme"));
+        assert!(!is_valid_slug("-acme"));
+        assert!(!is_valid_slug("acme-"));
+        assert!(!is_valid_slug("acme corp"));
+        assert!(!is_valid_slug(&"a".repeat(41)));
+    }
+
+    async fn test_state(db: Database) -> Arc<AppState> {
+        let jwt = JwtConfig::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            NEW_PRIVATE.as_bytes(),
+            &[("new".to_string(), NEW_PUBLIC.as_bytes().to_vec())],
+        )
+        .unwrap();
+        let transport = transport_from_config(EmailConfig {
+            from: "Vexis <noreply@example.com>".to_string(),
+            transport: TransportConfig::File {
+                dir: std::env::temp_dir().join(format!("vexis-mail-{}", ObjectId::new())),
+            },
+        })
+        .unwrap();
+
+        Arc::new(AppState {
+            db,
+            jwt,
+            oidc: None,
+            password_policy: PasswordPolicy::default(),
+            auth_providers: vec![Box::new(PasswordProvider)],
+            token_cache: TokenStateCache::new(Duration::from_secs(30)),
+            presence: crate::utils::presence::PresenceHub::new(),
+            http: reqwest::Client::new(),
+            webhook_http: crate::utils::webhook::client(),
+            email: Mailer::new(transport, EmailTemplates::new("http://localhost:5173")),
+            push: None,
+            reset_email_limiter: RateLimiter::new(100, Duration::from_secs(3600)),
+            reset_ip_limiter: RateLimiter::new(100, Duration::from_secs(3600)),
+            login_code_limiter: RateLimiter::new(100, Duration::from_secs(900)),
+        })
+    }
+
+    async fn insert_user(db: &Database, tenant_id: ObjectId, email: &str, role: &str) -> ObjectId {
+        db.collection::<Document>("users")
+            .insert_one(doc! {
+                "tenant_id": tenant_id,
+                "name": email,
+                "email": email,
+                "identifier": email,
+                "password_hash": hash_password(PASSWORD).unwrap(),
+                "role": role,
+                "office_location": { "type": "Point", "coordinates": [0.0, 0.0] },
+                "face_landmarks": [],
+            })
+            .await
+            .unwrap()
+            .inserted_id
+            .as_object_id()
+            .unwrap()
+    }
+
+    async fn insert_attendance(db: &Database, tenant_id: ObjectId, user_id: ObjectId) {
+        db.collection::<Document>("attendances")
+            .insert_one(doc! {
+                "tenant_id": tenant_id,
+                "user_id": user_id,
+                "timestamp": DateTime::now(),
+                "type": "In",
+                "location": { "type": "Point", "coordinates": [106.8, -6.2] },
+                "face_verified": true,
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn insert_office(db: &Database, tenant_id: ObjectId, name: &str) {
+        db.collection::<Document>("offices")
+            .insert_one(doc! {
+                "tenant_id": tenant_id,
+                "name": name,
+                "location": { "type": "Point", "coordinates": [106.8, -6.2] },
+                "radius_m": 100.0,
+                "created_at": DateTime::now(),
+            })
+            .await
+            .unwrap();
+    }
+
+    struct Client {
+        http: reqwest::Client,
+        base: String,
+    }
+
+    impl Client {
+        async fn login(&self, tenant: Option<&str>, email: &str) -> Result<String, Status> {
+            let mut request = self
+                .http
+                .post(format!("{}/api/auth/login", self.base))
+                .json(&serde_json::json!({ "email_or_id": email, "password": PASSWORD }));
+            if let Some(tenant) = tenant {
+                request = request.header(TENANT_HEADER, tenant);
+            }
+            let response = request.send().await.unwrap();
+            if response.status() != Status::OK {
+                return Err(response.status());
+            }
+            let body: Value = response.json().await.unwrap();
+            Ok(body["access_token"].as_str().unwrap().to_string())
+        }
+
+        fn request(&self, method: reqwest::Method, token: &str, path: &str) -> reqwest::RequestBuilder {
+            self.http.request(method, format!("{}{}", self.base, path)).bearer_auth(token)
+        }
+
+        async fn get(&self, token: &str, path: &str) -> (Status, Value) {
+            let response = self.request(reqwest::Method::GET, token, path).send().await.unwrap();
+            let status = response.status();
+            (status, response.json().await.unwrap_or(Value::Null))
+        }
+    }
+
+    /// Prepares a throwaway database and serves the app on it.
+    async fn start_server() -> (Database, Client) {
+        let url = std::env::var("MONGODB_TEST_URL").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+        let db = mongodb::Client::with_uri_str(url)
+            .await
+            .unwrap()
+            .database(&format!("vexis_test_{}", ObjectId::new()));
+        crate::config::db::prepare(&db).await.unwrap();
+        let state = test_state(db.clone()).await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = crate::app(state);
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        });
+        (db, Client { http: reqwest::Client::new(), base })
+    }
+
+    /// Needs a local MongoDB, see "Multi-Tenant" in the README. Runs against a
+    /// throwaway database that is dropped at the end.
+    #[tokio::test]
+    #[ignore]
+    async fn test_tenant_isolation() {
+        let (db, client) = start_server().await;
+
+        // The default tenant, with a super admin and an ordinary admin
+        let default_id = db
+            .collection::<Tenant>("tenants")
+            .find_one(doc! { "slug": DEFAULT_TENANT_SLUG })
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+            .unwrap();
+        insert_user(&db, default_id, "root@example.com", "super_admin").await;
+        let admin_a = insert_user(&db, default_id, "admin@a.example.com", "admin").await;
+        insert_attendance(&db, default_id, admin_a).await;
+        insert_office(&db, default_id, "Kantor A").await;
+
+        // A second tenant created through the API
+        let root = client.login(None, "root@example.com").await.unwrap();
+        let response = client
+            .request(reqwest::Method::POST, &root, "/api/admin/tenants")
+            .json(&serde_json::json!({ "name": "Acme", "slug": "acme" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), Status::CREATED);
+        let body: Value = response.json().await.unwrap();
+        let acme_id = ObjectId::parse_str(body["tenant"]["id"].as_str().unwrap()).unwrap();
+        insert_user(&db, acme_id, "admin@b.example.com", "admin").await;
+        let user_b = insert_user(&db, acme_id, "user@b.example.com", "user").await;
+        insert_attendance(&db, acme_id, user_b).await;
+        insert_office(&db, acme_id, "Kantor B").await;
+
+        let token_a = client.login(None, "admin@a.example.com").await.unwrap();
+        let token_b = client.login(Some("acme"), "admin@b.example.com").await.unwrap();
+
+        // Logins only find accounts of the named tenant
+        assert_eq!(client.login(None, "admin@b.example.com").await, Err(Status::UNAUTHORIZED));
+        assert_eq!(client.login(Some("acme"), "admin@a.example.com").await, Err(Status::UNAUTHORIZED));
+        assert_eq!(client.login(Some("nope"), "admin@a.example.com").await, Err(Status::NOT_FOUND));
+
+        // Users
+        let (status, body) = client.get(&token_a, "/api/users/?status=all").await;
+        assert_eq!(status, Status::OK);
+        let emails: Vec<&str> = body["users"].as_array().unwrap().iter().map(|u| u["email"].as_str().unwrap()).collect();
+        assert!(emails.contains(&"admin@a.example.com"));
+        assert!(!emails.iter().any(|e| e.ends_with("@b.example.com")));
+
+        let path = format!("/api/users/{}", user_b.to_hex());
+        assert_eq!(client.get(&token_a, &path).await.0, Status::NOT_FOUND);
+        let patched = client
+            .request(reqwest::Method::PATCH, &token_a, &path)
+            .json(&serde_json::json!({ "name": "Taken over" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(patched.status(), Status::NOT_FOUND);
+        let deleted = client.request(reqwest::Method::DELETE, &token_a, &path).send().await.unwrap();
+        assert_eq!(deleted.status(), Status::NOT_FOUND);
+        assert_eq!(client.get(&token_b, &path).await.0, Status::OK);
+
+        // Attendance, including the user lookup
+        let (status, body) = client.get(&token_a, "/api/admin/attendance").await;
+        assert_eq!(status, Status::OK);
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["data"][0]["user_id"]["$oid"], admin_a.to_hex());
+        let (_, body) = client.get(&token_a, &format!("/api/admin/attendance?user_id={}", user_b.to_hex())).await;
+        assert_eq!(body["total"], 0);
+
+        // Offices
+        let (_, body) = client.get(&token_a, "/api/admin/offices").await;
+        let names: Vec<&str> = body.as_array().unwrap().iter().map(|o| o["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["Kantor A"]);
+
+        // The token decides the tenant, not the header
+        let response = client
+            .request(reqwest::Method::GET, &token_b, "/api/admin/offices")
+            .header(TENANT_HEADER, DEFAULT_TENANT_SLUG)
+            .send()
+            .await
+            .unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body[0]["name"], "Kantor B");
+
+        // Tenants are for super admins only
+        assert_eq!(client.get(&token_a, "/api/admin/tenants").await.0, Status::FORBIDDEN);
+        let (status, body) = client.get(&root, "/api/admin/tenants").await;
+        assert_eq!(status, Status::OK);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        // Tenant admins cannot touch a super admin
+        let (_, body) = client.get(&token_a, "/api/users/?role=super_admin").await;
+        let root_id = body["users"][0]["id"].as_str().unwrap().to_string();
+        let deleted = client
+            .request(reqwest::Method::DELETE, &token_a, &format!("/api/users/{}", root_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(deleted.status(), Status::NOT_FOUND);
+
+        // Deactivating a tenant ends its sessions
+        let response = client
+            .request(reqwest::Method::PUT, &root, &format!("/api/admin/tenants/{}", acme_id.to_hex()))
+            .json(&serde_json::json!({ "active": false }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(client.get(&token_b, "/api/admin/offices").await.0, Status::UNAUTHORIZED);
+        let acme_users = db
+            .collection::<Document>("users")
+            .distinct("_id", doc! { "tenant_id": acme_id })
+            .await
+            .unwrap();
+        let refresh_tokens = db
+            .collection::<Document>("refresh_tokens")
+            .count_documents(doc! { "user_id": { "$in": acme_users } })
+            .await
+            .unwrap();
+        assert_eq!(refresh_tokens, 0);
+        assert_eq!(client.login(Some("acme"), "admin@b.example.com").await, Err(Status::NOT_FOUND));
+
+        db.drop().await.unwrap();
+    }
+
+    /// Needs a local MongoDB, like `test_tenant_isolation`.
+    #[tokio::test]
+    #[ignore]
+    async fn test_register_and_verify_mixed_case_email() {
+        let (db, client) = start_server().await;
+
+        let response = client
+            .http
+            .post(format!("{}/api/auth/register", client.base))
+            .json(&serde_json::json!({
+                "name": "Dewi Lestari",
+                "email": " Dewi.Lestari@Example.COM ",
+                "identifier": "198805",
+                "password": PASSWORD,
+                "lat": -6.2,
+                "long": 106.8,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), Status::CREATED);
+
+        // The link itself is only emailed, so swap in a token we know
+        let verification = db
+            .collection::<Document>("email_verifications")
+            .find_one_and_update(doc! {}, doc! { "$set": { "token_hash": hash_token("known-token") } })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(verification.get_str("email").unwrap(), "dewi.lestari@example.com");
+
+        let response = client
+            .http
+            .post(format!("{}/api/auth/verify-email", client.base))
+            .json(&serde_json::json!({ "token": "known-token" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), Status::OK);
+
+        let user = db
+            .collection::<Document>("users")
+            .find_one(doc! { "identifier": "198805" })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.get_str("email").unwrap(), "dewi.lestari@example.com");
+        assert!(user.get_bool("email_verified").unwrap());
+
+        db.drop().await.unwrap();
+    }
+}
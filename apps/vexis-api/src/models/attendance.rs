@@ -1,16 +1,64 @@
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Attendance {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
     pub user_id: ObjectId,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
     pub timestamp: DateTime<Utc>,
     pub r#type: String, // "In" | "Out"
     pub location: GeoPoint,
     pub face_verified: bool,
+    /// Office whose fence the check-in was validated against; `None` for the
+    /// default fence and for manual entries.
+    #[serde(default)]
+    pub office_id: Option<ObjectId>,
+    /// Distance from the fence center at check-in, in meters.
+    #[serde(default)]
+    pub distance_m: Option<f64>,
+    /// Score from `compare_landmarks` at check-in.
+    #[serde(default)]
+    pub face_similarity: Option<f32>,
+    #[serde(default)]
+    pub device: Option<DeviceInfo>,
+    /// Added by an admin rather than checked in from a device.
+    #[serde(default)]
+    pub manual: bool,
+    /// Voided records stay in the collection for the history but no longer
+    /// count anywhere: not for the next check-in type, reminders, or reports.
+    #[serde(default)]
+    pub voided: bool,
+    /// Admin changes, oldest first.
+    #[serde(default)]
+    pub revisions: Vec<AttendanceRevision>,
+}
+
+/// One admin change to an attendance record. `timestamp` and `type` hold the
+/// values from before the change, so the original record can always be
+/// reconstructed; both are `None` for "create".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttendanceRevision {
+    pub action: String, // "create" | "edit" | "void"
+    pub reason: String,
+    pub changed_by: ObjectId,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub changed_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub r#type: Option<String>,
+}
+
+/// The client that checked in, from `ClientInfo`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use mongodb::bson::Document;
+use serde::{Deserialize, Serialize};
+
+/// An administrative or security-relevant action, recorded by
+/// `utils::audit::AuditEvent::record`. Entries are never updated or deleted:
+/// each one carries the hash of its predecessor in the tenant's chain, so a
+/// changed or removed entry breaks every hash after it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub tenant_id: ObjectId,
+    /// Position in the tenant's chain, starting at 1.
+    pub seq: i64,
+    /// `hash` of entry `seq - 1`, empty for the first entry.
+    pub prev_hash: String,
+    /// User who acted. `None` for failed logins of unknown accounts and for
+    /// entries written by background jobs on nobody's behalf.
+    pub actor_id: Option<ObjectId>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub action: String, // e.g. "user.update", "auth.login", "attendance.export"
+    pub target_type: String, // "user" | "office" | "tenant" | ...
+    pub target_id: Option<ObjectId>,
+    /// Previous values of the fields that changed.
+    #[serde(default)]
+    pub before: Document,
+    /// New values of the fields that changed. Never contains secrets.
+    #[serde(default)]
+    pub after: Document,
+    /// Context that is not a change, such as export filters or why a login
+    /// failed.
+    #[serde(default)]
+    pub details: Document,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    /// SHA-256 over the stored entry without this field.
+    pub hash: String,
+}
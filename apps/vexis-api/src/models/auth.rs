@@ -1,18 +1,75 @@
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
 use serde::{Deserialize, Serialize};
 
+/// Single-use password reset link. Only the SHA-256 of the token is stored.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PasswordReset {
     pub user_id: ObjectId,
     pub email: String,
-    pub token: String,
+    pub token_hash: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
     pub expires_at: DateTime<Utc>,
 }
 
+/// Pending proof of an email address, either for a new account (`signup`) or
+/// for a requested address change (`change`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerification {
+    pub user_id: ObjectId,
+    pub email: String,
+    pub token_hash: String,
+    pub purpose: String, // "signup" | "change"
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A refresh token belongs to a family that starts at login. Every rotation
+/// keeps the old document (marked `rotated_at`) so that a replayed token can be
+/// recognised and the whole family revoked.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RefreshToken {
     pub user_id: ObjectId,
-    pub token: String,
+    pub token_hash: String,
+    pub family_id: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub rotated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityEvent {
+    pub user_id: ObjectId,
+    pub event: String, // "refresh_token_reuse"
+    pub details: mongodb::bson::Document,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pending OIDC authorization request, keyed by the `state` parameter.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcLoginState {
+    pub state: String,
+    /// Tenant the login started in; new accounts are provisioned there.
+    pub tenant_id: ObjectId,
+    pub nonce: String,
+    pub code_verifier: String,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Pending passwordless login: a short numeric code and a magic link token
+/// sent in the same email. Both are stored hashed and either one can be used.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginCode {
+    pub user_id: ObjectId,
+    pub code_hash: String,
+    pub link_token_hash: String,
+    pub attempts: i32,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
     pub expires_at: DateTime<Utc>,
 }
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use serde::{Deserialize, Serialize};
+
+/// A unit of the organisation, assigned via `User::department_id`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Department {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
+    pub name: String,
+    /// Supervises everyone in the department, and is who team members go to
+    /// when their team has no supervisor.
+    #[serde(default)]
+    pub supervisor_id: Option<ObjectId>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A group within a department, assigned via `User::team_id`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Team {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
+    pub name: String,
+    pub department_id: ObjectId,
+    #[serde(default)]
+    pub supervisor_id: Option<ObjectId>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use mongodb::bson::Document;
+use serde::{Deserialize, Serialize};
+
+/// A unit of background work in the `jobs` collection, see `utils::jobs`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub kind: String, // "email" | "push" | "webhook"
+    pub payload: Document,
+    pub status: String, // "pending" | "running" | "done" | "dead"
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Earliest time the job may (re)run.
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub run_at: DateTime<Utc>,
+    /// While running, the lease after which another worker may take the job over.
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    /// Set once the job is done so the TTL index clears it out.
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
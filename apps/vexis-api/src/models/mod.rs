@@ -1,3 +1,12 @@
 pub mod attendance;
 pub mod auth;
 pub mod user;
+pub mod settings;
+pub mod job;
+pub mod push;
+pub mod webhook;
+pub mod office;
+pub mod audit;
+pub mod user_import;
+pub mod department;
+pub mod tenant;
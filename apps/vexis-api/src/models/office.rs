@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use serde::{Deserialize, Serialize};
+use crate::models::user::OfficeLocation;
+
+/// A workplace users can be assigned to via `User::office_id`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Office {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
+    pub name: String,
+    pub location: OfficeLocation,
+    /// Check-in radius around `location`, in meters.
+    pub radius_m: f64,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use serde::{Deserialize, Serialize};
+
+/// A browser or device registered for Web Push. The endpoint is unique; a
+/// device that logs in as someone else is moved to that user.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushSubscription {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub device: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Marks a reminder as sent so it goes out once per user, kind and day even
+/// with several instances running the scheduler.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SentReminder {
+    pub user_id: ObjectId,
+    pub kind: String, // "check_in" | "check_out"
+    pub date: String, // WIB date, YYYY-MM-DD
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
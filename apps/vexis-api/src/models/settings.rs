@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// `_id` of the deployment-wide document in the `settings` collection, from
+/// before settings moved onto each tenant. Only read by the backfill.
+pub const PASSWORDLESS_SETTINGS_ID: &str = "passwordless";
+
+/// Runtime switch for email code / magic link login, stored in
+/// `Tenant::settings` so admins can change it without a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PasswordlessSettings {
+    pub enabled_roles: Vec<String>,
+}
+
+impl PasswordlessSettings {
+    pub fn is_enabled_for(&self, role: &str) -> bool {
+        self.enabled_roles.iter().any(|r| r == role)
+    }
+}
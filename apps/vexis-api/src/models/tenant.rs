@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use serde::{Deserialize, Serialize};
+use crate::models::settings::PasswordlessSettings;
+
+/// Created on first start for data that predates tenants, and used when a
+/// request does not name a tenant.
+pub const DEFAULT_TENANT_SLUG: &str = "default";
+
+/// A company sharing this deployment. Every tenant-owned document carries its
+/// `tenant_id`; see `middleware::tenant`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tenant {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    /// Sent by clients in the `X-Tenant` header before login.
+    pub slug: String,
+    /// Inactive tenants cannot log in and their tokens stop working.
+    pub active: bool,
+    #[serde(default)]
+    pub settings: TenantSettings,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Settings each tenant's admins (or a super admin) can change at runtime.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantSettings {
+    #[serde(default)]
+    pub passwordless: PasswordlessSettings,
+}
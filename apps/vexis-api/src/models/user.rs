@@ -1,18 +1,75 @@
+use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional;
 use serde::{Deserialize, Serialize};
+use crate::utils::email_templates::DEFAULT_LOCALE;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
     pub name: String,
     pub email: String,
+    #[serde(default = "default_email_verified")]
+    pub email_verified: bool,
+    /// New address waiting for confirmation; `email` changes only once verified.
+    #[serde(default)]
+    pub pending_email: Option<String>,
     pub identifier: String, // NIP or NIM
     pub password_hash: String,
-    pub role: String, // "admin" | "user"
+    /// Previous password hashes, most recent last, for the reuse check.
+    #[serde(default)]
+    pub password_history: Vec<String>,
+    pub role: String, // "user" | "admin" | "super_admin"
     pub office_location: OfficeLocation,
     pub face_landmarks: Vec<f32>,
+    /// Whether `face_landmarks` is set, kept as its own field so the admin
+    /// user list can filter on it with an index.
+    #[serde(default)]
+    pub face_enrolled: bool,
     pub photo_url: Option<String>,
+    /// Bumped to revoke every access token issued before the change.
+    #[serde(default)]
+    pub token_version: i32,
+    #[serde(default)]
+    pub office_id: Option<ObjectId>,
+    #[serde(default)]
+    pub department_id: Option<ObjectId>,
+    /// Always a team of `department_id`.
+    #[serde(default)]
+    pub team_id: Option<ObjectId>,
+    /// `sub` of the linked identity provider account, for SSO users.
+    #[serde(default)]
+    pub oidc_subject: Option<String>,
+    /// DN of the directory entry, for LDAP users.
+    #[serde(default)]
+    pub ldap_dn: Option<String>,
+    /// Language for emails: "id" | "en".
+    #[serde(default = "default_locale")]
+    pub locale: String,
+    /// Set for admin-created accounts with a temporary password; cleared when
+    /// the user picks their own.
+    #[serde(default)]
+    pub must_change_password: bool,
+    /// Set by `delete_user`. The account can no longer log in or check in, but
+    /// its attendance stays in reports until the retention purge.
+    #[serde(default)]
+    pub deactivated: bool,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Admin who deleted the account.
+    #[serde(default)]
+    pub deleted_by: Option<ObjectId>,
+}
+
+/// Accounts created before email verification existed are treated as verified.
+fn default_email_verified() -> bool {
+    true
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use serde::{Deserialize, Serialize};
+
+/// A validated row waiting to become an account.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRow {
+    /// Line in the uploaded file (the header is line 1).
+    pub row: u32,
+    pub name: String,
+    pub email: String,
+    pub identifier: String,
+    pub role: String,
+    pub office_id: Option<ObjectId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RowError {
+    pub row: u32,
+    pub message: String,
+}
+
+/// A committed bulk import, processed by a `user_import` job. Progress is
+/// saved as rows are created so a restarted job resumes where it stopped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserImport {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
+    pub created_by: ObjectId,
+    pub status: String, // "pending" | "running" | "done"
+    pub rows: Vec<ImportRow>,
+    pub total: i32,
+    pub processed: i32,
+    pub created: i32,
+    /// Rows that failed at commit time, e.g. taken since the dry run.
+    pub errors: Vec<RowError>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::{
+    chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional,
+};
+use serde::{Deserialize, Serialize};
+
+/// An admin-managed endpoint that receives the events it subscribes to, see
+/// `utils::webhook`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub tenant_id: ObjectId,
+    pub url: String,
+    /// Shared secret for the `X-Vexis-Signature` header. Kept in plain text
+    /// because it is needed to sign every delivery.
+    pub secret: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub description: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// One event sent to one webhook. The body is stored as sent so retries carry
+/// the same bytes and signature input.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub webhook_id: ObjectId,
+    pub event: String,
+    pub body: String,
+    pub status: String, // "pending" | "delivered" | "failed"
+    pub attempts: i32,
+    /// HTTP status of the last attempt, if the endpoint answered.
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub delivered_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
@@ -1,15 +1,19 @@
-use crate::handlers::admin_attendance::{export_attendance_csv, list_attendance};
+use crate::handlers::admin_attendance::{
+    create_attendance, export_attendance_csv, get_attendance, list_attendance, update_attendance, void_attendance,
+};
 use crate::middleware::auth::require_auth;
 use crate::middleware::rbac::require_admin;
 use crate::AppState;
-use axum::{middleware, routing::get, Router};
+use axum::{middleware, routing::{get, post}, Router};
 
 use std::sync::Arc;
 
-pub fn admin_attendance_routes() -> Router<Arc<AppState>> {
+pub fn admin_attendance_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", get(list_attendance))
+        .route("/", get(list_attendance).post(create_attendance))
         .route("/export", get(export_attendance_csv))
+        .route("/:id", get(get_attendance).patch(update_attendance))
+        .route("/:id/void", post(void_attendance))
         .layer(middleware::from_fn(require_admin))
-        .layer(middleware::from_fn(require_auth))
+        .layer(middleware::from_fn_with_state(state, require_auth))
 }
@@ -0,0 +1,16 @@
+use crate::handlers::admin_audit::{list_audit, verify_audit};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+/// Read only: the audit log has no endpoints that change it.
+pub fn admin_audit_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_audit))
+        .route("/verify", get(verify_audit))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
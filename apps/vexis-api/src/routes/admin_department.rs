@@ -0,0 +1,26 @@
+use crate::handlers::admin_department::{
+    create_department, create_team, delete_department, delete_team, list_departments, list_teams,
+    update_department, update_team,
+};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::{get, put}, Router};
+
+use std::sync::Arc;
+
+pub fn admin_department_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_departments).post(create_department))
+        .route("/:id", put(update_department).delete(delete_department))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
+
+pub fn admin_team_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_teams).post(create_team))
+        .route("/:id", put(update_team).delete(delete_team))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
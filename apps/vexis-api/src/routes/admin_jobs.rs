@@ -0,0 +1,15 @@
+use crate::handlers::admin_jobs::{list_jobs, retry_job};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_super_admin;
+use crate::AppState;
+use axum::{middleware, routing::{get, post}, Router};
+
+use std::sync::Arc;
+
+pub fn admin_jobs_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_jobs))
+        .route("/:id/retry", post(retry_job))
+        .layer(middleware::from_fn(require_super_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -0,0 +1,15 @@
+use crate::handlers::admin_office::{create_office, delete_office, list_offices, update_office};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::{get, put}, Router};
+
+use std::sync::Arc;
+
+pub fn admin_office_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_offices).post(create_office))
+        .route("/:id", put(update_office).delete(delete_office))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -0,0 +1,15 @@
+use crate::handlers::admin_presence::{get_presence, presence_stream};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+pub fn admin_presence_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_presence))
+        .route("/stream", get(presence_stream))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -0,0 +1,17 @@
+use crate::handlers::passwordless::{get_passwordless_settings, update_passwordless_settings};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+pub fn admin_settings_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/passwordless",
+            get(get_passwordless_settings).put(update_passwordless_settings),
+        )
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
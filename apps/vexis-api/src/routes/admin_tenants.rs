@@ -0,0 +1,18 @@
+use crate::handlers::admin_tenants::{
+    create_tenant, get_tenant, get_tenant_settings, list_tenants, update_tenant, update_tenant_settings,
+};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_super_admin;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+pub fn admin_tenants_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_tenants).post(create_tenant))
+        .route("/:id", get(get_tenant).put(update_tenant))
+        .route("/:id/settings", get(get_tenant_settings).put(update_tenant_settings))
+        .layer(middleware::from_fn(require_super_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -1,15 +1,26 @@
-use crate::handlers::admin_user::{delete_user, list_users};
+use crate::handlers::admin_import::{get_import, import_users};
+use crate::handlers::admin_user::{
+    create_user, delete_user, force_password_reset, get_user, list_users, reactivate_user, update_user,
+};
 use crate::middleware::auth::require_auth;
 use crate::middleware::rbac::require_admin;
 use crate::AppState;
-use axum::{middleware, routing::get, Router};
+use axum::{extract::DefaultBodyLimit, middleware, routing::{get, post}, Router};
 
 use std::sync::Arc;
 
-pub fn admin_user_routes() -> Router<Arc<AppState>> {
+/// Roughly 5000 rows of CSV with room to spare for XLSX overhead.
+const IMPORT_BODY_LIMIT: usize = 10 * 1024 * 1024;
+
+pub fn admin_user_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", get(list_users))
-        .route("/:id", axum::routing::delete(delete_user))
+        .route("/", get(list_users).post(create_user))
+        .route("/import", post(import_users).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
+        .route("/imports/:id", get(get_import))
+        .route("/:id", get(get_user).patch(update_user).delete(delete_user))
+        .route("/:id/reset-password", post(force_password_reset))
+        .route("/:id/deactivate", post(delete_user))
+        .route("/:id/reactivate", post(reactivate_user))
         .layer(middleware::from_fn(require_admin))
-        .layer(middleware::from_fn(require_auth))
+        .layer(middleware::from_fn_with_state(state, require_auth))
 }
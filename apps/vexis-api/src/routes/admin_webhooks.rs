@@ -0,0 +1,19 @@
+use crate::handlers::admin_webhooks::{
+    create_webhook, delete_webhook, list_deliveries, list_webhooks, test_webhook, update_webhook,
+};
+use crate::middleware::auth::require_auth;
+use crate::middleware::rbac::require_admin;
+use crate::AppState;
+use axum::{middleware, routing::{get, post, put}, Router};
+
+use std::sync::Arc;
+
+pub fn admin_webhooks_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_webhooks).post(create_webhook))
+        .route("/:id", put(update_webhook).delete(delete_webhook))
+        .route("/:id/deliveries", get(list_deliveries))
+        .route("/:id/test", post(test_webhook))
+        .layer(middleware::from_fn(require_admin))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -4,8 +4,8 @@ use crate::AppState;
 use axum::Router;
 use std::sync::Arc;
 
-pub fn routes() -> Router<Arc<AppState>> {
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/check", axum::routing::post(attendance::check_in_out))
-        .layer(axum::middleware::from_fn(require_auth))
+        .layer(axum::middleware::from_fn_with_state(state, require_auth))
 }
@@ -1,13 +1,28 @@
-use crate::handlers::auth::{forgot_password, login, refresh_token, register, reset_password};
+use crate::handlers::auth::{
+    forgot_password, login, refresh_token, register, resend_verification, reset_password, verify_email,
+};
+use crate::handlers::oidc::{oidc_authorize, oidc_callback};
+use crate::handlers::passwordless::{request_login_code, verify_login_code, verify_magic_link};
+use crate::middleware::tenant::resolve_tenant;
 use crate::AppState;
-use axum::{routing::post, Router};
+use axum::{middleware, routing::{get, post}, Router};
 use std::sync::Arc;
 
-pub fn auth_routes() -> Router<Arc<AppState>> {
+pub fn auth_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
+        // Routes that look an account up by email or identifier need the tenant
         .route("/register", post(register))
         .route("/login", post(login))
-        .route("/refresh", post(refresh_token))
         .route("/forgot-password", post(forgot_password))
+        .route("/verify-email/resend", post(resend_verification))
+        .route("/oidc/authorize", get(oidc_authorize))
+        .route("/passwordless/request", post(request_login_code))
+        .route("/passwordless/verify", post(verify_login_code))
+        .route_layer(middleware::from_fn_with_state(state, resolve_tenant))
+        // Tokens already identify the account, and with it the tenant
+        .route("/refresh", post(refresh_token))
         .route("/reset-password", post(reset_password))
+        .route("/verify-email", post(verify_email))
+        .route("/oidc/callback", post(oidc_callback))
+        .route("/passwordless/magic-link", post(verify_magic_link))
 }
@@ -4,8 +4,8 @@ use crate::AppState;
 use axum::Router;
 use std::sync::Arc;
 
-pub fn routes() -> Router<Arc<AppState>> {
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/stats", axum::routing::get(dashboard::get_dashboard_stats))
-        .layer(axum::middleware::from_fn(require_auth))
+        .layer(axum::middleware::from_fn_with_state(state, require_auth))
 }
@@ -1,6 +1,17 @@
 pub mod admin_attendance;
+pub mod admin_audit;
+pub mod admin_department;
+pub mod admin_jobs;
+pub mod admin_office;
+pub mod admin_presence;
+pub mod admin_settings;
+pub mod admin_tenants;
 pub mod admin_user;
+pub mod admin_webhooks;
 pub mod attendance;
 pub mod auth;
 pub mod dashboard;
+pub mod push;
+pub mod team;
 pub mod user;
+pub mod well_known;
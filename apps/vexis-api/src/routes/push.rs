@@ -0,0 +1,19 @@
+use crate::handlers::push::{list_subscriptions, subscribe, unsubscribe, vapid_public_key};
+use crate::middleware::auth::require_auth;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let protected = Router::new()
+        .route(
+            "/subscriptions",
+            get(list_subscriptions).post(subscribe).delete(unsubscribe),
+        )
+        .layer(middleware::from_fn_with_state(state, require_auth));
+
+    Router::new()
+        .route("/vapid-public-key", get(vapid_public_key))
+        .merge(protected)
+}
@@ -0,0 +1,16 @@
+use crate::handlers::team::{export_team_attendance_csv, list_members, list_team_attendance};
+use crate::middleware::auth::require_auth;
+use crate::AppState;
+use axum::{middleware, routing::get, Router};
+
+use std::sync::Arc;
+
+/// Supervisor view; each handler checks that the caller leads a team or
+/// department.
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/members", get(list_members))
+        .route("/attendance", get(list_team_attendance))
+        .route("/attendance/export", get(export_team_attendance_csv))
+        .layer(middleware::from_fn_with_state(state, require_auth))
+}
@@ -1,4 +1,6 @@
-use crate::handlers::user::{get_me, register_face, update_location, update_me, upload_photo};
+use crate::handlers::user::{
+    change_email, change_password, get_me, register_face, update_location, update_me, upload_photo,
+};
 use crate::middleware::auth::require_auth;
 use crate::AppState;
 use axum::{
@@ -9,11 +11,13 @@ use axum::{
 
 use std::sync::Arc;
 
-pub fn user_routes() -> Router<Arc<AppState>> {
+pub fn user_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/me", get(get_me).put(update_me))
+        .route("/me/email", put(change_email))
+        .route("/me/password", put(change_password))
         .route("/me/photo", post(upload_photo))
         .route("/me/location", put(update_location))
         .route("/me/face", post(register_face))
-        .layer(middleware::from_fn(require_auth))
+        .layer(middleware::from_fn_with_state(state, require_auth))
 }
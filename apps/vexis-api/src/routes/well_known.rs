@@ -0,0 +1,8 @@
+use crate::handlers::well_known::jwks;
+use crate::AppState;
+use axum::{routing::get, Router};
+use std::sync::Arc;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/jwks.json", get(jwks))
+}
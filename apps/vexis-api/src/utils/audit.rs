@@ -0,0 +1,365 @@
+use crate::config::db::is_duplicate_key;
+use crate::middleware::client::ClientInfo;
+use crate::models::audit::AuditEntry;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, Bson, Document};
+use mongodb::Database;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::Mutex;
+
+pub const COLLECTION: &str = "audit_logs";
+/// Name of the collection before it was renamed to `COLLECTION`.
+pub const LEGACY_COLLECTION: &str = "audit_log";
+
+/// Writers in other processes race for the next `seq`; the loser re-reads
+/// the head.
+const APPEND_ATTEMPTS: usize = 5;
+
+/// One lock per tenant chain, serializing appends within this process so
+/// only other API instances can make an append lose a race. Chains of
+/// different tenants are written concurrently.
+static APPEND_LOCKS: LazyLock<std::sync::Mutex<HashMap<ObjectId, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+fn append_lock(tenant_id: ObjectId) -> Arc<Mutex<()>> {
+    APPEND_LOCKS.lock().unwrap().entry(tenant_id).or_default().clone()
+}
+
+/// An entry about to be appended to the audit log.
+///
+/// ```ignore
+/// AuditEvent::new(tenant_id, Some(admin_id), "office.update", "office", Some(office_id))
+///     .changes(&current, &update)
+///     .record(&state.db, &client)
+///     .await;
+/// ```
+pub struct AuditEvent {
+    tenant_id: ObjectId,
+    actor_id: Option<ObjectId>,
+    action: &'static str,
+    target_type: &'static str,
+    target_id: Option<ObjectId>,
+    before: Document,
+    after: Document,
+    details: Document,
+}
+
+impl AuditEvent {
+    pub fn new(
+        tenant_id: ObjectId,
+        actor_id: Option<ObjectId>,
+        action: &'static str,
+        target_type: &'static str,
+        target_id: Option<ObjectId>,
+    ) -> Self {
+        Self {
+            tenant_id,
+            actor_id,
+            action,
+            target_type,
+            target_id,
+            before: Document::new(),
+            after: Document::new(),
+            details: Document::new(),
+        }
+    }
+
+    /// Records the fields of `update` whose values differ from `current`, the
+    /// entity as it was. Keys may be dotted paths, as in a `$set`.
+    pub fn changes<T: Serialize>(mut self, current: &T, update: &Document) -> Self {
+        let current = bson::to_document(current).unwrap_or_default();
+        (self.before, self.after) = diff(&current, update);
+        self
+    }
+
+    /// State of a created entity, or the fields a one-off action set.
+    pub fn after(mut self, after: Document) -> Self {
+        self.after = after;
+        self
+    }
+
+    /// State of a deleted entity.
+    pub fn before(mut self, before: Document) -> Self {
+        self.before = before;
+        self
+    }
+
+    pub fn details(mut self, details: Document) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// Appends the entry to the tenant's chain. A failure is logged rather
+    /// than returned, since the action it describes has already happened.
+    pub async fn record(self, db: &Database, client: &ClientInfo) {
+        let action = self.action;
+        let entry = |seq: i64, prev_hash: String| -> Result<Document, bson::ser::Error> {
+            seal(&AuditEntry {
+                id: ObjectId::new(),
+                tenant_id: self.tenant_id,
+                seq,
+                prev_hash,
+                actor_id: self.actor_id,
+                ip: client.ip.clone(),
+                user_agent: client.user_agent.clone(),
+                action: self.action.to_string(),
+                target_type: self.target_type.to_string(),
+                target_id: self.target_id,
+                before: self.before.clone(),
+                after: self.after.clone(),
+                details: self.details.clone(),
+                created_at: Utc::now(),
+                hash: String::new(),
+            })
+        };
+
+        if let Err(e) = append(db, self.tenant_id, entry).await {
+            eprintln!("Failed to record audit entry {} in tenant {}: {:?}", action, self.tenant_id, e);
+        }
+    }
+}
+
+/// Before and after values of the fields in `update` that differ from
+/// `current`. Fields missing from `current` count as null.
+pub fn diff(current: &Document, update: &Document) -> (Document, Document) {
+    let mut before = Document::new();
+    let mut after = Document::new();
+    for (key, new) in update {
+        let old = get_path(current, key).cloned().unwrap_or(Bson::Null);
+        if old != *new {
+            before.insert(key, old);
+            after.insert(key, new.clone());
+        }
+    }
+    (before, after)
+}
+
+fn get_path<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    match path.split_once('.') {
+        Some((head, rest)) => get_path(document.get_document(head).ok()?, rest),
+        None => document.get(path),
+    }
+}
+
+/// Serializes `entry` and fills in its hash.
+fn seal<T: Serialize>(entry: &T) -> Result<Document, bson::ser::Error> {
+    let mut document = bson::to_document(entry)?;
+    let hash = entry_hash(&document);
+    document.insert("hash", hash);
+    Ok(document)
+}
+
+/// SHA-256 over the BSON bytes of a stored entry, leaving out `hash` itself.
+/// MongoDB keeps field order, so the bytes read back match those written.
+pub fn entry_hash(entry: &Document) -> String {
+    let mut unsealed = entry.clone();
+    unsealed.remove("hash");
+    let mut bytes = Vec::new();
+    unsealed
+        .to_writer(&mut bytes)
+        .expect("writing BSON to a Vec cannot fail");
+    format!("{:x}", Sha256::digest(&bytes))
+}
+
+/// `seq` and `hash` of the newest entry in the chain, or `(0, "")`.
+async fn chain_head(db: &Database, tenant_id: ObjectId) -> mongodb::error::Result<(i64, String)> {
+    let head = db
+        .collection::<Document>(COLLECTION)
+        .find_one(doc! { "tenant_id": tenant_id, "seq": { "$exists": true } })
+        .sort(doc! { "seq": -1 })
+        .projection(doc! { "seq": 1, "hash": 1 })
+        .await?;
+    Ok(match head {
+        Some(head) => (
+            head.get_i64("seq").unwrap_or(0),
+            head.get_str("hash").unwrap_or_default().to_string(),
+        ),
+        None => (0, String::new()),
+    })
+}
+
+/// Inserts the entry `build` makes for the next position in the chain. The
+/// unique `(tenant_id, seq)` index turns a lost race into a retry.
+async fn append<F>(db: &Database, tenant_id: ObjectId, build: F) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(i64, String) -> Result<Document, bson::ser::Error>,
+{
+    let collection = db.collection::<Document>(COLLECTION);
+    let lock = append_lock(tenant_id);
+    let _guard = lock.lock().await;
+    for _ in 0..APPEND_ATTEMPTS {
+        let (seq, prev_hash) = chain_head(db, tenant_id).await?;
+        match collection.insert_one(build(seq + 1, prev_hash)?).await {
+            Ok(_) => return Ok(()),
+            Err(e) if is_duplicate_key(&e) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err("audit chain stayed contended".into())
+}
+
+/// Links entries written before hash chaining into their tenant's chain, in
+/// the order they were written. Runs on start-up, before any new entry.
+pub async fn chain_unsealed(db: &Database) -> mongodb::error::Result<()> {
+    let collection = db.collection::<Document>(COLLECTION);
+    let mut cursor = collection
+        .find(doc! { "seq": { "$exists": false } })
+        .sort(doc! { "created_at": 1, "_id": 1 })
+        .await?;
+
+    while let Some(mut entry) = cursor.try_next().await? {
+        let (Ok(id), Ok(tenant_id)) = (entry.get_object_id("_id"), entry.get_object_id("tenant_id")) else {
+            continue;
+        };
+        let (seq, prev_hash) = chain_head(db, tenant_id).await?;
+        entry.insert("seq", seq + 1);
+        entry.insert("prev_hash", prev_hash);
+        let hash = entry_hash(&entry);
+        entry.insert("hash", hash);
+        collection
+            .replace_one(doc! { "_id": id, "seq": { "$exists": false } }, entry)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Result of re-checking a tenant's chain.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ChainReport {
+    pub valid: bool,
+    pub entries: i64,
+    /// First position where the chain does not hold.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+/// Recomputes every hash in the tenant's chain. Removing the newest entries
+/// cannot be detected this way, which is why `entries` is reported for
+/// comparison with an earlier run.
+pub async fn verify_chain(db: &Database, tenant_id: ObjectId) -> mongodb::error::Result<ChainReport> {
+    let mut cursor = db
+        .collection::<Document>(COLLECTION)
+        .find(doc! { "tenant_id": tenant_id, "seq": { "$exists": true } })
+        .sort(doc! { "seq": 1 })
+        .await?;
+
+    let mut checked = 0;
+    let mut prev_hash = String::new();
+    while let Some(entry) = cursor.try_next().await? {
+        let expected = checked + 1;
+        let problem = if entry.get_i64("seq").ok() != Some(expected) {
+            Some("missing entry")
+        } else if entry.get_str("prev_hash").ok() != Some(prev_hash.as_str()) {
+            Some("previous hash does not match")
+        } else if entry.get_str("hash").ok() != Some(entry_hash(&entry).as_str()) {
+            Some("entry was modified")
+        } else {
+            None
+        };
+        if let Some(reason) = problem {
+            return Ok(ChainReport { valid: false, entries: checked, broken_at: Some(expected), reason: Some(reason) });
+        }
+        prev_hash = entry.get_str("hash").unwrap_or_default().to_string();
+        checked = expected;
+    }
+
+    Ok(ChainReport { valid: true, entries: checked, broken_at: None, reason: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_keeps_changed_fields_only() {
+        let current = doc! {
+            "name": "Budi",
+            "role": "user",
+            "settings": { "passwordless": { "enabled_roles": ["user"] } },
+        };
+        let update = doc! {
+            "name": "Budi",
+            "role": "admin",
+            "office_id": ObjectId::parse_str("65a000000000000000000001").unwrap(),
+            "settings.passwordless.enabled_roles": ["user"],
+        };
+
+        let (before, after) = diff(&current, &update);
+        assert_eq!(before, doc! { "role": "user", "office_id": Bson::Null });
+        assert_eq!(
+            after,
+            doc! { "role": "admin", "office_id": ObjectId::parse_str("65a000000000000000000001").unwrap() }
+        );
+    }
+
+    #[test]
+    fn test_entry_hash_covers_every_field_but_itself() {
+        let entry = seal(&doc! { "seq": 1_i64, "prev_hash": "", "action": "user.update" }).unwrap();
+        let hash = entry.get_str("hash").unwrap().to_string();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(entry_hash(&entry), hash);
+
+        let mut tampered = entry.clone();
+        tampered.insert("action", "user.create");
+        assert_ne!(entry_hash(&tampered), hash);
+    }
+
+    /// Needs a local MongoDB, like `middleware::tenant::tests::test_tenant_isolation`.
+    #[tokio::test]
+    #[ignore]
+    async fn test_chain_detects_tampering() {
+        let url = std::env::var("MONGODB_TEST_URL").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+        let db = mongodb::Client::with_uri_str(url)
+            .await
+            .unwrap()
+            .database(&format!("vexis_test_{}", ObjectId::new()));
+        crate::config::db::prepare(&db).await.unwrap();
+        let collection = db.collection::<Document>(COLLECTION);
+        let tenant_id = ObjectId::new();
+
+        // An entry from before chaining is linked in first
+        collection
+            .insert_one(doc! {
+                "tenant_id": tenant_id,
+                "actor_id": ObjectId::new(),
+                "action": "office.create",
+                "target_type": "office",
+                "target_id": ObjectId::new(),
+                "details": { "name": "Pusat" },
+                "created_at": bson::DateTime::now(),
+            })
+            .await
+            .unwrap();
+        chain_unsealed(&db).await.unwrap();
+
+        let client = ClientInfo { ip: Some("192.0.2.1".to_string()), user_agent: None };
+        let writes = (0..10).map(|i| {
+            AuditEvent::new(tenant_id, None, "auth.login_failed", "user", None)
+                .details(doc! { "attempt": i })
+                .record(&db, &client)
+        });
+        futures::future::join_all(writes).await;
+
+        let report = verify_chain(&db, tenant_id).await.unwrap();
+        assert_eq!(report, ChainReport { valid: true, entries: 11, broken_at: None, reason: None });
+
+        collection
+            .update_one(doc! { "tenant_id": tenant_id, "seq": 4_i64 }, doc! { "$set": { "details.attempt": 99 } })
+            .await
+            .unwrap();
+        let report = verify_chain(&db, tenant_id).await.unwrap();
+        assert_eq!((report.broken_at, report.reason), (Some(4), Some("entry was modified")));
+
+        collection.delete_one(doc! { "tenant_id": tenant_id, "seq": 4_i64 }).await.unwrap();
+        let report = verify_chain(&db, tenant_id).await.unwrap();
+        assert_eq!((report.broken_at, report.reason), (Some(4), Some("missing entry")));
+
+        db.drop().await.unwrap();
+    }
+}
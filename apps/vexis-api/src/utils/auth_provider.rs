@@ -0,0 +1,137 @@
+use crate::config::db::is_duplicate_key;
+use crate::models::user::User;
+use async_trait::async_trait;
+use crate::utils::email::normalize_address;
+use crate::utils::password::{hash_password, verify_password, PasswordCheck};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Database;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AuthProviderError {
+    Database(mongodb::error::Error),
+    Directory(String),
+    /// The credentials are valid but the matching Vexis account cannot be used.
+    Conflict(&'static str),
+}
+
+impl fmt::Display for AuthProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthProviderError::Database(e) => write!(f, "database error: {}", e),
+            AuthProviderError::Directory(msg) => write!(f, "directory error: {}", msg),
+            AuthProviderError::Conflict(msg) => write!(f, "account conflict: {}", msg),
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for AuthProviderError {
+    fn from(e: mongodb::error::Error) -> Self {
+        if is_duplicate_key(&e) {
+            return AuthProviderError::Conflict("directory email or identifier belongs to another account");
+        }
+        AuthProviderError::Database(e)
+    }
+}
+
+/// A way of checking a login (email or NIP/NIM) and password within a tenant.
+///
+/// `login` asks each configured provider in turn. `Ok(None)` means this
+/// provider does not accept the credentials and the next one is tried; `Err`
+/// means the provider itself failed.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn authenticate(
+        &self,
+        db: &Database,
+        tenant_id: ObjectId,
+        login: &str,
+        password: &str,
+    ) -> Result<Option<User>, AuthProviderError>;
+}
+
+/// Local accounts with a password hash. Legacy bcrypt hashes are upgraded to
+/// Argon2id on the first successful login.
+pub struct PasswordProvider;
+
+#[async_trait]
+impl AuthProvider for PasswordProvider {
+    fn name(&self) -> &'static str {
+        "password"
+    }
+
+    async fn authenticate(
+        &self,
+        db: &Database,
+        tenant_id: ObjectId,
+        login: &str,
+        password: &str,
+    ) -> Result<Option<User>, AuthProviderError> {
+        let filter = doc! {
+            "tenant_id": tenant_id,
+            "$or": [
+                { "email": normalize_address(login) },
+                { "identifier": login }
+            ]
+        };
+
+        let Some(user) = db.collection::<User>("users").find_one(filter).await? else {
+            return Ok(None);
+        };
+
+        match verify_password(password, &user.password_hash) {
+            PasswordCheck::Invalid => Ok(None),
+            PasswordCheck::Valid { needs_rehash } => {
+                if needs_rehash {
+                    if let Ok(upgraded) = hash_password(password) {
+                        db.collection::<User>("users")
+                            .update_one(
+                                doc! { "_id": user.id },
+                                doc! { "$set": { "password_hash": upgraded } },
+                            )
+                            .await?;
+                    }
+                }
+                Ok(Some(user))
+            }
+        }
+    }
+}
+
+/// Maps directory or IdP group names to a Vexis role. `admin` wins over any
+/// other mapped role; without a match `default_role` is used.
+pub fn map_groups_to_role<'a>(
+    groups: impl IntoIterator<Item = &'a str>,
+    mapping: &HashMap<String, String>,
+    default_role: &str,
+) -> String {
+    let roles: Vec<&String> = groups.into_iter().filter_map(|g| mapping.get(g)).collect();
+
+    if roles.iter().any(|r| r.as_str() == "admin") {
+        return "admin".to_string();
+    }
+    roles
+        .first()
+        .map(|r| r.to_string())
+        .unwrap_or_else(|| default_role.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_groups_to_role() {
+        let mapping = HashMap::from([
+            ("staff".to_string(), "user".to_string()),
+            ("it-admins".to_string(), "admin".to_string()),
+        ]);
+
+        assert_eq!(map_groups_to_role(["staff", "it-admins"], &mapping, "user"), "admin");
+        assert_eq!(map_groups_to_role(["staff"], &mapping, "guest"), "user");
+        assert_eq!(map_groups_to_role(["unknown"], &mapping, "guest"), "guest");
+    }
+}
@@ -1,44 +1,304 @@
+use crate::config::email::{EmailConfig, SmtpTls, TransportConfig};
+use crate::utils::email_templates::EmailTemplates;
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use reqwest::Client;
 use serde::Serialize;
-use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum EmailError {
+    Http(reqwest::Error),
+    /// The provider answered but refused the message.
+    Rejected(String),
+    Smtp(lettre::transport::smtp::Error),
+    InvalidMessage(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::Http(e) => write!(f, "request to email provider failed: {}", e),
+            EmailError::Rejected(msg) => write!(f, "email provider rejected message: {}", msg),
+            EmailError::Smtp(e) => write!(f, "SMTP error: {}", e),
+            EmailError::InvalidMessage(msg) => write!(f, "invalid email: {}", msg),
+            EmailError::Io(e) => write!(f, "could not write email: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+impl From<reqwest::Error> for EmailError {
+    fn from(e: reqwest::Error) -> Self {
+        EmailError::Http(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    /// Plain-text alternative for clients that do not render HTML.
+    pub text: String,
+}
+
+/// Addresses are stored and looked up in this form, so an account matches
+/// however the address was capitalised when typed.
+pub fn normalize_address(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Loose syntax check for addresses typed in by admins; delivery is the real
+/// test.
+pub fn is_valid_address(email: &str) -> bool {
+    email.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !domain.contains('@')
+            && !email.contains(char::is_whitespace)
+    })
+}
+
+/// A way of delivering outgoing email, chosen by `EMAIL_TRANSPORT`.
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError>;
+}
+
+pub fn transport_from_config(config: EmailConfig) -> Result<Box<dyn EmailTransport>, String> {
+    Ok(match config.transport {
+        TransportConfig::Resend { api_key } => Box::new(ResendTransport {
+            client: Client::new(),
+            api_key,
+            from: config.from,
+        }),
+        TransportConfig::Smtp { host, port, username, password, tls } => {
+            let builder = match tls {
+                SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+                SmtpTls::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+                SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)),
+            }
+            .map_err(|e| format!("invalid SMTP settings: {}", e))?;
+            let builder = match (username, password) {
+                (Some(user), Some(pass)) => builder.credentials(Credentials::new(user, pass)),
+                _ => builder,
+            };
+            let from = config
+                .from
+                .parse()
+                .map_err(|e| format!("invalid EMAIL_FROM: {}", e))?;
+            Box::new(SmtpTransport {
+                mailer: builder.port(port).build(),
+                from,
+            })
+        }
+        TransportConfig::File { dir } => Box::new(FileTransport { dir, from: config.from }),
+    })
+}
 
 #[derive(Serialize)]
-struct ResendEmail {
+struct ResendEmail<'a> {
+    from: &'a str,
+    to: Vec<&'a str>,
+    subject: &'a str,
+    html: &'a str,
+    text: &'a str,
+}
+
+pub struct ResendTransport {
+    client: Client,
+    api_key: String,
+    from: String,
+}
+
+#[async_trait]
+impl EmailTransport for ResendTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let response = self
+            .client
+            .post("https://api.resend.com/emails")
+            .bearer_auth(&self.api_key)
+            .json(&ResendEmail {
+                from: &self.from,
+                to: vec![&message.to],
+                subject: &message.subject,
+                html: &message.html,
+                text: &message.text,
+            })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(EmailError::Rejected(format!("{} {}", status, body)));
+        }
+        Ok(())
+    }
+}
+
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|e| EmailError::InvalidMessage(format!("{}: {}", message.to, e)))?;
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .multipart(MultiPart::alternative_plain_html(message.text.clone(), message.html.clone()))
+            .map_err(|e| EmailError::InvalidMessage(e.to_string()))?;
+
+        self.mailer.send(email).await.map_err(EmailError::Smtp)?;
+        Ok(())
+    }
+}
+
+/// Development transport: each message is written to `dir` as an `.eml` file
+/// and logged, so links can be opened without a mail provider.
+pub struct FileTransport {
+    dir: PathBuf,
     from: String,
-    to: Vec<String>,
-    subject: String,
-    html: String,
-}
-
-pub async fn send_reset_email(to: &str, token: &str) -> Result<(), reqwest::Error> {
-    let api_key = env::var("RESEND_API_KEY").unwrap_or_else(|_| "".to_string());
-    let client = Client::new();
-    
-    // In production, use a verified domain. For testing with Resend default:
-    let from = "Vexis <onboarding@resend.dev>".to_string();
-    
-    let reset_link = format!("http://localhost:5173/reset-password?token={}", token);
-    
-    let email = ResendEmail {
-        from,
-        to: vec![to.to_string()],
-        subject: "Reset Your Password - Vexis".to_string(),
-        html: format!(
-            "<h1>Password Reset Request</h1>
-             <p>You requested a password reset for your Vexis account.</p>
-             <p>Click the link below to reset your password:</p>
-             <a href='{}'>Reset Password</a>
-             <p>If you didn't request this, please ignore this email.</p>",
-            reset_link
-        ),
-    };
-
-    client
-        .post("https://api.resend.com/emails")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&email)
-        .send()
-        .await?;
-
-    Ok(())
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        tokio::fs::create_dir_all(&self.dir).await.map_err(EmailError::Io)?;
+
+        let path = self
+            .dir
+            .join(format!("{}_{}.eml", Utc::now().format("%Y%m%dT%H%M%S"), Uuid::new_v4()));
+        let boundary = Uuid::new_v4().simple().to_string();
+        let contents = format!(
+            "From: {from}\r\nTo: {to}\r\nSubject: {subject}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: multipart/alternative; boundary=\"{b}\"\r\n\r\n\
+             --{b}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{text}\r\n\
+             --{b}\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{html}\r\n\
+             --{b}--\r\n",
+            from = self.from,
+            to = message.to,
+            subject = message.subject,
+            b = boundary,
+            text = message.text,
+            html = message.html,
+        );
+        tokio::fs::write(&path, contents).await.map_err(EmailError::Io)?;
+
+        println!("email to {} ({}) written to {}", message.to, message.subject, path.display());
+        Ok(())
+    }
+}
+
+/// Renders templates and hands the result to the configured transport.
+pub struct Mailer {
+    transport: Box<dyn EmailTransport>,
+    templates: EmailTemplates,
+}
+
+impl Mailer {
+    pub fn new(transport: Box<dyn EmailTransport>, templates: EmailTemplates) -> Self {
+        Self { transport, templates }
+    }
+
+    /// Sends `template` (see `utils::email_templates`) to `to` in the
+    /// recipient's `locale`.
+    pub async fn send_template<S: Serialize>(
+        &self,
+        to: &str,
+        locale: &str,
+        template: &str,
+        data: S,
+    ) -> Result<(), EmailError> {
+        let rendered = self
+            .templates
+            .render(template, locale, data)
+            .map_err(|e| EmailError::InvalidMessage(e.to_string()))?;
+
+        self.transport
+            .send(&EmailMessage {
+                to: to.to_string(),
+                subject: rendered.subject,
+                html: rendered.html,
+                text: rendered.text,
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(normalize_address("  Budi.Santoso@Example.COM "), "budi.santoso@example.com");
+        assert_eq!(normalize_address("siti@example.com"), "siti@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_file_transport_writes_message() {
+        let dir = std::env::temp_dir().join(format!("vexis-mail-{}", Uuid::new_v4()));
+        let transport = FileTransport {
+            dir: dir.clone(),
+            from: "Vexis <noreply@example.com>".to_string(),
+        };
+
+        let mailer = Mailer::new(Box::new(transport), EmailTemplates::new("http://localhost:5173"));
+        mailer
+            .send_template("budi@example.com", "en", "password_reset", serde_json::json!({ "name": "Budi", "token": "abc123" }))
+            .await
+            .unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let path = entries.next().unwrap().unwrap().path();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("To: budi@example.com"));
+        assert!(contents.contains("Subject: Reset Your Password - Vexis"));
+        assert!(contents.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(contents.contains("reset-password?token=abc123"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_valid_address() {
+        assert!(is_valid_address("budi@example.co.id"));
+        assert!(!is_valid_address("budi@localhost"));
+        assert!(!is_valid_address("@example.com"));
+        assert!(!is_valid_address("budi@@example.com"));
+        assert!(!is_valid_address("budi @example.com"));
+        assert!(!is_valid_address("budi@example."));
+    }
+
+    #[test]
+    fn test_smtp_transport_rejects_invalid_from() {
+        let config = EmailConfig {
+            from: "not an address".to_string(),
+            transport: TransportConfig::Smtp {
+                host: "smtp.example.com".to_string(),
+                port: 587,
+                username: None,
+                password: None,
+                tls: SmtpTls::StartTls,
+            },
+        };
+        assert!(transport_from_config(config).is_err());
+    }
 }
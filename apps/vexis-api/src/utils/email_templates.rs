@@ -0,0 +1,178 @@
+use minijinja::{context, Environment, Value};
+use serde::Serialize;
+
+pub const LOCALES: [&str; 2] = ["id", "en"];
+pub const DEFAULT_LOCALE: &str = "id";
+
+/// Every template exists in each locale as `<name>.txt` (which also sets
+/// `subject`) and `<name>.html` (which extends `layout.html`).
+macro_rules! email_templates {
+    ($($name:literal),* $(,)?) => {
+        &[$(
+            (concat!("id/", $name, ".txt"), include_str!(concat!("../templates/email/id/", $name, ".txt"))),
+            (concat!("id/", $name, ".html"), include_str!(concat!("../templates/email/id/", $name, ".html"))),
+            (concat!("en/", $name, ".txt"), include_str!(concat!("../templates/email/en/", $name, ".txt"))),
+            (concat!("en/", $name, ".html"), include_str!(concat!("../templates/email/en/", $name, ".html"))),
+        )*]
+    };
+}
+
+const TEMPLATES: &[(&str, &str)] = email_templates!(
+    "password_reset",
+    "email_verification",
+    "login_code",
+    "password_changed",
+    "leave_decision",
+    "missed_checkout",
+    "weekly_summary",
+    "invitation",
+);
+
+#[derive(Debug, Clone)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Transactional email templates, rendered with the public URL of the web app
+/// so links work outside development.
+pub struct EmailTemplates {
+    env: Environment<'static>,
+    public_base_url: String,
+}
+
+impl EmailTemplates {
+    pub fn new(public_base_url: &str) -> Self {
+        let mut env = Environment::new();
+        env.add_template("layout.html", include_str!("../templates/email/layout.html"))
+            .expect("email layout should parse");
+        for (name, source) in TEMPLATES {
+            env.add_template(name, source).expect("email template should parse");
+        }
+
+        Self {
+            env,
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Reads `PUBLIC_BASE_URL`, the address users open the web app at.
+    pub fn from_env() -> Self {
+        Self::new(&std::env::var("PUBLIC_BASE_URL").unwrap_or_else(|_| "http://localhost:5173".to_string()))
+    }
+
+    /// Renders `template` in `locale`, falling back to Indonesian for unknown
+    /// locales. `data` supplies the template variables.
+    pub fn render<S: Serialize>(
+        &self,
+        template: &str,
+        locale: &str,
+        data: S,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        let locale = if LOCALES.contains(&locale) { locale } else { DEFAULT_LOCALE };
+        let ctx = context! {
+            base_url => &self.public_base_url,
+            locale => locale,
+            ..Value::from_serialize(data)
+        };
+
+        let text = self
+            .env
+            .get_template(&format!("{}/{}.txt", locale, template))?
+            .render_captured(&ctx)?;
+        let subject = text
+            .state()
+            .lookup("subject")
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+
+        let html = self
+            .env
+            .get_template(&format!("{}/{}.html", locale, template))?
+            .render(context! { subject => &subject, ..ctx })?;
+
+        Ok(RenderedEmail {
+            subject,
+            html,
+            text: text.output().trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_uses_locale_and_base_url() {
+        let templates = EmailTemplates::new("https://absen.example.id/");
+        let data = json!({ "name": "Budi", "token": "abc123" });
+
+        let id = templates.render("password_reset", "id", &data).unwrap();
+        assert_eq!(id.subject, "Atur Ulang Password - Vexis");
+        assert!(id.text.contains("https://absen.example.id/reset-password?token=abc123"));
+        assert!(id.html.contains("<title>Atur Ulang Password - Vexis</title>"));
+        assert!(id.html.contains("Email ini dikirim otomatis"));
+
+        let en = templates.render("password_reset", "en", &data).unwrap();
+        assert_eq!(en.subject, "Reset Your Password - Vexis");
+
+        let fallback = templates.render("password_reset", "fr", &data).unwrap();
+        assert_eq!(fallback.subject, id.subject);
+    }
+
+    #[test]
+    fn test_html_escapes_values_but_text_does_not() {
+        let templates = EmailTemplates::new("http://localhost:5173");
+        let rendered = templates
+            .render(
+                "leave_decision",
+                "en",
+                json!({
+                    "name": "<Sari & Co>",
+                    "approved": false,
+                    "leave_type": "annual leave",
+                    "start_date": "2026-01-05",
+                    "end_date": "2026-01-07",
+                    "note": "Overlaps year-end closing",
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(rendered.subject, "Leave Request Rejected - Vexis");
+        assert!(rendered.html.contains("&lt;Sari &amp; Co&gt;"));
+        assert!(rendered.text.contains("Hi <Sari & Co>,"));
+        assert!(rendered.text.contains("Note: Overlaps year-end closing"));
+    }
+
+    #[test]
+    fn test_all_templates_render_in_all_locales() {
+        let templates = EmailTemplates::new("http://localhost:5173");
+        let data = json!({
+            "name": "Budi",
+            "token": "t",
+            "code": "123456",
+            "approved": true,
+            "leave_type": "cuti",
+            "start_date": "2026-01-05",
+            "end_date": "2026-01-06",
+            "date": "2026-01-05",
+            "check_in": "08:01",
+            "week_start": "2026-01-05",
+            "week_end": "2026-01-11",
+            "days_present": 1,
+            "valid_days": 7,
+            "days": [{ "date": "2026-01-05", "check_in": "08:01", "check_out": null }],
+        });
+
+        for (name, _) in TEMPLATES.iter().filter(|(n, _)| n.ends_with(".txt")) {
+            let (locale, rest) = name.split_once('/').unwrap();
+            let template = rest.trim_end_matches(".txt");
+            let rendered = templates.render(template, locale, &data).unwrap();
+            assert!(!rendered.subject.is_empty(), "{} has no subject", name);
+            assert!(!rendered.text.is_empty());
+        }
+    }
+}
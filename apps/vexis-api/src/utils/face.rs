@@ -1,6 +1,6 @@
 /// Calculates similarity between two sets of 3D landmarks.
 /// landmarks1 and landmarks2 should be flat vectors of 1434 elements (478 * 3).
-pub fn compare_landmarks(landmarks1: &Vec<f32>, landmarks2: &Vec<f32>) -> f32 {
+pub fn compare_landmarks(landmarks1: &[f32], landmarks2: &[f32]) -> f32 {
     if landmarks1.len() != landmarks2.len() || landmarks1.is_empty() {
         return 0.0;
     }
@@ -17,9 +17,7 @@ pub fn compare_landmarks(landmarks1: &Vec<f32>, landmarks2: &Vec<f32>) -> f32 {
     // Convert distance to similarity score (0.0 to 1.0)
     // This is a heuristic, threshold needs to be tuned.
     // MediaPipe landmarks are normalized [0, 1] usually.
-    let similarity = 1.0 / (1.0 + rmse);
-
-    similarity
+    1.0 / (1.0 + rmse)
 }
 
 #[cfg(test)]
@@ -1,4 +1,42 @@
+use crate::models::office::Office;
 use crate::models::user::OfficeLocation;
+use mongodb::bson::oid::ObjectId;
+
+/// Fence for users without an assigned office: center as [long, lat], and
+/// radius in meters.
+pub const DEFAULT_FENCE: ([f64; 2], f64) = ([106.58955212564815, -6.288265750819264], 200.0);
+
+/// Area a check-in must fall inside.
+#[derive(Debug)]
+pub struct Fence {
+    /// `None` for `DEFAULT_FENCE`.
+    pub office_id: Option<ObjectId>,
+    pub center: OfficeLocation,
+    pub radius_m: f64,
+}
+
+/// The fence of the user's assigned office, or `DEFAULT_FENCE` for users
+/// without one.
+pub fn check_in_fence(office: Option<Office>) -> Fence {
+    match office {
+        Some(office) => Fence {
+            office_id: office.id,
+            center: office.location,
+            radius_m: office.radius_m,
+        },
+        None => {
+            let (center, radius_m) = DEFAULT_FENCE;
+            Fence {
+                office_id: None,
+                center: OfficeLocation {
+                    r#type: "Point".to_string(),
+                    coordinates: center.to_vec(),
+                },
+                radius_m,
+            }
+        }
+    }
+}
 
 /// Calculates the Haversine distance between two points on the Earth's surface.
 /// Returns distance in meters.
@@ -19,15 +57,6 @@ pub fn calculate_distance(loc1: &OfficeLocation, loc2: &OfficeLocation) -> f64 {
     earth_radius_m * c
 }
 
-/// Checks if user is within the geofence radius.
-pub fn is_within_geofence(
-    user_loc: &OfficeLocation,
-    office_loc: &OfficeLocation,
-    radius_m: f64,
-) -> bool {
-    calculate_distance(user_loc, office_loc) <= radius_m
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -47,4 +76,30 @@ mod tests {
         let dist = calculate_distance(&monas, &sarinah);
         assert!(dist > 1300.0 && dist < 1400.0); // Rough check
     }
+
+    #[test]
+    fn test_check_in_fence_prefers_the_assigned_office() {
+        let office_id = ObjectId::new();
+        let office = Office {
+            id: Some(office_id),
+            tenant_id: ObjectId::new(),
+            name: "Sarinah".to_string(),
+            location: OfficeLocation {
+                r#type: "Point".to_string(),
+                coordinates: vec![106.823908, -6.187383],
+            },
+            radius_m: 50.0,
+            created_at: chrono::Utc::now(),
+        };
+
+        let fence = check_in_fence(Some(office));
+        assert_eq!(fence.office_id, Some(office_id));
+        assert_eq!(fence.center.coordinates, vec![106.823908, -6.187383]);
+        assert_eq!(fence.radius_m, 50.0);
+
+        let fence = check_in_fence(None);
+        assert_eq!(fence.office_id, None);
+        assert_eq!(fence.center.coordinates, DEFAULT_FENCE.0.to_vec());
+        assert_eq!(fence.radius_m, DEFAULT_FENCE.1);
+    }
 }
@@ -0,0 +1,239 @@
+use crate::models::job::Job;
+use crate::utils::push::{self, PushJob};
+use crate::utils::user_import::{self, UserImportJob};
+use crate::utils::webhook::{self, WebhookJob};
+use crate::AppState;
+use chrono::{Duration, Utc};
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use mongodb::options::ReturnDocument;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub const JOB_KIND_EMAIL: &str = "email";
+pub const JOB_KIND_PUSH: &str = "push";
+pub const JOB_KIND_WEBHOOK: &str = "webhook";
+pub const JOB_KIND_USER_IMPORT: &str = "user_import";
+
+const DEFAULT_MAX_ATTEMPTS: i32 = 8;
+/// How long a worker owns a job before it is considered crashed and the job is
+/// handed to another worker. Handlers must finish well within this.
+const LEASE_SECONDS: i64 = 300;
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+/// Finished jobs are kept this long for inspection.
+const DONE_RETENTION_DAYS: i64 = 7;
+
+/// Payload of an `email` job, rendered by `Mailer::send_template` when run.
+/// `data` is dropped once the email has been sent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailJob {
+    pub to: String,
+    pub locale: String,
+    pub template: String,
+    pub data: serde_json::Value,
+}
+
+fn jobs(db: &Database) -> Collection<Job> {
+    db.collection::<Job>("jobs")
+}
+
+pub async fn enqueue<P: Serialize>(
+    db: &Database,
+    kind: &str,
+    payload: &P,
+) -> Result<ObjectId, mongodb::error::Error> {
+    let now = Utc::now();
+    let job = Job {
+        id: None,
+        kind: kind.to_string(),
+        payload: bson::to_document(payload)?,
+        status: "pending".to_string(),
+        attempts: 0,
+        max_attempts: DEFAULT_MAX_ATTEMPTS,
+        run_at: now,
+        locked_until: None,
+        last_error: None,
+        created_at: now,
+        expires_at: None,
+    };
+
+    let result = jobs(db).insert_one(job).await?;
+    Ok(result.inserted_id.as_object_id().unwrap())
+}
+
+/// Queues a templated email instead of sending it inline, so a provider outage
+/// does not fail the request.
+pub async fn enqueue_email(
+    db: &Database,
+    to: &str,
+    locale: &str,
+    template: &str,
+    data: serde_json::Value,
+) -> Result<ObjectId, mongodb::error::Error> {
+    enqueue(
+        db,
+        JOB_KIND_EMAIL,
+        &EmailJob {
+            to: to.to_string(),
+            locale: locale.to_string(),
+            template: template.to_string(),
+            data,
+        },
+    )
+    .await
+}
+
+/// Delay before retry number `attempts` (1-based): 30s doubling up to an hour.
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 10) as u32;
+    Duration::seconds((30 * 2i64.pow(exponent)).min(3600))
+}
+
+/// Starts `count` workers that poll the `jobs` collection for the lifetime of
+/// the process.
+pub fn spawn_workers(state: Arc<AppState>, count: usize) {
+    for _ in 0..count {
+        let state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                match claim_next(&state.db).await {
+                    Ok(Some(job)) => finish(&state, job).await,
+                    Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                    Err(e) => {
+                        eprintln!("Job queue error: {}", e);
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Atomically takes the next due job. A `running` job whose lease ran out
+/// belonged to a worker that died, so it is picked up again (at-least-once).
+async fn claim_next(db: &Database) -> Result<Option<Job>, mongodb::error::Error> {
+    let now = DateTime::now();
+    let lease = Utc::now() + Duration::seconds(LEASE_SECONDS);
+
+    jobs(db)
+        .find_one_and_update(
+            doc! {
+                "$or": [
+                    { "status": "pending", "run_at": { "$lte": now } },
+                    { "status": "running", "locked_until": { "$lt": now } },
+                ]
+            },
+            doc! {
+                "$set": { "status": "running", "locked_until": DateTime::from_chrono(lease) },
+                "$inc": { "attempts": 1 },
+            },
+        )
+        .sort(doc! { "run_at": 1 })
+        .return_document(ReturnDocument::After)
+        .await
+}
+
+async fn finish(state: &AppState, job: Job) {
+    let id = job.id.unwrap();
+    let update = match run(state, &job).await {
+        Ok(()) => doc! {
+            "$set": {
+                "status": "done",
+                "last_error": null,
+                "expires_at": DateTime::from_chrono(Utc::now() + Duration::days(DONE_RETENTION_DAYS)),
+            },
+            // Template data can carry one-time tokens; keep only the envelope
+            "$unset": { "locked_until": "", "payload.data": "" },
+        },
+        Err(error) if job.attempts >= job.max_attempts => {
+            eprintln!("Job {} ({}) failed permanently: {}", id, job.kind, error);
+            doc! {
+                "$set": { "status": "dead", "last_error": error },
+                "$unset": { "locked_until": "" },
+            }
+        }
+        Err(error) => doc! {
+            "$set": {
+                "status": "pending",
+                "last_error": error,
+                "run_at": DateTime::from_chrono(Utc::now() + backoff(job.attempts)),
+            },
+            "$unset": { "locked_until": "" },
+        },
+    };
+
+    // If the lock expired and another worker claimed the job meanwhile, that
+    // run owns the result now
+    let claimed = doc! { "_id": id, "attempts": job.attempts, "status": "running" };
+    if let Err(e) = jobs(&state.db).update_one(claimed, update).await {
+        eprintln!("Failed to record result of job {}: {}", id, e);
+    }
+}
+
+async fn run(state: &AppState, job: &Job) -> Result<(), String> {
+    match job.kind.as_str() {
+        JOB_KIND_EMAIL => {
+            let email: EmailJob = bson::from_document(job.payload.clone()).map_err(|e| e.to_string())?;
+            state
+                .email
+                .send_template(&email.to, &email.locale, &email.template, &email.data)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        JOB_KIND_PUSH => {
+            let push: PushJob = bson::from_document(job.payload.clone()).map_err(|e| e.to_string())?;
+            push::deliver(state, push).await
+        }
+        JOB_KIND_WEBHOOK => {
+            let job: WebhookJob = bson::from_document(job.payload.clone()).map_err(|e| e.to_string())?;
+            webhook::deliver(state, job).await
+        }
+        JOB_KIND_USER_IMPORT => {
+            let job: UserImportJob = bson::from_document(job.payload.clone()).map_err(|e| e.to_string())?;
+            user_import::run(state, job).await
+        }
+        other => Err(format!("unknown job kind {}", other)),
+    }
+}
+
+/// Puts a dead (or stuck) job back in the queue with a fresh attempt budget.
+pub async fn retry(db: &Database, id: ObjectId) -> Result<bool, mongodb::error::Error> {
+    let result = jobs(db)
+        .update_one(
+            doc! { "_id": id, "status": "dead" },
+            doc! {
+                "$set": { "status": "pending", "attempts": 0, "run_at": DateTime::now() },
+                "$unset": { "locked_until": "" },
+            },
+        )
+        .await?;
+    Ok(result.matched_count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff(1), Duration::seconds(30));
+        assert_eq!(backoff(2), Duration::seconds(60));
+        assert_eq!(backoff(4), Duration::seconds(240));
+        assert_eq!(backoff(8), Duration::seconds(3600));
+        assert_eq!(backoff(50), Duration::seconds(3600));
+    }
+
+    #[test]
+    fn test_email_job_roundtrips_through_bson() {
+        let job = EmailJob {
+            to: "budi@example.com".to_string(),
+            locale: "id".to_string(),
+            template: "password_reset".to_string(),
+            data: serde_json::json!({ "name": "Budi", "token": "abc", "days": [{ "check_out": null }] }),
+        };
+        let doc = bson::to_document(&job).unwrap();
+        let back: EmailJob = bson::from_document(doc).unwrap();
+        assert_eq!(back.to, job.to);
+        assert_eq!(back.data, job.data);
+    }
+}
@@ -1,45 +1,237 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, encode, errors::ErrorKind, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::env;
+use uuid::Uuid;
+use crate::config::jwt::JwtConfig;
+use crate::models::user::User;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String, // User ID
     pub role: String,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
     pub exp: usize,
+    pub jti: String,
+    /// Tokens issued before tenants existed have none and are rejected.
+    #[serde(default)]
+    pub tenant_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub office_id: Option<String>,
+    #[serde(default)]
+    pub ver: i32, // User token_version at issue time
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenError {
+    Expired,
+    Invalid,
 }
 
 pub fn create_access_token(
-    user_id: &str,
-    role: &str,
+    keys: &JwtConfig,
+    user: &User,
 ) -> Result<String, jsonwebtoken::errors::Error> {
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string());
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::minutes(15))
-        .expect("valid timestamp")
-        .timestamp();
+    let now = Utc::now().timestamp() as usize;
+    let lifetime = keys.lifetimes.access_for(&user.role).as_secs() as usize;
 
     let claims = Claims {
-        sub: user_id.to_owned(),
-        role: role.to_owned(),
-        exp: expiration as usize,
+        sub: user.id.expect("persisted user").to_hex(),
+        role: user.role.clone(),
+        iss: keys.issuer.clone(),
+        aud: keys.audience.clone(),
+        iat: now,
+        exp: now + lifetime,
+        jti: Uuid::new_v4().to_string(),
+        tenant_id: user.tenant_id.to_hex(),
+        office_id: user.office_id.map(|id| id.to_hex()),
+        ver: user.token_version,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.signing_kid.clone());
+
+    encode(&header, &claims, keys.encoding_key())
+}
+
+pub fn decode_jwt(keys: &JwtConfig, token: &str) -> Result<Claims, TokenError> {
+    // Pick the verification key by `kid` so tokens signed before a rotation stay valid
+    let header = decode_header(token).map_err(|_| TokenError::Invalid)?;
+    let key = header
+        .kid
+        .as_deref()
+        .and_then(|kid| keys.decoding_key(kid))
+        .ok_or(TokenError::Invalid)?;
+
+    let mut validation = Validation::new(keys.algorithm);
+    validation.set_issuer(&[&keys.issuer]);
+    validation.set_audience(&[&keys.audience]);
+    validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+    validation.leeway = keys.leeway.as_secs();
+
+    match decode::<Claims>(token, key, &validation) {
+        Ok(token_data) => Ok(token_data.claims),
+        Err(e) if *e.kind() == ErrorKind::ExpiredSignature => Err(TokenError::Expired),
+        Err(_) => Err(TokenError::Invalid),
+    }
 }
 
-pub fn decode_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string());
-    let validation = Validation::new(Algorithm::HS256);
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
-    )?;
-    Ok(token_data.claims)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::jwt::test_keys::{NEW_PRIVATE, NEW_PUBLIC, OLD_PRIVATE, OLD_PUBLIC};
+    use crate::config::jwt::JwtConfigError;
+    use crate::models::user::OfficeLocation;
+    use jsonwebtoken::Algorithm;
+    use mongodb::bson::oid::ObjectId;
+
+    fn keys(kid: &str, private: &str, public: &[(&str, &str)]) -> JwtConfig {
+        let public: Vec<(String, Vec<u8>)> = public
+            .iter()
+            .map(|(kid, pem)| (kid.to_string(), pem.as_bytes().to_vec()))
+            .collect();
+        JwtConfig::from_pem(Algorithm::EdDSA, kid, private.as_bytes(), &public).unwrap()
+    }
+
+    fn user(role: &str) -> User {
+        User {
+            id: Some(ObjectId::new()),
+            tenant_id: ObjectId::new(),
+            name: "Budi".to_string(),
+            email: "budi@example.com".to_string(),
+            email_verified: true,
+            pending_email: None,
+            identifier: "1987".to_string(),
+            password_hash: String::new(),
+            password_history: vec![],
+            role: role.to_string(),
+            office_location: OfficeLocation {
+                r#type: "Point".to_string(),
+                coordinates: vec![106.8, -6.2],
+            },
+            face_landmarks: vec![],
+            face_enrolled: false,
+            photo_url: None,
+            token_version: 3,
+            office_id: Some(ObjectId::new()),
+            department_id: None,
+            team_id: None,
+            oidc_subject: None,
+            ldap_dn: None,
+            locale: "id".to_string(),
+            must_change_password: false,
+            deactivated: false,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    fn sign(keys: &JwtConfig, claims: &Claims) -> String {
+        let mut header = Header::new(keys.algorithm);
+        header.kid = Some(keys.signing_kid.clone());
+        encode(&header, claims, keys.encoding_key()).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_with_kid() {
+        let keys = keys("old", OLD_PRIVATE, &[("old", OLD_PUBLIC)]);
+        let user = user("admin");
+        let token = create_access_token(&keys, &user).unwrap();
+
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("old"));
+        let claims = decode_jwt(&keys, &token).unwrap();
+        assert_eq!(claims.sub, user.id.unwrap().to_hex());
+        assert_eq!(claims.ver, 3);
+        assert_eq!(claims.iss, "vexis");
+        assert_eq!(claims.office_id, user.office_id.map(|id| id.to_hex()));
+        assert_eq!(claims.exp - claims.iat, 15 * 60);
+        assert!(!claims.jti.is_empty());
+    }
+
+    #[test]
+    fn test_lifetime_per_role() {
+        let mut keys = keys("old", OLD_PRIVATE, &[("old", OLD_PUBLIC)]);
+        keys.lifetimes
+            .access_by_role
+            .insert("admin".to_string(), std::time::Duration::from_secs(300));
+
+        let admin = decode_jwt(&keys, &create_access_token(&keys, &user("admin")).unwrap()).unwrap();
+        let staff = decode_jwt(&keys, &create_access_token(&keys, &user("user")).unwrap()).unwrap();
+        assert_eq!(admin.exp - admin.iat, 300);
+        assert_eq!(staff.exp - staff.iat, 15 * 60);
+    }
+
+    #[test]
+    fn test_expired_and_invalid_are_distinct() {
+        let keys = keys("old", OLD_PRIVATE, &[("old", OLD_PUBLIC)]);
+        let token = create_access_token(&keys, &user("user")).unwrap();
+        let mut claims = decode_jwt(&keys, &token).unwrap();
+
+        // Within the leeway the token is still accepted
+        claims.exp = Utc::now().timestamp() as usize - 10;
+        assert!(decode_jwt(&keys, &sign(&keys, &claims)).is_ok());
+
+        claims.exp = Utc::now().timestamp() as usize - 120;
+        assert_eq!(decode_jwt(&keys, &sign(&keys, &claims)).unwrap_err(), TokenError::Expired);
+
+        claims.exp = Utc::now().timestamp() as usize + 60;
+        claims.aud = "another-service".to_string();
+        assert_eq!(decode_jwt(&keys, &sign(&keys, &claims)).unwrap_err(), TokenError::Invalid);
+
+        assert_eq!(decode_jwt(&keys, "not-a-token").unwrap_err(), TokenError::Invalid);
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_tokens_valid() {
+        let before = keys("old", OLD_PRIVATE, &[("old", OLD_PUBLIC)]);
+        let token = create_access_token(&before, &user("user")).unwrap();
+
+        let rotated = keys("new", NEW_PRIVATE, &[("new", NEW_PUBLIC), ("old", OLD_PUBLIC)]);
+        assert!(decode_jwt(&rotated, &token).is_ok());
+
+        let retired = keys("new", NEW_PRIVATE, &[("new", NEW_PUBLIC)]);
+        assert!(decode_jwt(&retired, &token).is_err());
+    }
+
+    #[test]
+    fn test_jwks_publishes_verification_keys() {
+        let keys = keys("new", NEW_PRIVATE, &[("new", NEW_PUBLIC), ("old", OLD_PUBLIC)]);
+        let jwks = serde_json::to_value(keys.jwks()).unwrap();
+
+        assert_eq!(jwks["keys"].as_array().unwrap().len(), 2);
+        assert_eq!(jwks["keys"][0]["kid"], "new");
+        assert_eq!(jwks["keys"][0]["kty"], "OKP");
+        assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+        assert!(jwks["keys"][0].get("d").is_none());
+    }
+
+    #[test]
+    fn test_signing_key_must_be_published() {
+        let public = vec![("old".to_string(), OLD_PUBLIC.as_bytes().to_vec())];
+        let result = JwtConfig::from_pem(Algorithm::EdDSA, "new", NEW_PRIVATE.as_bytes(), &public);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_signing_key_must_match_its_public_key() {
+        let public = vec![("new".to_string(), OLD_PUBLIC.as_bytes().to_vec())];
+        let result = JwtConfig::from_pem(Algorithm::EdDSA, "new", NEW_PRIVATE.as_bytes(), &public);
+        assert!(matches!(result, Err(JwtConfigError::SigningKeyMismatch(_))));
+    }
+
+    #[test]
+    fn test_public_key_must_be_ed25519() {
+        // Same length as an Ed25519 key, but the OID is X25519 (1.3.101.110)
+        let x25519 = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VuAyEAI/RLyUONpxcmZE1KaR2aLOpjsPJf1PRdFH1pZxAjZ4o=
+-----END PUBLIC KEY-----
+";
+        let public = vec![
+            ("new".to_string(), NEW_PUBLIC.as_bytes().to_vec()),
+            ("x".to_string(), x25519.as_bytes().to_vec()),
+        ];
+        let result = JwtConfig::from_pem(Algorithm::EdDSA, "new", NEW_PRIVATE.as_bytes(), &public);
+        assert!(matches!(result, Err(JwtConfigError::InvalidKey { .. })));
+    }
 }
@@ -0,0 +1,321 @@
+use crate::config::ldap::LdapConfig;
+use crate::models::user::{OfficeLocation, User};
+use crate::utils::auth_provider::{map_groups_to_role, AuthProvider, AuthProviderError};
+use crate::utils::email::normalize_address;
+use crate::utils::email_templates::DEFAULT_LOCALE;
+use async_trait::async_trait;
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Database;
+use std::collections::HashMap;
+
+/// LDAP result code for a failed bind with a wrong password.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// A user as found in the directory, mapped to Vexis fields.
+#[derive(Debug, PartialEq)]
+pub struct LdapIdentity {
+    pub dn: String,
+    pub name: String,
+    pub email: String,
+    pub identifier: String,
+    pub role: String,
+}
+
+/// Authenticates by binding to the directory as the user, then syncs the
+/// directory attributes and group-derived role into the local `users` record.
+pub struct LdapProvider {
+    config: LdapConfig,
+}
+
+impl From<LdapError> for AuthProviderError {
+    fn from(e: LdapError) -> Self {
+        AuthProviderError::Directory(e.to_string())
+    }
+}
+
+impl LdapProvider {
+    pub fn new(config: LdapConfig) -> Self {
+        Self { config }
+    }
+
+    /// Looks the login up with the service account and verifies the password
+    /// with a bind as the found DN.
+    pub async fn lookup(
+        &self,
+        login: &str,
+        password: &str,
+    ) -> Result<Option<LdapIdentity>, AuthProviderError> {
+        // An empty password would be an unauthenticated bind, which always succeeds
+        if password.is_empty() {
+            return Ok(None);
+        }
+
+        let settings = LdapConnSettings::new().set_starttls(self.config.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+
+        ldap.simple_bind(&self.config.bind_dn, &self.config.bind_password)
+            .await?
+            .success()?;
+
+        let filter = self.config.user_filter.replace("{login}", &ldap_escape(login));
+        let attrs = [
+            self.config.name_attr.as_str(),
+            self.config.email_attr.as_str(),
+            self.config.identifier_attr.as_str(),
+            self.config.group_attr.as_str(),
+        ];
+        let (entries, _) = ldap
+            .search(&self.config.base_dn, Scope::Subtree, &filter, attrs)
+            .await?
+            .success()?;
+
+        // Zero or ambiguous matches are treated as unknown users
+        let [entry] = entries.as_slice() else {
+            let _ = ldap.unbind().await;
+            return Ok(None);
+        };
+        let entry = SearchEntry::construct(entry.clone());
+
+        let bind = ldap.simple_bind(&entry.dn, password).await?;
+        let _ = ldap.unbind().await;
+        if bind.rc == INVALID_CREDENTIALS {
+            return Ok(None);
+        }
+        bind.success()?;
+
+        Ok(self.to_identity(&entry.dn, &entry.attrs))
+    }
+
+    /// Maps directory attributes to Vexis fields. Entries without an email
+    /// cannot be provisioned.
+    fn to_identity(&self, dn: &str, attrs: &HashMap<String, Vec<String>>) -> Option<LdapIdentity> {
+        let first = |attr: &str| attrs.get(attr).and_then(|values| values.first()).cloned();
+
+        let email = normalize_address(&first(&self.config.email_attr)?);
+        let groups = attrs.get(&self.config.group_attr).cloned().unwrap_or_default();
+        let group_names: Vec<String> = groups
+            .iter()
+            .flat_map(|g| [g.to_lowercase(), group_cn(g).to_lowercase()])
+            .collect();
+
+        Some(LdapIdentity {
+            dn: dn.to_string(),
+            name: first(&self.config.name_attr).unwrap_or_else(|| email.clone()),
+            identifier: first(&self.config.identifier_attr).unwrap_or_else(|| email.clone()),
+            email,
+            role: map_groups_to_role(
+                group_names.iter().map(String::as_str),
+                &self.config.role_mapping,
+                &self.config.default_role,
+            ),
+        })
+    }
+}
+
+/// `cn=it-admins,ou=groups,dc=example,dc=org` -> `it-admins`
+fn group_cn(dn: &str) -> &str {
+    dn.split(',')
+        .next()
+        .and_then(|rdn| rdn.split_once('='))
+        .filter(|(attr, _)| attr.trim().eq_ignore_ascii_case("cn"))
+        .map(|(_, value)| value.trim())
+        .unwrap_or(dn)
+}
+
+#[async_trait]
+impl AuthProvider for LdapProvider {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    async fn authenticate(
+        &self,
+        db: &Database,
+        tenant_id: ObjectId,
+        login: &str,
+        password: &str,
+    ) -> Result<Option<User>, AuthProviderError> {
+        let Some(identity) = self.lookup(login, password).await? else {
+            return Ok(None);
+        };
+        let users_col = db.collection::<User>("users");
+
+        let linked = users_col
+            .find_one(doc! { "tenant_id": tenant_id, "ldap_dn": &identity.dn })
+            .await?;
+        let existing = match linked {
+            Some(user) => Some(user),
+            None => {
+                users_col
+                    .find_one(doc! {
+                        "tenant_id": tenant_id,
+                        "$or": [
+                            { "email": &identity.email },
+                            { "identifier": &identity.identifier }
+                        ]
+                    })
+                    .await?
+            }
+        };
+
+        if let Some(user) = existing {
+            if user.ldap_dn.as_deref().is_some_and(|dn| dn != identity.dn) {
+                return Err(AuthProviderError::Conflict("account is linked to another directory entry"));
+            }
+            let taken = users_col
+                .find_one(doc! {
+                    "tenant_id": tenant_id,
+                    "_id": { "$ne": user.id },
+                    "$or": [
+                        { "email": &identity.email },
+                        { "identifier": &identity.identifier }
+                    ]
+                })
+                .await?;
+            if taken.is_some() {
+                return Err(AuthProviderError::Conflict("directory email or identifier belongs to another account"));
+            }
+
+            let mut synced = doc! {
+                "ldap_dn": &identity.dn,
+                "name": &identity.name,
+                "email": &identity.email,
+                "identifier": &identity.identifier,
+            };
+            // The role of a local account is left alone when it is first
+            // linked; after that the directory groups own it. Directory groups
+            // never grant or take away super admin.
+            if user.ldap_dn.is_some() && user.role != "super_admin" {
+                synced.insert("role", &identity.role);
+            }
+            let updated = users_col
+                .find_one_and_update(doc! { "_id": user.id }, doc! { "$set": synced })
+                .return_document(mongodb::options::ReturnDocument::After)
+                .await?;
+            return Ok(updated);
+        }
+
+        let mut new_user = User {
+            id: None,
+            tenant_id,
+            name: identity.name,
+            email: identity.email,
+            email_verified: true,
+            pending_email: None,
+            identifier: identity.identifier,
+            // Directory accounts have no local password; an empty hash never verifies
+            password_hash: String::new(),
+            password_history: vec![],
+            role: identity.role,
+            office_location: OfficeLocation {
+                r#type: "Point".to_string(),
+                coordinates: vec![0.0, 0.0],
+            },
+            face_landmarks: vec![],
+            face_enrolled: false,
+            photo_url: None,
+            token_version: 0,
+            office_id: None,
+            department_id: None,
+            team_id: None,
+            oidc_subject: None,
+            ldap_dn: Some(identity.dn),
+            locale: DEFAULT_LOCALE.to_string(),
+            must_change_password: false,
+            deactivated: false,
+            deleted_at: None,
+            deleted_by: None,
+        };
+        let result = users_col.insert_one(&new_user).await?;
+        new_user.id = result.inserted_id.as_object_id();
+        Ok(Some(new_user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LdapConfig {
+        LdapConfig {
+            url: std::env::var("LDAP_TEST_URL").unwrap_or_else(|_| "ldap://localhost:389".to_string()),
+            starttls: false,
+            bind_dn: "cn=admin,dc=example,dc=org".to_string(),
+            bind_password: "admin".to_string(),
+            base_dn: "dc=example,dc=org".to_string(),
+            user_filter: "(|(uid={login})(mail={login}))".to_string(),
+            name_attr: "cn".to_string(),
+            email_attr: "mail".to_string(),
+            identifier_attr: "employeeNumber".to_string(),
+            group_attr: "memberOf".to_string(),
+            role_mapping: HashMap::from([("it-admins".to_string(), "admin".to_string())]),
+            default_role: "user".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_group_cn() {
+        assert_eq!(group_cn("cn=IT-Admins,ou=groups,dc=example,dc=org"), "IT-Admins");
+        assert_eq!(group_cn("ou=groups,dc=example,dc=org"), "ou=groups,dc=example,dc=org");
+    }
+
+    #[test]
+    fn test_to_identity_maps_attributes_and_groups() {
+        let provider = LdapProvider::new(config());
+        let attrs = HashMap::from([
+            ("cn".to_string(), vec!["Dewi Lestari".to_string()]),
+            ("mail".to_string(), vec!["dewi@example.org".to_string()]),
+            ("employeeNumber".to_string(), vec!["198805".to_string()]),
+            (
+                "memberOf".to_string(),
+                vec!["cn=IT-Admins,ou=groups,dc=example,dc=org".to_string()],
+            ),
+        ]);
+
+        let identity = provider.to_identity("uid=dewi,dc=example,dc=org", &attrs).unwrap();
+        assert_eq!(identity.name, "Dewi Lestari");
+        assert_eq!(identity.identifier, "198805");
+        assert_eq!(identity.role, "admin");
+
+        let without_email = HashMap::from([("cn".to_string(), vec!["X".to_string()])]);
+        assert!(provider.to_identity("uid=x", &without_email).is_none());
+    }
+
+    /// Needs a local OpenLDAP, see "Autentikasi LDAP" in the README.
+    #[tokio::test]
+    #[ignore]
+    async fn test_bind_against_openldap() {
+        let (conn, mut ldap) = LdapConnAsync::new(&config().url).await.unwrap();
+        ldap3::drive!(conn);
+        ldap.simple_bind("cn=admin,dc=example,dc=org", "admin").await.unwrap().success().unwrap();
+        let _ = ldap.delete("uid=tono,dc=example,dc=org").await;
+        ldap.add(
+            "uid=tono,dc=example,dc=org",
+            vec![
+                ("objectClass", ["inetOrgPerson"].into()),
+                ("uid", ["tono"].into()),
+                ("cn", ["Tono Sutono"].into()),
+                ("sn", ["Sutono"].into()),
+                ("mail", ["tono@example.org"].into()),
+                ("employeeNumber", ["199001"].into()),
+                ("userPassword", ["rahasia"].into()),
+            ],
+        )
+        .await
+        .unwrap()
+        .success()
+        .unwrap();
+
+        let provider = LdapProvider::new(config());
+        let identity = provider.lookup("tono", "rahasia").await.unwrap().unwrap();
+        assert_eq!(identity.email, "tono@example.org");
+        assert_eq!(identity.identifier, "199001");
+        assert_eq!(identity.role, "user");
+
+        assert!(provider.lookup("tono", "salah").await.unwrap().is_none());
+        assert!(provider.lookup("tono", "").await.unwrap().is_none());
+        assert!(provider.lookup("nobody", "rahasia").await.unwrap().is_none());
+    }
+}
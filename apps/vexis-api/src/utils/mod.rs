@@ -2,3 +2,20 @@ pub mod jwt;
 pub mod email;
 pub mod geofence;
 pub mod face;
+pub mod token;
+pub mod revocation;
+pub mod oidc;
+pub mod auth_provider;
+pub mod ldap;
+pub mod password;
+pub mod rate_limit;
+pub mod email_templates;
+pub mod jobs;
+pub mod push;
+pub mod reminders;
+pub mod webhook;
+pub mod retention;
+pub mod audit;
+pub mod user_import;
+pub mod org;
+pub mod presence;
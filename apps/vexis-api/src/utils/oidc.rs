@@ -0,0 +1,413 @@
+use crate::config::oidc::OidcConfig;
+use crate::utils::auth_provider::map_groups_to_role;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Discovery and JWKS are cached this long before being fetched again.
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub enum OidcError {
+    Http(reqwest::Error),
+    Provider(String),
+    InvalidIdToken(String),
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OidcError::Http(e) => write!(f, "request to identity provider failed: {}", e),
+            OidcError::Provider(msg) => write!(f, "identity provider error: {}", msg),
+            OidcError::InvalidIdToken(msg) => write!(f, "invalid ID token: {}", msg),
+        }
+    }
+}
+
+impl From<reqwest::Error> for OidcError {
+    fn from(e: reqwest::Error) -> Self {
+        OidcError::Http(e)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+struct ProviderCache {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Authorization-code-with-PKCE client for the configured identity provider.
+pub struct OidcClient {
+    pub config: OidcConfig,
+    http: Client,
+    cache: RwLock<Option<ProviderCache>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig, http: Client) -> Self {
+        Self {
+            config,
+            http,
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Provider metadata from `/.well-known/openid-configuration`.
+    pub async fn metadata(&self) -> Result<ProviderMetadata, OidcError> {
+        if let Some(cache) = self.cache.read().await.as_ref() {
+            if cache.fetched_at.elapsed() < METADATA_TTL {
+                return Ok(cache.metadata.clone());
+            }
+        }
+        Ok(self.refresh().await?.0)
+    }
+
+    async fn refresh(&self) -> Result<(ProviderMetadata, JwkSet), OidcError> {
+        let discovery_url = format!("{}/.well-known/openid-configuration", self.config.issuer_url);
+        let metadata: ProviderMetadata = self
+            .http
+            .get(discovery_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer_url {
+            return Err(OidcError::Provider(format!(
+                "discovery issuer {} does not match {}",
+                metadata.issuer, self.config.issuer_url
+            )));
+        }
+
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *self.cache.write().await = Some(ProviderCache {
+            metadata: metadata.clone(),
+            jwks: jwks.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok((metadata, jwks))
+    }
+
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", pkce_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Provider(e.to_string()))?;
+
+        Ok(url.to_string())
+    }
+
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<TokenResponse, OidcError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let response = self.http.post(&metadata.token_endpoint).form(&form).send().await?;
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::Provider(format!("token endpoint rejected the code: {}", body)));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Verifies signature, issuer, audience, expiry and nonce of an ID token.
+    pub async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(id_token).map_err(|e| OidcError::InvalidIdToken(e.to_string()))?;
+        if !matches!(
+            header.alg,
+            Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::ES256
+                | Algorithm::ES384
+                | Algorithm::EdDSA
+        ) {
+            return Err(OidcError::InvalidIdToken(format!("algorithm {:?} not allowed", header.alg)));
+        }
+        let kid = header
+            .kid
+            .ok_or_else(|| OidcError::InvalidIdToken("missing kid".to_string()))?;
+
+        let (metadata, key) = match self.cached_key(&kid).await {
+            Some(found) => found,
+            None => {
+                // The provider may have rotated its keys since the last fetch
+                let (metadata, jwks) = self.refresh().await?;
+                let jwk = jwks
+                    .find(&kid)
+                    .ok_or_else(|| OidcError::InvalidIdToken(format!("unknown kid {}", kid)))?;
+                let key = DecodingKey::from_jwk(jwk)
+                    .map_err(|e| OidcError::InvalidIdToken(e.to_string()))?;
+                (metadata, key)
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.leeway = 60;
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| OidcError::InvalidIdToken(e.to_string()))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("nonce mismatch".to_string()));
+        }
+        Ok(claims)
+    }
+
+    async fn cached_key(&self, kid: &str) -> Option<(ProviderMetadata, DecodingKey)> {
+        let cache = self.cache.read().await;
+        let cache = cache.as_ref().filter(|c| c.fetched_at.elapsed() < METADATA_TTL)?;
+        let key = DecodingKey::from_jwk(cache.jwks.find(kid)?).ok()?;
+        Some((cache.metadata.clone(), key))
+    }
+
+    /// The configured identifier claim (e.g. `preferred_username`), if present.
+    pub fn identifier(&self, claims: &IdTokenClaims) -> Option<String> {
+        claims
+            .extra
+            .get(&self.config.identifier_claim)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    }
+
+    /// Maps the IdP groups of a user to a Vexis role.
+    pub fn map_role(&self, claims: &IdTokenClaims) -> String {
+        let groups = claims
+            .extra
+            .get(&self.config.groups_claim)
+            .and_then(|v| v.as_array())
+            .map(|groups| groups.iter().filter_map(|g| g.as_str()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        // Keycloak reports group paths ("/staff-admins") unless configured otherwise
+        map_groups_to_role(
+            groups.iter().flat_map(|g| [*g, g.trim_start_matches('/')]),
+            &self.config.role_mapping,
+            &self.config.default_role,
+        )
+    }
+}
+
+/// PKCE S256 code challenge (RFC 7636).
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::jwt::{test_keys, JwtConfig};
+    use axum::{extract::State, routing::{get, post}, Form, Json, Router};
+    use jsonwebtoken::{encode, Header};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pkce_challenge_rfc7636_vector() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    struct MockIdp {
+        issuer: String,
+        keys: JwtConfig,
+        nonce: String,
+    }
+
+    /// Serves discovery, JWKS and a token endpoint that answers any code with
+    /// an ID token for the `staff-admins` group.
+    async fn start_mock_idp(nonce: &str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let public = vec![("idp-key".to_string(), test_keys::OLD_PUBLIC.as_bytes().to_vec())];
+        let keys = JwtConfig::from_pem(
+            Algorithm::EdDSA,
+            "idp-key",
+            test_keys::OLD_PRIVATE.as_bytes(),
+            &public,
+        )
+        .unwrap();
+        let idp = Arc::new(MockIdp {
+            issuer: issuer.clone(),
+            keys,
+            nonce: nonce.to_string(),
+        });
+
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(|State(idp): State<Arc<MockIdp>>| async move {
+                    Json(serde_json::json!({
+                        "issuer": idp.issuer,
+                        "authorization_endpoint": format!("{}/auth", idp.issuer),
+                        "token_endpoint": format!("{}/token", idp.issuer),
+                        "jwks_uri": format!("{}/jwks", idp.issuer),
+                    }))
+                }),
+            )
+            .route(
+                "/jwks",
+                get(|State(idp): State<Arc<MockIdp>>| async move { Json(idp.keys.jwks().clone()) }),
+            )
+            .route(
+                "/token",
+                post(
+                    |State(idp): State<Arc<MockIdp>>,
+                     Form(form): Form<HashMap<String, String>>| async move {
+                        assert_eq!(form["grant_type"], "authorization_code");
+                        assert!(form.contains_key("code_verifier"));
+                        let now = chrono::Utc::now().timestamp();
+                        let claims = serde_json::json!({
+                            "iss": idp.issuer,
+                            "aud": "vexis",
+                            "sub": "kc-123",
+                            "iat": now,
+                            "exp": now + 300,
+                            "nonce": idp.nonce,
+                            "email": "siti@example.com",
+                            "email_verified": true,
+                            "name": "Siti",
+                            "preferred_username": "198701",
+                            "groups": ["/staff", "/staff-admins"],
+                        });
+                        let mut header = Header::new(Algorithm::EdDSA);
+                        header.kid = Some("idp-key".to_string());
+                        let id_token = encode(&header, &claims, idp.keys.encoding_key()).unwrap();
+                        Json(serde_json::json!({ "id_token": id_token, "access_token": "x" }))
+                    },
+                ),
+            )
+            .with_state(idp);
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    fn client(issuer: &str) -> OidcClient {
+        OidcClient::new(
+            OidcConfig {
+                issuer_url: issuer.to_string(),
+                client_id: "vexis".to_string(),
+                client_secret: None,
+                redirect_uri: "http://localhost:5173/sso/callback".to_string(),
+                scopes: "openid email profile".to_string(),
+                identifier_claim: "preferred_username".to_string(),
+                groups_claim: "groups".to_string(),
+                role_mapping: HashMap::from([("staff-admins".to_string(), "admin".to_string())]),
+                default_role: "user".to_string(),
+                role_sync: false,
+            },
+            Client::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_login_against_mock_idp() {
+        let issuer = start_mock_idp("nonce-1").await;
+        let client = client(&issuer);
+
+        let url = client.authorization_url("state-1", "nonce-1", "verifier").await.unwrap();
+        assert!(url.starts_with(&format!("{}/auth?", issuer)));
+        assert!(url.contains("code_challenge_method=S256"));
+        assert!(url.contains(&format!("code_challenge={}", pkce_challenge("verifier"))));
+
+        let tokens = client.exchange_code("code-1", "verifier").await.unwrap();
+        let claims = client.validate_id_token(&tokens.id_token, "nonce-1").await.unwrap();
+        assert_eq!(claims.sub, "kc-123");
+        assert_eq!(claims.email.as_deref(), Some("siti@example.com"));
+        assert_eq!(client.identifier(&claims).as_deref(), Some("198701"));
+        assert_eq!(client.map_role(&claims), "admin");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_nonce_and_audience() {
+        let issuer = start_mock_idp("nonce-1").await;
+        let client = client(&issuer);
+        let tokens = client.exchange_code("code-1", "verifier").await.unwrap();
+
+        assert!(client.validate_id_token(&tokens.id_token, "other").await.is_err());
+
+        let mut other_client = self::client(&issuer);
+        other_client.config.client_id = "another-app".to_string();
+        assert!(other_client.validate_id_token(&tokens.id_token, "nonce-1").await.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::models::department::{Department, Team};
+use crate::models::user::User;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::Database;
+
+/// Who approves `user`'s requests: the team supervisor, or the department
+/// supervisor when the team has none, the user leads the team themselves, or
+/// the supervisor was deactivated.
+pub async fn supervisor_of(db: &Database, user: &User) -> Result<Option<ObjectId>, mongodb::error::Error> {
+    let mut candidates = Vec::new();
+    if let Some(team_id) = user.team_id {
+        if let Some(team) = db.collection::<Team>("teams").find_one(doc! { "_id": team_id }).await? {
+            candidates.extend(team.supervisor_id);
+        }
+    }
+    if let Some(department_id) = user.department_id {
+        if let Some(department) = db
+            .collection::<Department>("departments")
+            .find_one(doc! { "_id": department_id })
+            .await?
+        {
+            candidates.extend(department.supervisor_id);
+        }
+    }
+
+    let users_col = db.collection::<User>("users");
+    for candidate in candidates.into_iter().filter(|&id| Some(id) != user.id) {
+        let active = users_col
+            .count_documents(doc! { "_id": candidate, "deactivated": { "$ne": true } })
+            .await?;
+        if active > 0 {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// Users filter matching the active members of every team and department
+/// `supervisor_id` leads, or `None` if they lead none.
+pub async fn reports_filter(
+    db: &Database,
+    tenant_id: ObjectId,
+    supervisor_id: ObjectId,
+) -> Result<Option<Document>, mongodb::error::Error> {
+    let teams: Vec<ObjectId> = db
+        .collection::<Team>("teams")
+        .find(doc! { "tenant_id": tenant_id, "supervisor_id": supervisor_id })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .filter_map(|team| team.id)
+        .collect();
+    let departments: Vec<ObjectId> = db
+        .collection::<Department>("departments")
+        .find(doc! { "tenant_id": tenant_id, "supervisor_id": supervisor_id })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .filter_map(|department| department.id)
+        .collect();
+    if teams.is_empty() && departments.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(doc! {
+        "tenant_id": tenant_id,
+        "$or": [
+            { "team_id": { "$in": teams } },
+            { "department_id": { "$in": departments } },
+        ],
+        "_id": { "$ne": supervisor_id },
+        "deactivated": { "$ne": true },
+    }))
+}
+
+/// Ids of the users matching `filter`.
+pub async fn user_ids(db: &Database, filter: Document) -> Result<Vec<ObjectId>, mongodb::error::Error> {
+    Ok(db
+        .collection::<User>("users")
+        .distinct("_id", filter)
+        .await?
+        .into_iter()
+        .filter_map(|id| id.as_object_id())
+        .collect())
+}
+
+/// Narrows an attendance filter to `allowed` users, keeping an explicit
+/// `user_id` only if it is one of them.
+pub fn restrict_to_users(filter: &mut Document, allowed: Vec<ObjectId>) {
+    let restriction = match filter.get_object_id("user_id") {
+        Ok(user_id) if allowed.contains(&user_id) => return,
+        Ok(_) => doc! { "$in": Bson::Array(vec![]) },
+        Err(_) => doc! { "$in": allowed },
+    };
+    filter.insert("user_id", restriction);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restrict_to_users() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+
+        let mut filter = doc! { "type": "In" };
+        restrict_to_users(&mut filter, vec![a, b]);
+        assert_eq!(filter, doc! { "type": "In", "user_id": { "$in": [a, b] } });
+
+        let mut filter = doc! { "user_id": a };
+        restrict_to_users(&mut filter, vec![a, b]);
+        assert_eq!(filter, doc! { "user_id": a });
+
+        // Asking for someone outside the allowed set matches nothing
+        let mut filter = doc! { "user_id": ObjectId::new() };
+        restrict_to_users(&mut filter, vec![a]);
+        assert_eq!(filter, doc! { "user_id": { "$in": [] } });
+    }
+}
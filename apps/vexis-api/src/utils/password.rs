@@ -0,0 +1,217 @@
+use crate::config::password::PasswordPolicy;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::seq::SliceRandom;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Offline list of common and breached passwords, one per line.
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PolicyViolation {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl PolicyViolation {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks a new password against the policy. Reuse of previous passwords is
+/// checked separately with `is_reused`, since that needs the stored hashes.
+pub fn check_policy(
+    policy: &PasswordPolicy,
+    password: &str,
+    email: &str,
+    identifier: &str,
+) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+
+    if password.chars().count() < policy.min_length {
+        violations.push(PolicyViolation::new(
+            "too_short",
+            format!("Password must be at least {} characters", policy.min_length),
+        ));
+    }
+    if policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+        violations.push(PolicyViolation::new("missing_lowercase", "Password must contain a lowercase letter"));
+    }
+    if policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+        violations.push(PolicyViolation::new("missing_uppercase", "Password must contain an uppercase letter"));
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        violations.push(PolicyViolation::new("missing_digit", "Password must contain a digit"));
+    }
+    if policy.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+        violations.push(PolicyViolation::new("missing_symbol", "Password must contain a symbol"));
+    }
+
+    let lowered = password.to_lowercase();
+    if lowered == email.to_lowercase() || lowered == identifier.to_lowercase() {
+        violations.push(PolicyViolation::new(
+            "matches_account",
+            "Password must not be your email or identifier",
+        ));
+    }
+    if is_common_password(&lowered) {
+        violations.push(PolicyViolation::new(
+            "common_password",
+            "Password is too common or has appeared in a data breach",
+        ));
+    }
+
+    violations
+}
+
+pub fn is_common_password(password: &str) -> bool {
+    static LIST: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    LIST.get_or_init(|| COMMON_PASSWORDS.lines().map(str::trim).collect())
+        .contains(password.to_lowercase().as_str())
+}
+
+/// Whether the password matches any of the given (current or previous) hashes.
+pub fn is_reused(password: &str, hashes: &[String]) -> bool {
+    hashes
+        .iter()
+        .any(|h| matches!(verify_password(password, h), PasswordCheck::Valid { .. }))
+}
+
+/// 422 response listing every violation, so the client can show them all at once.
+pub fn violations_response(violations: Vec<PolicyViolation>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({
+            "error": "Password does not meet the password policy",
+            "violations": violations,
+        })),
+    )
+        .into_response()
+}
+
+/// Generates a password for an admin-created account that satisfies any
+/// policy: at least 16 characters with every character class.
+pub fn generate_temporary_password(policy: &PasswordPolicy) -> String {
+    const CLASSES: [&[u8]; 4] = [
+        b"abcdefghijkmnpqrstuvwxyz",
+        b"ABCDEFGHJKLMNPQRSTUVWXYZ",
+        b"23456789",
+        b"!@#$%*-_+=?",
+    ];
+    let mut rng = rand::thread_rng();
+    let length = policy.min_length.max(16);
+
+    let mut chars: Vec<u8> = CLASSES.iter().map(|class| *class.choose(&mut rng).unwrap()).collect();
+    let all: Vec<u8> = CLASSES.concat();
+    while chars.len() < length {
+        chars.push(*all.choose(&mut rng).unwrap());
+    }
+    chars.shuffle(&mut rng);
+    String::from_utf8(chars).unwrap()
+}
+
+/// Hashes a password with Argon2id.
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordCheck {
+    Invalid,
+    /// `needs_rehash` is set for legacy bcrypt hashes, which should be
+    /// replaced with an Argon2id hash now that the plaintext is known.
+    Valid { needs_rehash: bool },
+}
+
+pub fn verify_password(password: &str, hash: &str) -> PasswordCheck {
+    if hash.starts_with("$2") {
+        return match bcrypt::verify(password, hash) {
+            Ok(true) => PasswordCheck::Valid { needs_rehash: true },
+            _ => PasswordCheck::Invalid,
+        };
+    }
+
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return PasswordCheck::Invalid;
+    };
+    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+        Ok(()) => PasswordCheck::Valid { needs_rehash: false },
+        Err(_) => PasswordCheck::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(violations: Vec<PolicyViolation>) -> Vec<&'static str> {
+        violations.into_iter().map(|v| v.code).collect()
+    }
+
+    #[test]
+    fn test_policy_violations() {
+        let policy = PasswordPolicy::default();
+
+        assert_eq!(codes(check_policy(&policy, "", "a@b.c", "1")), ["too_short", "missing_lowercase", "missing_digit"]);
+        assert_eq!(codes(check_policy(&policy, "password123", "a@b.c", "1")), ["common_password"]);
+        assert_eq!(codes(check_policy(&policy, "198701234", "a@b.c", "198701234")), ["missing_lowercase", "matches_account"]);
+        assert!(check_policy(&policy, "kopi-tubruk-7", "a@b.c", "1").is_empty());
+
+        let strict = PasswordPolicy {
+            require_uppercase: true,
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(codes(check_policy(&strict, "kopitubruk7", "a@b.c", "1")), ["missing_uppercase", "missing_symbol"]);
+    }
+
+    #[test]
+    fn test_temporary_password_satisfies_strict_policy() {
+        let strict = PasswordPolicy {
+            min_length: 20,
+            require_uppercase: true,
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        };
+        let password = generate_temporary_password(&strict);
+        assert_eq!(password.len(), 20);
+        assert!(check_policy(&strict, &password, "a@b.c", "1").is_empty());
+        assert_ne!(password, generate_temporary_password(&strict));
+    }
+
+    #[test]
+    fn test_common_password_is_case_insensitive() {
+        assert!(is_common_password("Bismillah"));
+        assert!(!is_common_password("kopi-tubruk-7"));
+    }
+
+    #[test]
+    fn test_argon2_and_legacy_bcrypt() {
+        let hash = hash_password("kopi-tubruk-7").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(verify_password("kopi-tubruk-7", &hash), PasswordCheck::Valid { needs_rehash: false });
+        assert_eq!(verify_password("salah", &hash), PasswordCheck::Invalid);
+
+        let legacy = bcrypt::hash("kopi-tubruk-7", 4).unwrap();
+        assert_eq!(verify_password("kopi-tubruk-7", &legacy), PasswordCheck::Valid { needs_rehash: true });
+        assert_eq!(verify_password("", ""), PasswordCheck::Invalid);
+
+        assert!(is_reused("kopi-tubruk-7", &[legacy, hash]));
+        assert!(!is_reused("lain-lagi-8", &[]));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::models::office::Office;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::Database;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often the presence of a tenant with open streams is recomputed.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Who is on site right now, grouped by office.
+#[derive(Debug, Clone, Serialize)]
+pub struct Presence {
+    pub as_of: DateTime<Utc>,
+    pub total: usize,
+    pub offices: Vec<OfficePresence>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OfficePresence {
+    /// `None` for people without an office.
+    pub office_id: Option<String>,
+    pub office_name: Option<String>,
+    pub count: usize,
+    /// Earliest check-in first.
+    pub people: Vec<PresentUser>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PresentUser {
+    pub user_id: String,
+    pub name: String,
+    pub identifier: String,
+    pub checked_in_at: DateTime<Utc>,
+}
+
+impl Presence {
+    /// The same presence limited to one office, as `current_presence` with
+    /// `office_id` would return it.
+    pub fn for_office(&self, office_id: Option<ObjectId>) -> Presence {
+        let Some(office_id) = office_id else {
+            return self.clone();
+        };
+        let office_id = office_id.to_hex();
+        let offices: Vec<OfficePresence> = self
+            .offices
+            .iter()
+            .filter(|office| office.office_id.as_ref() == Some(&office_id))
+            .cloned()
+            .collect();
+        Presence {
+            as_of: self.as_of,
+            total: offices.iter().map(|office| office.count).sum(),
+            offices,
+        }
+    }
+}
+
+type PresenceSender = watch::Sender<Option<Arc<Presence>>>;
+
+/// Latest presence per tenant, shared by every open presence stream. A
+/// tenant's presence is computed once per `POLL_INTERVAL` while it has at
+/// least one subscriber, however many streams are open; polling keeps every
+/// instance consistent without a change stream.
+#[derive(Default)]
+pub struct PresenceHub {
+    channels: Arc<Mutex<HashMap<ObjectId, PresenceSender>>>,
+}
+
+impl PresenceHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the tenant's presence, starting its poller for the first
+    /// subscriber. The receiver is marked changed, so a value that is already
+    /// known is seen straight away; after that it changes on every poll.
+    pub fn subscribe(&self, db: &Database, tenant_id: ObjectId) -> watch::Receiver<Option<Arc<Presence>>> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(&tenant_id) {
+            let mut receiver = sender.subscribe();
+            receiver.mark_changed();
+            return receiver;
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        channels.insert(tenant_id, sender.clone());
+        tokio::spawn(poll(self.channels.clone(), db.clone(), tenant_id, sender));
+        receiver
+    }
+}
+
+/// Recomputes a tenant's presence until its last subscriber is gone.
+async fn poll(
+    channels: Arc<Mutex<HashMap<ObjectId, PresenceSender>>>,
+    db: Database,
+    tenant_id: ObjectId,
+    sender: PresenceSender,
+) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        {
+            // Checked under the lock, so no one subscribes to a channel that
+            // is about to be dropped
+            let mut channels = channels.lock().unwrap();
+            if sender.receiver_count() == 0 {
+                channels.remove(&tenant_id);
+                return;
+            }
+        }
+        match current_presence(&db, tenant_id, None).await {
+            Ok(presence) => {
+                sender.send_replace(Some(Arc::new(presence)));
+            }
+            Err(e) => eprintln!("Failed to compute presence for tenant {}: {:?}", tenant_id, e),
+        }
+    }
+}
+
+/// One row of the presence aggregation.
+#[derive(Debug, Deserialize)]
+struct PresentRow {
+    #[serde(rename = "_id")]
+    user_id: ObjectId,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    checked_in_at: DateTime<Utc>,
+    #[serde(default)]
+    office_id: Option<ObjectId>,
+    name: String,
+    identifier: String,
+}
+
+/// Users whose latest attendance today (WIB) is "In". They count for the
+/// office they checked in at, or for their assigned office if the record has
+/// none (manual entries, check-ins against the default fence). `office_id`
+/// limits the result to one office.
+pub async fn current_presence(
+    db: &Database,
+    tenant_id: ObjectId,
+    office_id: Option<ObjectId>,
+) -> mongodb::error::Result<Presence> {
+    let now = Utc::now();
+    // WIB offset (UTC+7)
+    let wib = FixedOffset::east_opt(7 * 3600).unwrap();
+    let day_start = now
+        .with_timezone(&wib)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_local_timezone(wib)
+        .unwrap()
+        .with_timezone(&Utc);
+
+    let mut pipeline = vec![
+        doc! {
+            "$match": {
+                "tenant_id": tenant_id,
+                "timestamp": { "$gte": mongodb::bson::DateTime::from_chrono(day_start) },
+                "voided": { "$ne": true },
+            }
+        },
+        doc! { "$sort": { "timestamp": 1 } },
+        doc! {
+            "$group": {
+                "_id": "$user_id",
+                "type": { "$last": "$type" },
+                "checked_in_at": { "$last": "$timestamp" },
+                "office_id": { "$last": "$office_id" },
+            }
+        },
+        doc! { "$match": { "type": "In" } },
+        doc! {
+            "$lookup": {
+                "from": "users",
+                "let": { "uid": "$_id" },
+                "pipeline": [
+                    { "$match": { "$expr": { "$eq": ["$_id", "$$uid"] }, "tenant_id": tenant_id } },
+                    { "$project": { "name": 1, "identifier": 1, "office_id": 1 } },
+                ],
+                "as": "user"
+            }
+        },
+        // Keep people whose account was purged while on site
+        doc! { "$unwind": { "path": "$user", "preserveNullAndEmptyArrays": true } },
+        doc! {
+            "$project": {
+                "checked_in_at": 1,
+                "office_id": { "$ifNull": ["$office_id", "$user.office_id"] },
+                "name": { "$ifNull": ["$user.name", "(deleted user)"] },
+                "identifier": { "$ifNull": ["$user.identifier", ""] },
+            }
+        },
+    ];
+    if let Some(office_id) = office_id {
+        pipeline.push(doc! { "$match": { "office_id": office_id } });
+    }
+
+    let mut rows = Vec::new();
+    let mut cursor = db.collection::<Document>("attendances").aggregate(pipeline).await?;
+    while let Some(doc) = cursor.try_next().await? {
+        match mongodb::bson::from_document::<PresentRow>(doc) {
+            Ok(row) => rows.push(row),
+            Err(e) => eprintln!("Error decoding presence row: {}", e),
+        }
+    }
+
+    let mut names = HashMap::new();
+    let mut offices = db.collection::<Office>("offices").find(doc! { "tenant_id": tenant_id }).await?;
+    while let Some(office) = offices.try_next().await? {
+        if let Some(id) = office.id {
+            names.insert(id, office.name);
+        }
+    }
+
+    let offices = group_by_office(rows, &names);
+    Ok(Presence {
+        as_of: now,
+        total: offices.iter().map(|office| office.count).sum(),
+        offices,
+    })
+}
+
+/// Offices sorted by name, people without an office last.
+fn group_by_office(rows: Vec<PresentRow>, names: &HashMap<ObjectId, String>) -> Vec<OfficePresence> {
+    let mut groups: HashMap<Option<ObjectId>, Vec<PresentUser>> = HashMap::new();
+    for row in rows {
+        groups.entry(row.office_id).or_default().push(PresentUser {
+            user_id: row.user_id.to_hex(),
+            name: row.name,
+            identifier: row.identifier,
+            checked_in_at: row.checked_in_at,
+        });
+    }
+
+    let mut offices: Vec<OfficePresence> = groups
+        .into_iter()
+        .map(|(office_id, mut people)| {
+            people.sort_by(|a, b| a.checked_in_at.cmp(&b.checked_in_at).then_with(|| a.name.cmp(&b.name)));
+            OfficePresence {
+                office_id: office_id.map(|id| id.to_hex()),
+                office_name: office_id.and_then(|id| names.get(&id).cloned()),
+                count: people.len(),
+                people,
+            }
+        })
+        .collect();
+    offices.sort_by(|a, b| match (&a.office_id, &b.office_id) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(_), Some(_)) => a.office_name.cmp(&b.office_name).then_with(|| a.office_id.cmp(&b.office_id)),
+    });
+    offices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn row(office_id: Option<ObjectId>, name: &str, hour: u32) -> PresentRow {
+        PresentRow {
+            user_id: ObjectId::new(),
+            checked_in_at: Utc.with_ymd_and_hms(2026, 10, 19, hour, 0, 0).unwrap(),
+            office_id,
+            name: name.to_string(),
+            identifier: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_group_by_office() {
+        let (north, south) = (ObjectId::new(), ObjectId::new());
+        let names = HashMap::from([(north, "North".to_string()), (south, "South".to_string())]);
+        let rows = vec![
+            row(None, "Dewi", 1),
+            row(Some(south), "Budi", 2),
+            row(Some(north), "Citra", 3),
+            row(Some(south), "Ani", 1),
+        ];
+
+        let offices = group_by_office(rows, &names);
+
+        let summary: Vec<_> = offices
+            .iter()
+            .map(|office| {
+                let people: Vec<_> = office.people.iter().map(|p| p.name.as_str()).collect();
+                (office.office_name.as_deref(), office.count, people)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("North"), 1, vec!["Citra"]),
+                (Some("South"), 2, vec!["Ani", "Budi"]),
+                (None, 1, vec!["Dewi"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_for_office_keeps_one_office() {
+        let (north, south) = (ObjectId::new(), ObjectId::new());
+        let names = HashMap::from([(north, "North".to_string()), (south, "South".to_string())]);
+        let offices = group_by_office(
+            vec![row(Some(north), "Citra", 3), row(Some(south), "Ani", 1), row(Some(south), "Budi", 2)],
+            &names,
+        );
+        let presence = Presence { as_of: Utc::now(), total: 3, offices };
+
+        let south_only = presence.for_office(Some(south));
+        assert_eq!(south_only.total, 2);
+        assert_eq!(south_only.offices.len(), 1);
+        assert_eq!(south_only.offices[0].office_name.as_deref(), Some("South"));
+        assert_eq!(presence.for_office(None).total, 3);
+        assert_eq!(presence.for_office(Some(ObjectId::new())).total, 0);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::config::push::PushConfig;
+use crate::models::push::PushSubscription;
+use crate::utils::jobs::{enqueue, JOB_KIND_PUSH};
+use crate::AppState;
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use web_push::{
+    request_builder::build_request, ContentEncoding, PartialVapidSignatureBuilder, SubscriptionInfo,
+    VapidSignatureBuilder, WebPushMessageBuilder,
+};
+
+/// Push services drop undelivered messages after this many seconds.
+const MESSAGE_TTL: u32 = 4 * 3600;
+
+#[derive(Debug)]
+pub enum PushError {
+    /// The subscription no longer exists (404/410) and should be deleted.
+    Gone,
+    Http(reqwest::Error),
+    Rejected(String),
+    Encryption(String),
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Gone => write!(f, "push subscription has expired"),
+            PushError::Http(e) => write!(f, "request to push service failed: {}", e),
+            PushError::Rejected(msg) => write!(f, "push service rejected message: {}", msg),
+            PushError::Encryption(msg) => write!(f, "could not encrypt push message: {}", msg),
+        }
+    }
+}
+
+impl From<reqwest::Error> for PushError {
+    fn from(e: reqwest::Error) -> Self {
+        PushError::Http(e)
+    }
+}
+
+/// What the service worker shows. Serialized as the JSON push payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushNotification {
+    pub title: String,
+    pub body: String,
+    /// Page opened when the notification is clicked.
+    pub url: String,
+    /// Notifications with the same tag replace each other on the device.
+    pub tag: String,
+}
+
+/// Payload of a `push` job: one notification to every device of a user.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushJob {
+    pub user_id: ObjectId,
+    pub notification: PushNotification,
+}
+
+/// A way of delivering Web Push messages. Tests point the real transport at a
+/// local mock push service.
+#[async_trait]
+pub trait PushTransport: Send + Sync {
+    /// Public VAPID key the browser needs to subscribe (base64url).
+    fn application_server_key(&self) -> String;
+
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> Result<(), PushError>;
+}
+
+/// Sends encrypted (aes128gcm) messages signed with a VAPID key.
+pub struct WebPushTransport {
+    http: Client,
+    vapid: PartialVapidSignatureBuilder,
+    subject: String,
+}
+
+impl WebPushTransport {
+    pub fn new(config: PushConfig, http: Client) -> Result<Self, String> {
+        let vapid = VapidSignatureBuilder::from_base64_no_sub(&config.vapid_private_key)
+            .map_err(|e| format!("invalid VAPID_PRIVATE_KEY: {}", e))?;
+        Ok(Self {
+            http,
+            vapid,
+            subject: config.subject,
+        })
+    }
+}
+
+#[async_trait]
+impl PushTransport for WebPushTransport {
+    fn application_server_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.vapid.get_public_key())
+    }
+
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> Result<(), PushError> {
+        let info = SubscriptionInfo::new(
+            subscription.endpoint.as_str(),
+            subscription.p256dh.as_str(),
+            subscription.auth.as_str(),
+        );
+
+        let mut signature = self.vapid.clone().add_sub_info(&info);
+        signature.add_claim("sub", self.subject.as_str());
+        let signature = signature.build().map_err(|e| PushError::Encryption(e.to_string()))?;
+
+        let mut message = WebPushMessageBuilder::new(&info);
+        message.set_ttl(MESSAGE_TTL);
+        message.set_payload(ContentEncoding::Aes128Gcm, payload);
+        message.set_vapid_signature(signature);
+        let message = message.build().map_err(|e| PushError::Encryption(e.to_string()))?;
+
+        // web-push builds an `http` request; replay it with our client
+        let request = build_request::<Vec<u8>>(message);
+        let mut builder = self.http.post(request.uri().to_string());
+        for (name, value) in request.headers() {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+        let response = builder.body(request.into_body()).send().await?;
+
+        match response.status().as_u16() {
+            200..=299 => Ok(()),
+            404 | 410 => Err(PushError::Gone),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(PushError::Rejected(format!("{} {}", status, body)))
+            }
+        }
+    }
+}
+
+/// Queues `notification` for every device of the user. Does nothing when push
+/// is not configured.
+pub async fn notify_user(
+    state: &AppState,
+    user_id: ObjectId,
+    notification: PushNotification,
+) -> Result<(), mongodb::error::Error> {
+    if state.push.is_none() {
+        return Ok(());
+    }
+    enqueue(&state.db, JOB_KIND_PUSH, &PushJob { user_id, notification }).await?;
+    Ok(())
+}
+
+/// Runs a `push` job. Expired subscriptions are removed; the job only fails
+/// (and is retried) if no device could be reached because of a transient error.
+pub async fn deliver(state: &AppState, job: PushJob) -> Result<(), String> {
+    let Some(push) = &state.push else {
+        return Ok(());
+    };
+    let subscriptions_col = state.db.collection::<PushSubscription>("push_subscriptions");
+
+    let subscriptions: Vec<PushSubscription> = subscriptions_col
+        .find(doc! { "user_id": job.user_id })
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let payload = serde_json::to_vec(&job.notification).map_err(|e| e.to_string())?;
+    let mut delivered = false;
+    let mut last_error = None;
+
+    for subscription in &subscriptions {
+        match push.send(subscription, &payload).await {
+            Ok(()) => delivered = true,
+            Err(PushError::Gone) => {
+                let _ = subscriptions_col
+                    .delete_one(doc! { "endpoint": &subscription.endpoint })
+                    .await;
+            }
+            Err(e) => last_error = Some(e.to_string()),
+        }
+    }
+
+    match last_error {
+        Some(error) if !delivered => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use chrono::Utc;
+    use std::sync::{Arc, Mutex};
+
+    // Test vectors from the web-push crate
+    const VAPID_PRIVATE_KEY: &str = "IQ9Ur0ykXoHS9gzfYX0aBjy9lvdrjx_PFUXmie9YRcY";
+    const P256DH: &str = "BLMbF9ffKBiWQLCKvTHb6LO8Nb6dcUh6TItC455vu2kElga6PQvUmaFyCdykxY2nOSSL3yKgfbmFLRTUaGv4yV8";
+    const AUTH: &str = "xS03Fi5ErfTNH_l9WHE9Ig";
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, usize)>>>;
+
+    /// Mock push service: `/ok` accepts messages, `/gone` reports an expired
+    /// subscription.
+    async fn mock_push_service() -> (String, Received) {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/ok",
+                post(|State(received): State<Received>, headers: HeaderMap, body: axum::body::Bytes| async move {
+                    received.lock().unwrap().push((headers, body.len()));
+                    StatusCode::CREATED
+                }),
+            )
+            .route("/gone", post(|| async { StatusCode::GONE }))
+            .with_state(received.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), received)
+    }
+
+    fn subscription(endpoint: String) -> PushSubscription {
+        PushSubscription {
+            id: None,
+            user_id: ObjectId::new(),
+            endpoint,
+            p256dh: P256DH.to_string(),
+            auth: AUTH.to_string(),
+            device: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn transport() -> WebPushTransport {
+        WebPushTransport::new(
+            PushConfig {
+                vapid_private_key: VAPID_PRIVATE_KEY.to_string(),
+                subject: "mailto:it@example.com".to_string(),
+            },
+            Client::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sends_encrypted_signed_message() {
+        let (base, received) = mock_push_service().await;
+        let transport = transport();
+
+        transport
+            .send(&subscription(format!("{}/ok", base)), br#"{"title":"Absen"}"#)
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        let (headers, body_len) = &received[0];
+        assert_eq!(headers["content-encoding"], "aes128gcm");
+        assert_eq!(headers["ttl"], MESSAGE_TTL.to_string().as_str());
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("vapid t="));
+        assert!(authorization.contains(&format!("k={}", transport.application_server_key())));
+        // Ciphertext is longer than the plaintext (header, tag, padding)
+        assert!(*body_len > r#"{"title":"Absen"}"#.len());
+    }
+
+    #[tokio::test]
+    async fn test_expired_subscription_is_gone() {
+        let (base, _) = mock_push_service().await;
+        let result = transport().send(&subscription(format!("{}/gone", base)), b"{}").await;
+        assert!(matches!(result, Err(PushError::Gone)));
+    }
+}
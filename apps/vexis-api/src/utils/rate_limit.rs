@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Window {
+    started_at: Instant,
+    hits: u32,
+}
+
+/// Fixed-window request counter per key (e.g. `email:<addr>` or `ip:<addr>`).
+/// State lives in this process only, so each instance enforces its own limit.
+pub struct RateLimiter {
+    windows: Mutex<HashMap<String, Window>>,
+    max_hits: u32,
+    window: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(max_hits: u32, window: Duration) -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+            max_hits,
+            window,
+        }
+    }
+
+    /// Records a hit for `key` and returns `false` once the limit for the
+    /// current window is exceeded.
+    pub fn check(&self, key: &str) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        let now = Instant::now();
+
+        // Drop finished windows so the map does not grow without bound
+        if windows.len() > 1024 {
+            windows.retain(|_, w| now.duration_since(w.started_at) < self.window);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            hits: 0,
+        });
+        if now.duration_since(entry.started_at) >= self.window {
+            entry.started_at = now;
+            entry.hits = 0;
+        }
+
+        entry.hits += 1;
+        entry.hits <= self.max_hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_per_key() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("email:a@example.com"));
+        assert!(limiter.check("email:a@example.com"));
+        assert!(!limiter.check("email:a@example.com"));
+        assert!(limiter.check("email:b@example.com"));
+    }
+
+    #[test]
+    fn test_window_resets() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(20));
+        assert!(limiter.check("ip:127.0.0.1"));
+        assert!(!limiter.check("ip:127.0.0.1"));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.check("ip:127.0.0.1"));
+    }
+}
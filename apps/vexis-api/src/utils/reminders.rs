@@ -0,0 +1,210 @@
+use crate::config::push::ReminderConfig;
+use crate::models::attendance::Attendance;
+use crate::models::push::SentReminder;
+use crate::models::user::User;
+use crate::utils::push::{notify_user, PushNotification};
+use crate::AppState;
+use chrono::{Datelike, Duration, FixedOffset, NaiveDateTime, Utc};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use std::sync::Arc;
+
+const TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReminderKind {
+    CheckIn,
+    CheckOut,
+}
+
+impl ReminderKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReminderKind::CheckIn => "check_in",
+            ReminderKind::CheckOut => "check_out",
+        }
+    }
+}
+
+/// Which reminder is due at `now` (WIB): check-in shortly before the shift
+/// starts, check-out from a while after it ends until midnight.
+pub fn due_reminder(config: &ReminderConfig, now: NaiveDateTime) -> Option<ReminderKind> {
+    if !config.workdays.contains(&now.weekday()) {
+        return None;
+    }
+
+    let time = now.time();
+    let check_in_from = config.shift_start - Duration::minutes(config.before_start_minutes);
+    if time >= check_in_from && time < config.shift_start {
+        return Some(ReminderKind::CheckIn);
+    }
+    if time >= config.shift_end + Duration::minutes(config.after_end_minutes) {
+        return Some(ReminderKind::CheckOut);
+    }
+    None
+}
+
+/// `last_type_today` is the type of the user's latest attendance today, if any.
+pub fn needs_reminder(kind: ReminderKind, last_type_today: Option<&str>) -> bool {
+    match kind {
+        ReminderKind::CheckIn => last_type_today.is_none(),
+        ReminderKind::CheckOut => last_type_today == Some("In"),
+    }
+}
+
+fn notification(kind: ReminderKind, config: &ReminderConfig, locale: &str) -> PushNotification {
+    let start = config.shift_start.format("%H:%M");
+    let end = config.shift_end.format("%H:%M");
+    let (title, body) = match (kind, locale) {
+        (ReminderKind::CheckIn, "en") => (
+            "Don't forget to check in".to_string(),
+            format!("Your shift starts at {} and you haven't checked in yet.", start),
+        ),
+        (ReminderKind::CheckIn, _) => (
+            "Jangan lupa absen masuk".to_string(),
+            format!("Shift dimulai pukul {} dan Anda belum absen masuk.", start),
+        ),
+        (ReminderKind::CheckOut, "en") => (
+            "You haven't checked out".to_string(),
+            format!("Your shift ended at {}. Remember to check out.", end),
+        ),
+        (ReminderKind::CheckOut, _) => (
+            "Anda belum absen pulang".to_string(),
+            format!("Shift berakhir pukul {}. Jangan lupa absen pulang.", end),
+        ),
+    };
+
+    PushNotification {
+        title,
+        body,
+        url: "/attendance".to_string(),
+        tag: format!("reminder-{}", kind.as_str()),
+    }
+}
+
+/// Checks once a minute whether a reminder is due and queues it for users
+/// with a push subscription.
+pub fn spawn_scheduler(state: Arc<AppState>, config: ReminderConfig) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            if let Err(e) = send_due_reminders(&state, &config).await {
+                eprintln!("Reminder scheduler error: {}", e);
+            }
+        }
+    });
+}
+
+async fn send_due_reminders(state: &AppState, config: &ReminderConfig) -> Result<(), mongodb::error::Error> {
+    let wib = FixedOffset::east_opt(7 * 3600).unwrap();
+    let now_wib = Utc::now().with_timezone(&wib);
+    let Some(kind) = due_reminder(config, now_wib.naive_local()) else {
+        return Ok(());
+    };
+    let today = now_wib.date_naive();
+
+    let user_ids = state
+        .db
+        .collection::<Document>("push_subscriptions")
+        .distinct("user_id", doc! {})
+        .await?;
+
+    for user_id in user_ids.into_iter().filter_map(|id| match id {
+        Bson::ObjectId(oid) => Some(oid),
+        _ => None,
+    }) {
+        let last = state
+            .db
+            .collection::<Attendance>("attendances")
+            .find_one(doc! { "user_id": user_id, "voided": { "$ne": true } })
+            .sort(doc! { "timestamp": -1 })
+            .await?;
+        let last_type_today = last
+            .as_ref()
+            .filter(|att| att.timestamp.with_timezone(&wib).date_naive() == today)
+            .map(|att| att.r#type.as_str());
+
+        if !needs_reminder(kind, last_type_today) {
+            continue;
+        }
+        if !mark_sent(state, user_id, kind, &today.format("%Y-%m-%d").to_string()).await? {
+            continue;
+        }
+
+        let Some(user) = state.db.collection::<User>("users").find_one(doc! { "_id": user_id }).await? else {
+            continue;
+        };
+        notify_user(state, user_id, notification(kind, config, &user.locale)).await?;
+    }
+
+    Ok(())
+}
+
+/// Records the reminder; `false` means it was already sent today (possibly by
+/// another instance).
+async fn mark_sent(
+    state: &AppState,
+    user_id: ObjectId,
+    kind: ReminderKind,
+    date: &str,
+) -> Result<bool, mongodb::error::Error> {
+    let sent = SentReminder {
+        user_id,
+        kind: kind.as_str().to_string(),
+        date: date.to_string(),
+        expires_at: Utc::now() + Duration::days(2),
+    };
+
+    match state.db.collection::<SentReminder>("sent_reminders").insert_one(sent).await {
+        Ok(_) => Ok(true),
+        Err(e) => match *e.kind {
+            ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000 => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime, Weekday};
+
+    fn config() -> ReminderConfig {
+        ReminderConfig {
+            shift_start: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            shift_end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            before_start_minutes: 15,
+            after_end_minutes: 30,
+            workdays: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        }
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2026-01-05 is a Monday
+        NaiveDate::from_ymd_opt(2026, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_due_reminder_windows() {
+        let config = config();
+        assert_eq!(due_reminder(&config, at(5, 7, 44)), None);
+        assert_eq!(due_reminder(&config, at(5, 7, 45)), Some(ReminderKind::CheckIn));
+        assert_eq!(due_reminder(&config, at(5, 7, 59)), Some(ReminderKind::CheckIn));
+        assert_eq!(due_reminder(&config, at(5, 8, 0)), None);
+        assert_eq!(due_reminder(&config, at(5, 17, 29)), None);
+        assert_eq!(due_reminder(&config, at(5, 17, 30)), Some(ReminderKind::CheckOut));
+        assert_eq!(due_reminder(&config, at(5, 23, 59)), Some(ReminderKind::CheckOut));
+        // Saturday
+        assert_eq!(due_reminder(&config, at(10, 7, 50)), None);
+    }
+
+    #[test]
+    fn test_needs_reminder() {
+        assert!(needs_reminder(ReminderKind::CheckIn, None));
+        assert!(!needs_reminder(ReminderKind::CheckIn, Some("In")));
+        assert!(needs_reminder(ReminderKind::CheckOut, Some("In")));
+        assert!(!needs_reminder(ReminderKind::CheckOut, Some("Out")));
+        assert!(!needs_reminder(ReminderKind::CheckOut, None));
+    }
+}
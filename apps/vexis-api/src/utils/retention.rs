@@ -0,0 +1,148 @@
+use crate::config::retention::RetentionConfig;
+use crate::models::user::User;
+use crate::AppState;
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+const TICK: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Collections holding per-user documents keyed by `user_id`, removed together
+/// with the user.
+const USER_DATA_COLLECTIONS: [&str; 8] = [
+    "attendances",
+    "refresh_tokens",
+    "password_resets",
+    "email_verifications",
+    "login_codes",
+    "push_subscriptions",
+    "sent_reminders",
+    "security_events",
+];
+
+/// Maps a stored `photo_url` (`/api/uploads/<file>`) to the file on disk.
+/// Anything else, including paths that try to leave `uploads`, is ignored.
+pub fn photo_path(photo_url: &str) -> Option<PathBuf> {
+    let file = photo_url.strip_prefix("/api/uploads/")?;
+    let mut components = Path::new(file).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => Some(Path::new("uploads").join(name)),
+        _ => None,
+    }
+}
+
+/// Purges soft-deleted users once an hour.
+pub fn spawn_scheduler(state: Arc<AppState>, config: RetentionConfig) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            match purge_deleted_users(&state, &config).await {
+                Ok(0) => {}
+                Ok(count) => println!("Retention purge removed {} deleted users", count),
+                Err(e) => eprintln!("Retention purge error: {}", e),
+            }
+        }
+    });
+}
+
+/// Removes users deleted more than `deleted_user_days` ago along with their
+/// attendance and other data. Returns how many users were purged.
+pub async fn purge_deleted_users(state: &AppState, config: &RetentionConfig) -> Result<u64, mongodb::error::Error> {
+    let Some(cutoff) = Duration::try_days(config.deleted_user_days).and_then(|age| Utc::now().checked_sub_signed(age))
+    else {
+        eprintln!("Retention period of {} days is out of range", config.deleted_user_days);
+        return Ok(0);
+    };
+    let users: Vec<User> = state
+        .db
+        .collection::<User>("users")
+        .find(doc! { "deactivated": true, "deleted_at": { "$lt": DateTime::from_chrono(cutoff) } })
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut purged = 0;
+    for user in users {
+        let user_id = user.id.unwrap();
+        match purge_user(state, user_id, cutoff).await {
+            Ok(true) => {}
+            // Reactivated since the query above
+            Ok(false) => continue,
+            Err(e) => {
+                // Left for the next run
+                eprintln!("Failed to purge user {}: {}", user_id, e);
+                continue;
+            }
+        }
+        if let Some(path) = user.photo_url.as_deref().and_then(photo_path) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+/// Deletes the user and all their documents in one transaction, so a failure
+/// never leaves attendance without its user or the reverse. The user goes
+/// first and must still be deleted before `cutoff`; otherwise, such as when
+/// an admin reactivated them meanwhile, nothing is touched and `false` is
+/// returned. Requires a replica set.
+async fn purge_user(
+    state: &AppState,
+    user_id: ObjectId,
+    cutoff: chrono::DateTime<Utc>,
+) -> Result<bool, mongodb::error::Error> {
+    let mut session = state.db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    let deleted = state
+        .db
+        .collection::<Document>("users")
+        .delete_one(doc! {
+            "_id": user_id,
+            "deactivated": true,
+            "deleted_at": { "$lt": DateTime::from_chrono(cutoff) },
+        })
+        .session(&mut session)
+        .await?;
+    if deleted.deleted_count == 0 {
+        session.abort_transaction().await?;
+        return Ok(false);
+    }
+
+    for collection in USER_DATA_COLLECTIONS {
+        state
+            .db
+            .collection::<Document>(collection)
+            .delete_many(doc! { "user_id": user_id })
+            .session(&mut session)
+            .await?;
+    }
+    for collection in ["departments", "teams"] {
+        state
+            .db
+            .collection::<Document>(collection)
+            .update_many(doc! { "supervisor_id": user_id }, doc! { "$set": { "supervisor_id": null } })
+            .session(&mut session)
+            .await?;
+    }
+
+    session.commit_transaction().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_photo_path_stays_in_uploads() {
+        assert_eq!(photo_path("/api/uploads/abc.jpg"), Some(PathBuf::from("uploads/abc.jpg")));
+        assert_eq!(photo_path("/api/uploads/../.env"), None);
+        assert_eq!(photo_path("/api/uploads/a/b.jpg"), None);
+        assert_eq!(photo_path("https://cdn.example.com/abc.jpg"), None);
+    }
+}
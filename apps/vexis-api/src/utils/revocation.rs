@@ -0,0 +1,246 @@
+use crate::utils::jwt::Claims;
+use crate::AppState;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::Database;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// The parts of a user that decide whether an access token is still valid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenState {
+    pub version: i32,
+    pub role: String,
+    pub tenant_id: ObjectId,
+}
+
+impl TokenState {
+    /// Whether an access token with these claims is still valid for the user.
+    pub fn matches(&self, claims: &Claims) -> bool {
+        self.version == claims.ver && self.role == claims.role && self.tenant_id.to_hex() == claims.tenant_id
+    }
+}
+
+struct CachedTokenState {
+    state: Option<TokenState>,
+    fetched_at: Instant,
+}
+
+struct Entries {
+    map: HashMap<ObjectId, CachedTokenState>,
+    /// Bumped by every invalidation, so a lookup that raced with one does not
+    /// cache the state it read before.
+    generation: u64,
+    pruned_at: Instant,
+}
+
+/// In-memory cache of `TokenState` per user so `require_auth` does not hit
+/// MongoDB on every request. Entries are dropped on revocation in this process
+/// and expire after `ttl`, which bounds staleness across instances. Expired
+/// entries are pruned at most once per `ttl`, so memory is bounded by the
+/// users seen within about two `ttl`s.
+pub struct TokenStateCache {
+    entries: RwLock<Entries>,
+    ttl: Duration,
+}
+
+impl TokenStateCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(Entries {
+                map: HashMap::new(),
+                generation: 0,
+                pruned_at: Instant::now(),
+            }),
+            ttl,
+        }
+    }
+
+    /// Returns the current token state for a user, or `None` if the user no
+    /// longer exists, was deactivated or belongs to a deactivated tenant.
+    pub async fn get(
+        &self,
+        db: &Database,
+        user_id: ObjectId,
+    ) -> Result<Option<TokenState>, mongodb::error::Error> {
+        let generation = {
+            let entries = self.entries.read().unwrap();
+            if let Some(cached) = entries.map.get(&user_id) {
+                if cached.fetched_at.elapsed() < self.ttl {
+                    return Ok(cached.state.clone());
+                }
+            }
+            entries.generation
+        };
+
+        let user = db
+            .collection::<Document>("users")
+            .find_one(doc! { "_id": user_id })
+            .projection(doc! { "token_version": 1, "role": 1, "deactivated": 1, "tenant_id": 1 })
+            .await?;
+
+        let mut state = user
+            .filter(|u| !u.get_bool("deactivated").unwrap_or(false))
+            .and_then(|u| {
+                Some(TokenState {
+                    version: u.get_i32("token_version").unwrap_or(0),
+                    role: u.get_str("role").unwrap_or_default().to_string(),
+                    tenant_id: u.get_object_id("tenant_id").ok()?,
+                })
+            });
+        if let Some(current) = &state {
+            let tenant_active = db
+                .collection::<Document>("tenants")
+                .count_documents(doc! { "_id": current.tenant_id, "active": true })
+                .await?;
+            if tenant_active == 0 {
+                state = None;
+            }
+        }
+
+        self.store(user_id, state.clone(), generation);
+        Ok(state)
+    }
+
+    /// Caches `state` unless something was invalidated since `generation`
+    /// was read.
+    fn store(&self, user_id: ObjectId, state: Option<TokenState>, generation: u64) {
+        let mut entries = self.entries.write().unwrap();
+        if entries.generation != generation {
+            return;
+        }
+        if entries.pruned_at.elapsed() >= self.ttl {
+            let ttl = self.ttl;
+            entries.map.retain(|_, cached| cached.fetched_at.elapsed() < ttl);
+            entries.pruned_at = Instant::now();
+        }
+        entries.map.insert(
+            user_id,
+            CachedTokenState {
+                state,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, user_id: &ObjectId) {
+        let mut entries = self.entries.write().unwrap();
+        entries.map.remove(user_id);
+        entries.generation += 1;
+    }
+
+    /// Drops the cached state of every user of `tenant_id`.
+    pub fn invalidate_tenant(&self, tenant_id: &ObjectId) {
+        let mut entries = self.entries.write().unwrap();
+        entries
+            .map
+            .retain(|_, cached| !matches!(&cached.state, Some(s) if s.tenant_id == *tenant_id));
+        entries.generation += 1;
+    }
+}
+
+/// Invalidates every outstanding access and refresh token of a user by bumping
+/// `token_version` and deleting the refresh tokens.
+pub async fn revoke_user_tokens(
+    state: &AppState,
+    user_id: ObjectId,
+) -> Result<(), mongodb::error::Error> {
+    state
+        .db
+        .collection::<Document>("users")
+        .update_one(doc! { "_id": user_id }, doc! { "$inc": { "token_version": 1 } })
+        .await?;
+    state
+        .db
+        .collection::<Document>("refresh_tokens")
+        .delete_many(doc! { "user_id": user_id })
+        .await?;
+
+    state.token_cache.invalidate(&user_id);
+    Ok(())
+}
+
+/// Ends every session in a deactivated tenant. Access tokens fail once the
+/// cached state is gone, since the tenant is no longer active; refresh tokens
+/// are deleted so none survive a later reactivation.
+pub async fn revoke_tenant_tokens(
+    state: &AppState,
+    tenant_id: ObjectId,
+) -> Result<(), mongodb::error::Error> {
+    let user_ids = state
+        .db
+        .collection::<Document>("users")
+        .distinct("_id", doc! { "tenant_id": tenant_id })
+        .await?;
+    state
+        .db
+        .collection::<Document>("refresh_tokens")
+        .delete_many(doc! { "user_id": { "$in": user_ids } })
+        .await?;
+
+    state.token_cache.invalidate_tenant(&tenant_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_state() -> Option<TokenState> {
+        Some(TokenState {
+            version: 0,
+            role: "user".to_string(),
+            tenant_id: ObjectId::new(),
+        })
+    }
+
+    #[test]
+    fn test_lookup_racing_an_invalidation_is_not_cached() {
+        let cache = TokenStateCache::new(Duration::from_secs(60));
+        let user_id = ObjectId::new();
+
+        let generation = cache.entries.read().unwrap().generation;
+        cache.invalidate(&user_id);
+        cache.store(user_id, token_state(), generation);
+        assert!(cache.entries.read().unwrap().map.is_empty());
+
+        let generation = cache.entries.read().unwrap().generation;
+        cache.store(user_id, token_state(), generation);
+        assert!(cache.entries.read().unwrap().map.contains_key(&user_id));
+    }
+
+    #[test]
+    fn test_expired_entries_are_pruned() {
+        let cache = TokenStateCache::new(Duration::ZERO);
+        let (first, second) = (ObjectId::new(), ObjectId::new());
+
+        cache.store(first, token_state(), 0);
+        cache.store(second, token_state(), 0);
+
+        let entries = cache.entries.read().unwrap();
+        assert_eq!(entries.map.len(), 1);
+        assert!(entries.map.contains_key(&second));
+    }
+
+    #[test]
+    fn test_invalidate_tenant_drops_only_its_users() {
+        let cache = TokenStateCache::new(Duration::from_secs(60));
+        let (tenant, other) = (ObjectId::new(), ObjectId::new());
+        let (member, outsider) = (ObjectId::new(), ObjectId::new());
+        let state_in = |tenant_id| {
+            Some(TokenState {
+                version: 0,
+                role: "user".to_string(),
+                tenant_id,
+            })
+        };
+
+        cache.store(member, state_in(tenant), 0);
+        cache.store(outsider, state_in(other), 0);
+        cache.invalidate_tenant(&tenant);
+
+        let entries = cache.entries.read().unwrap();
+        assert!(!entries.map.contains_key(&member));
+        assert!(entries.map.contains_key(&outsider));
+    }
+}
@@ -0,0 +1,549 @@
+use crate::config::db::is_duplicate_key;
+use crate::models::office::Office;
+use crate::models::user::{OfficeLocation, User};
+use crate::models::auth::PasswordReset;
+use crate::models::user_import::{ImportRow, RowError, UserImport};
+use crate::middleware::client::ClientInfo;
+use crate::utils::audit::AuditEvent;
+use crate::utils::email::{is_valid_address, normalize_address};
+use crate::utils::email_templates::DEFAULT_LOCALE;
+use crate::utils::jobs::{enqueue, enqueue_email, JOB_KIND_USER_IMPORT};
+use crate::utils::token::{generate_token, hash_token};
+use crate::utils::webhook::{emit, EVENT_USER_REGISTERED};
+use crate::AppState;
+use calamine::{Reader, Xlsx};
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+/// Upper bound on rows per upload; larger rosters are split by the admin.
+pub const MAX_ROWS: usize = 5000;
+/// Invitation links stay valid this long, unlike the one-hour reset link.
+pub const INVITATION_VALID_DAYS: i64 = 7;
+/// Progress is written back after this many rows.
+const PROGRESS_EVERY: usize = 25;
+/// A run hands the rest of the file to a fresh job after this long, staying
+/// well inside the job lease.
+const MAX_RUN_TIME: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+/// A data row as read from the file, before validation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawRow {
+    pub row: u32,
+    pub name: String,
+    pub email: String,
+    pub identifier: String,
+    pub role: String,
+    pub office: String,
+}
+
+/// Payload of a `user_import` job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserImportJob {
+    pub import_id: ObjectId,
+}
+
+/// Emails and identifiers already taken, including by deactivated accounts.
+#[derive(Debug, Default)]
+pub struct ExistingUsers {
+    pub emails: HashSet<String>,
+    pub identifiers: HashSet<String>,
+}
+
+/// XLSX files are zip archives, so anything starting with the zip signature
+/// is treated as one regardless of its name.
+pub fn detect_format(filename: Option<&str>, bytes: &[u8]) -> ImportFormat {
+    let is_xlsx_name = filename.is_some_and(|f| f.to_ascii_lowercase().ends_with(".xlsx"));
+    if is_xlsx_name || bytes.starts_with(b"PK\x03\x04") {
+        ImportFormat::Xlsx
+    } else {
+        ImportFormat::Csv
+    }
+}
+
+/// Reads the first sheet (XLSX) or the whole file (CSV). The first row is the
+/// header; columns are matched by name in any order, English or Indonesian.
+pub fn parse(bytes: &[u8], format: ImportFormat) -> Result<Vec<RawRow>, String> {
+    let grid = match format {
+        ImportFormat::Csv => read_csv(bytes)?,
+        ImportFormat::Xlsx => read_xlsx(bytes)?,
+    };
+    let mut lines = grid.into_iter();
+    let header = lines.next().ok_or("The file is empty")?;
+
+    let mut columns = HashMap::new();
+    for (index, title) in header.iter().enumerate() {
+        if let Some(field) = column_field(title) {
+            columns.entry(field).or_insert(index);
+        }
+    }
+    for required in ["name", "email", "identifier"] {
+        if !columns.contains_key(required) {
+            return Err(format!("Missing column: {}", required));
+        }
+    }
+
+    let cell = |line: &[String], field: &str| {
+        columns
+            .get(field)
+            .and_then(|&index| line.get(index))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut rows = Vec::new();
+    for (index, line) in lines.enumerate() {
+        if line.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        if rows.len() == MAX_ROWS {
+            return Err(format!("At most {} rows can be imported at once", MAX_ROWS));
+        }
+        rows.push(RawRow {
+            // Line 1 is the header
+            row: index as u32 + 2,
+            name: cell(&line, "name"),
+            email: cell(&line, "email"),
+            identifier: cell(&line, "identifier"),
+            role: cell(&line, "role"),
+            office: cell(&line, "office"),
+        });
+    }
+    Ok(rows)
+}
+
+fn column_field(title: &str) -> Option<&'static str> {
+    let title = title.trim().trim_start_matches('\u{feff}').to_lowercase();
+    match title.as_str() {
+        "name" | "nama" => Some("name"),
+        "email" | "e-mail" => Some("email"),
+        "identifier" | "nip" | "nim" | "nip/nim" => Some("identifier"),
+        "role" | "peran" => Some("role"),
+        "office" | "kantor" => Some("office"),
+        _ => None,
+    }
+}
+
+fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    // Spreadsheet apps in Indonesian locales export with semicolons
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    let delimiter = if first_line.contains(&b';') && !first_line.contains(&b',') { b';' } else { b',' };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(bytes);
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(str::to_string).collect())
+                .map_err(|e| format!("Invalid CSV: {}", e))
+        })
+        .collect()
+}
+
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut workbook = Xlsx::new(Cursor::new(bytes)).map_err(|e| format!("Invalid XLSX file: {}", e))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("The workbook has no sheets")?
+        .map_err(|e| format!("Invalid XLSX file: {}", e))?;
+    // Numeric identifiers come back as floats; Display drops the ".0"
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect())
+}
+
+/// Checks every row and returns the importable ones alongside one error per
+/// rejected row. Offices are matched by id or case-insensitive name; a blank
+/// role means `user`.
+pub fn validate(
+    rows: Vec<RawRow>,
+    offices: &[Office],
+    existing: &ExistingUsers,
+) -> (Vec<ImportRow>, Vec<RowError>) {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen_emails = HashMap::new();
+    let mut seen_identifiers = HashMap::new();
+
+    for raw in rows {
+        let fail = |message: String| RowError { row: raw.row, message };
+
+        if raw.name.is_empty() || raw.email.is_empty() || raw.identifier.is_empty() {
+            errors.push(fail("Name, email and identifier are required".to_string()));
+            continue;
+        }
+        if !is_valid_address(&raw.email) {
+            errors.push(fail(format!("Invalid email address: {}", raw.email)));
+            continue;
+        }
+        let role = if raw.role.is_empty() { "user".to_string() } else { raw.role.to_lowercase() };
+        if !matches!(role.as_str(), "user" | "admin") {
+            errors.push(fail("Role must be user or admin".to_string()));
+            continue;
+        }
+        let office_id = if raw.office.is_empty() {
+            None
+        } else {
+            match offices.iter().find(|o| {
+                o.id.is_some_and(|id| id.to_hex() == raw.office) || o.name.eq_ignore_ascii_case(&raw.office)
+            }) {
+                Some(office) => office.id,
+                None => {
+                    errors.push(fail(format!("Unknown office: {}", raw.office)));
+                    continue;
+                }
+            }
+        };
+
+        let email_key = normalize_address(&raw.email);
+        if existing.emails.contains(&email_key) {
+            errors.push(fail(format!("Email already registered: {}", raw.email)));
+            continue;
+        }
+        if existing.identifiers.contains(&raw.identifier) {
+            errors.push(fail(format!("Identifier already registered: {}", raw.identifier)));
+            continue;
+        }
+        if let Some(first) = seen_emails.get(&email_key) {
+            errors.push(fail(format!("Duplicate email, also on row {}", first)));
+            continue;
+        }
+        if let Some(first) = seen_identifiers.get(&raw.identifier) {
+            errors.push(fail(format!("Duplicate identifier, also on row {}", first)));
+            continue;
+        }
+        seen_emails.insert(email_key.clone(), raw.row);
+        seen_identifiers.insert(raw.identifier.clone(), raw.row);
+
+        valid.push(ImportRow {
+            row: raw.row,
+            name: raw.name,
+            email: email_key,
+            identifier: raw.identifier,
+            role,
+            office_id,
+        });
+    }
+    (valid, errors)
+}
+
+/// Runs a `user_import` job: creates each account with an invitation to set
+/// its password. Resumes after the last saved row if the job is retried.
+pub async fn run(state: &AppState, job: UserImportJob) -> Result<(), String> {
+    let imports_col = state.db.collection::<UserImport>("user_imports");
+    let Some(import) = imports_col
+        .find_one(doc! { "_id": job.import_id })
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+    if import.status == "done" {
+        return Ok(());
+    }
+    imports_col
+        .update_one(doc! { "_id": job.import_id }, doc! { "$set": { "status": "running" } })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let offices: Vec<Office> = state
+        .db
+        .collection::<Office>("offices")
+        .find(doc! { "tenant_id": import.tenant_id })
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let started = std::time::Instant::now();
+    let mut created = 0;
+    let mut failed = Vec::new();
+    let start = import.processed as usize;
+    for (index, row) in import.rows.iter().enumerate().skip(start) {
+        let invitation = Invitation {
+            tenant_id: import.tenant_id,
+            invited_by: import.created_by,
+            name: &row.name,
+            email: &row.email,
+            identifier: &row.identifier,
+            role: &row.role,
+            office: row.office_id.and_then(|id| offices.iter().find(|o| o.id == Some(id))),
+        };
+        match invite_user(state, &invitation).await {
+            Ok(_) => created += 1,
+            Err(message) => failed.push(RowError { row: row.row, message }),
+        }
+
+        let processed = index + 1;
+        if processed % PROGRESS_EVERY == 0 || processed == import.rows.len() {
+            let failed_rows = bson::to_bson(&failed).map_err(|e| e.to_string())?;
+            imports_col
+                .update_one(
+                    doc! { "_id": job.import_id },
+                    doc! {
+                        "$set": { "processed": processed as i32 },
+                        "$inc": { "created": created },
+                        "$push": { "errors": { "$each": failed_rows } },
+                    },
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            created = 0;
+            failed.clear();
+
+            if processed < import.rows.len() && started.elapsed() > MAX_RUN_TIME {
+                enqueue(&state.db, JOB_KIND_USER_IMPORT, &job).await.map_err(|e| e.to_string())?;
+                return Ok(());
+            }
+        }
+    }
+
+    let finished = imports_col
+        .find_one_and_update(
+            doc! { "_id": job.import_id },
+            doc! { "$set": { "status": "done", "finished_at": DateTime::now() } },
+        )
+        .return_document(mongodb::options::ReturnDocument::After)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(finished) = finished {
+        // Runs in the job worker, long after the upload request
+        AuditEvent::new(import.tenant_id, Some(import.created_by), "user.import", "user_import", Some(job.import_id))
+            .details(doc! { "total": finished.total, "created": finished.created, "failed": finished.errors.len() as i32 })
+            .record(&state.db, &ClientInfo::default())
+            .await;
+    }
+    Ok(())
+}
+
+/// An account to create without a password, with an emailed link to set one.
+pub struct Invitation<'a> {
+    pub tenant_id: ObjectId,
+    pub invited_by: ObjectId,
+    pub name: &'a str,
+    pub email: &'a str,
+    pub identifier: &'a str,
+    pub role: &'a str,
+    pub office: Option<&'a Office>,
+}
+
+/// Inserts an invited account and queues its invitation. An email or
+/// identifier already taken in the tenant is reported rather than retried;
+/// for imports the row may have become a duplicate since the dry run (or on a
+/// retried job).
+pub async fn invite_user(state: &AppState, invitation: &Invitation<'_>) -> Result<ObjectId, String> {
+    let users_col = state.db.collection::<User>("users");
+    let taken = users_col
+        .find_one(doc! {
+            "tenant_id": invitation.tenant_id,
+            "$or": [{ "email": invitation.email }, { "identifier": invitation.identifier }],
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    if taken.is_some() {
+        return Err("Email or identifier already registered".to_string());
+    }
+
+    let office = invitation.office;
+    let user = User {
+        id: None,
+        tenant_id: invitation.tenant_id,
+        name: invitation.name.to_string(),
+        email: invitation.email.to_string(),
+        email_verified: false,
+        pending_email: None,
+        identifier: invitation.identifier.to_string(),
+        // No password until the invitation is accepted
+        password_hash: String::new(),
+        password_history: vec![],
+        role: invitation.role.to_string(),
+        office_location: OfficeLocation {
+            r#type: "Point".to_string(),
+            coordinates: office
+                .map(|o| o.location.coordinates.clone())
+                .unwrap_or_else(|| vec![0.0, 0.0]),
+        },
+        face_landmarks: vec![],
+        face_enrolled: false,
+        photo_url: None,
+        token_version: 0,
+        office_id: office.and_then(|o| o.id),
+        department_id: None,
+        team_id: None,
+        oidc_subject: None,
+        ldap_dn: None,
+        locale: DEFAULT_LOCALE.to_string(),
+        must_change_password: false,
+        deactivated: false,
+        deleted_at: None,
+        deleted_by: None,
+    };
+    let user_id = users_col
+        .insert_one(&user)
+        .await
+        .map_err(|e| {
+            if is_duplicate_key(&e) {
+                "Email or identifier already registered".to_string()
+            } else {
+                e.to_string()
+            }
+        })?
+        .inserted_id
+        .as_object_id()
+        .unwrap();
+
+    let token = generate_token();
+    state
+        .db
+        .collection::<PasswordReset>("password_resets")
+        .insert_one(PasswordReset {
+            user_id,
+            email: user.email.clone(),
+            token_hash: hash_token(&token),
+            expires_at: Utc::now() + Duration::days(INVITATION_VALID_DAYS),
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    enqueue_email(
+        &state.db,
+        &user.email,
+        &user.locale,
+        "invitation",
+        serde_json::json!({ "name": &user.name, "token": token, "valid_days": INVITATION_VALID_DAYS }),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let data = serde_json::json!({
+        "user_id": user_id.to_hex(),
+        "name": &user.name,
+        "email": &user.email,
+        "identifier": &user.identifier,
+        "role": &user.role,
+        "created_by": invitation.invited_by.to_hex(),
+    });
+    if let Err(e) = emit(state, invitation.tenant_id, EVENT_USER_REGISTERED, data).await {
+        eprintln!("Failed to queue registration webhook: {:?}", e);
+    }
+    Ok(user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn office(name: &str) -> Office {
+        Office {
+            id: Some(ObjectId::new()),
+            tenant_id: ObjectId::new(),
+            name: name.to_string(),
+            location: OfficeLocation { r#type: "Point".to_string(), coordinates: vec![106.8, -6.2] },
+            radius_m: 100.0,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(Some("staff.XLSX"), b"whatever"), ImportFormat::Xlsx);
+        assert_eq!(detect_format(None, b"PK\x03\x04rest"), ImportFormat::Xlsx);
+        assert_eq!(detect_format(Some("staff.csv"), b"name,email"), ImportFormat::Csv);
+    }
+
+    #[test]
+    fn test_parse_csv_maps_headers_in_any_order() {
+        let csv = "\u{feff}NIP;Nama;Email;Kantor\n 1987 ;Budi;budi@example.com;Pusat\n;;;\nSiti;x\n";
+        let rows = parse(csv.as_bytes(), ImportFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            RawRow {
+                row: 2,
+                name: "Budi".to_string(),
+                email: "budi@example.com".to_string(),
+                identifier: "1987".to_string(),
+                role: String::new(),
+                office: "Pusat".to_string(),
+            }
+        );
+        // Blank lines are skipped but still counted, short lines are padded
+        assert_eq!(rows[1].row, 4);
+        assert_eq!(rows[1].identifier, "Siti");
+        assert_eq!(rows[1].email, "");
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_columns_and_garbage_xlsx() {
+        assert_eq!(
+            parse(b"name,email\nBudi,budi@example.com", ImportFormat::Csv).unwrap_err(),
+            "Missing column: identifier"
+        );
+        assert!(parse(b"", ImportFormat::Csv).is_err());
+        assert!(parse(b"PK\x03\x04not a zip", ImportFormat::Xlsx).is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_each_bad_row() {
+        let pusat = office("Kantor Pusat");
+        let existing = ExistingUsers {
+            emails: HashSet::from(["taken@example.com".to_string()]),
+            identifiers: HashSet::from(["900".to_string()]),
+        };
+        let row = |row: u32, email: &str, identifier: &str, role: &str, office: &str| RawRow {
+            row,
+            name: "Budi".to_string(),
+            email: email.to_string(),
+            identifier: identifier.to_string(),
+            role: role.to_string(),
+            office: office.to_string(),
+        };
+        let rows = vec![
+            row(2, "budi@example.com", "100", "", "kantor pusat"),
+            row(3, "BUDI@example.com", "101", "", ""),
+            row(4, "siti@example.com", "100", "", ""),
+            row(5, "Taken@example.com", "102", "", ""),
+            row(6, "rina@example.com", "900", "", ""),
+            row(7, "not-an-email", "103", "", ""),
+            row(8, "dewi@example.com", "104", "owner", ""),
+            row(9, "eko@example.com", "105", "Admin", "Cabang"),
+            row(10, "", "106", "", ""),
+            row(11, "agus@example.com", "107", "Admin", &pusat.id.unwrap().to_hex()),
+        ];
+
+        let (valid, errors) = validate(rows, &[pusat], &existing);
+        assert_eq!(valid.iter().map(|r| r.row).collect::<Vec<_>>(), vec![2, 11]);
+        assert_eq!(valid[0].role, "user");
+        assert!(valid[0].office_id.is_some());
+        assert_eq!(valid[1].role, "admin");
+        assert_eq!(valid[1].office_id, valid[0].office_id);
+
+        let messages: Vec<_> = errors.iter().map(|e| (e.row, e.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![
+                (3, "Duplicate email, also on row 2"),
+                (4, "Duplicate identifier, also on row 2"),
+                (5, "Email already registered: Taken@example.com"),
+                (6, "Identifier already registered: 900"),
+                (7, "Invalid email address: not-an-email"),
+                (8, "Role must be user or admin"),
+                (9, "Unknown office: Cabang"),
+                (10, "Name, email and identifier are required"),
+            ]
+        );
+    }
+}
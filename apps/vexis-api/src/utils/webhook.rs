@@ -0,0 +1,490 @@
+use crate::models::webhook::{Webhook, WebhookDelivery};
+use crate::utils::jobs::{enqueue, JOB_KIND_WEBHOOK};
+use crate::AppState;
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use hmac::{Hmac, Mac};
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::Database;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client, Url};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub const EVENT_CHECK_IN: &str = "attendance.check_in";
+pub const EVENT_CHECK_OUT: &str = "attendance.check_out";
+pub const EVENT_USER_REGISTERED: &str = "user.registered";
+pub const EVENT_USER_DELETED: &str = "user.deleted";
+/// Sent by the admin "send test event" endpoint; cannot be subscribed to.
+pub const EVENT_TEST: &str = "webhook.test";
+
+/// Events a webhook may subscribe to.
+pub const WEBHOOK_EVENTS: [&str; 4] = [
+    EVENT_CHECK_IN,
+    EVENT_CHECK_OUT,
+    EVENT_USER_REGISTERED,
+    EVENT_USER_DELETED,
+];
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Delivery log entries are kept this long.
+const DELIVERY_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug)]
+pub enum WebhookError {
+    Http(reqwest::Error),
+    /// The endpoint answered with a non-2xx status.
+    Rejected(u16),
+    /// The URL points at an internal address.
+    Blocked(String),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::Http(e) => write!(f, "request to webhook failed: {}", e),
+            WebhookError::Rejected(status) => write!(f, "webhook responded with status {}", status),
+            WebhookError::Blocked(reason) => write!(f, "webhook address not allowed: {}", reason),
+        }
+    }
+}
+
+impl From<reqwest::Error> for WebhookError {
+    fn from(e: reqwest::Error) -> Self {
+        WebhookError::Http(e)
+    }
+}
+
+/// JSON body posted to the webhook. `id` is shared by all deliveries of the
+/// same event so receivers can deduplicate.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    id: String,
+    event: &'a str,
+    created_at: String,
+    data: &'a serde_json::Value,
+}
+
+/// Payload of a `webhook` job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookJob {
+    pub delivery_id: ObjectId,
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`, sent as
+/// `X-Vexis-Signature: sha256=<hex>`. Including the timestamp lets receivers
+/// reject replayed requests.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Queues `event` for every active webhook of the tenant subscribed to it.
+/// Callers log the error rather than fail the request that caused the event.
+pub async fn emit(
+    state: &AppState,
+    tenant_id: ObjectId,
+    event: &str,
+    data: serde_json::Value,
+) -> Result<(), mongodb::error::Error> {
+    let webhooks: Vec<Webhook> = state
+        .db
+        .collection::<Webhook>("webhooks")
+        .find(doc! { "tenant_id": tenant_id, "active": true, "events": event })
+        .await?
+        .try_collect()
+        .await?;
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let body = envelope(event, &data);
+    for webhook in &webhooks {
+        queue_delivery(&state.db, webhook.id.unwrap(), event, &body).await?;
+    }
+    Ok(())
+}
+
+/// Queues a `webhook.test` event to `webhook` only, regardless of its
+/// subscriptions.
+pub async fn send_test(db: &Database, webhook: &Webhook) -> Result<ObjectId, mongodb::error::Error> {
+    let data = serde_json::json!({ "message": "Test event from Vexis" });
+    queue_delivery(db, webhook.id.unwrap(), EVENT_TEST, &envelope(EVENT_TEST, &data)).await
+}
+
+fn envelope(event: &str, data: &serde_json::Value) -> String {
+    serde_json::to_string(&Envelope {
+        id: Uuid::new_v4().to_string(),
+        event,
+        created_at: Utc::now().to_rfc3339(),
+        data,
+    })
+    .expect("event envelope serializes")
+}
+
+async fn queue_delivery(
+    db: &Database,
+    webhook_id: ObjectId,
+    event: &str,
+    body: &str,
+) -> Result<ObjectId, mongodb::error::Error> {
+    let now = Utc::now();
+    let delivery = WebhookDelivery {
+        id: None,
+        webhook_id,
+        event: event.to_string(),
+        body: body.to_string(),
+        status: "pending".to_string(),
+        attempts: 0,
+        response_status: None,
+        last_error: None,
+        created_at: now,
+        delivered_at: None,
+        expires_at: now + Duration::days(DELIVERY_RETENTION_DAYS),
+    };
+
+    let result = db
+        .collection::<WebhookDelivery>("webhook_deliveries")
+        .insert_one(delivery)
+        .await?;
+    let delivery_id = result.inserted_id.as_object_id().unwrap();
+    enqueue(db, JOB_KIND_WEBHOOK, &WebhookJob { delivery_id }).await?;
+    Ok(delivery_id)
+}
+
+/// Whether webhooks may be sent to `ip`. Loopback, private (RFC 1918),
+/// link-local, unique-local and unspecified addresses are internal, also when
+/// mapped into IPv6.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            let this_network = a == 0;
+            let shared = a == 100 && b & 0xc0 == 64;
+            let benchmarking = a == 198 && b & 0xfe == 18;
+            let documentation = matches!((a, b, c), (192, 0, 2) | (198, 51, 100) | (203, 0, 113));
+            // 240.0.0.0/4 also covers the broadcast address
+            let reserved = a >= 240;
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_multicast()
+                || this_network
+                || shared
+                || benchmarking
+                || documentation
+                || reserved)
+        }
+        IpAddr::V6(v6) => match embedded_ipv4(v6) {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let segments = v6.segments();
+                let unique_local = segments[0] & 0xfe00 == 0xfc00;
+                let link_local = segments[0] & 0xffc0 == 0xfe80;
+                let documentation = segments[0] == 0x2001 && segments[1] == 0x0db8;
+                !(v6.is_loopback() || v6.is_unspecified() || v6.is_multicast() || unique_local || link_local || documentation)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address forwards to: IPv4-mapped
+/// (`::ffff:0:0/96`), IPv4-compatible (`::/96`), 6to4 (`2002::/16`) and NAT64
+/// (`64:ff9b::/96`).
+fn embedded_ipv4(v6: Ipv6Addr) -> Option<Ipv4Addr> {
+    let s = v6.segments();
+    let from = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match s {
+        [0, 0, 0, 0, 0, 0xffff, high, low] | [0, 0, 0, 0, 0, 0, high, low] => Some(from(high, low)),
+        [0x0064, 0xff9b, 0, 0, 0, 0, high, low] => Some(from(high, low)),
+        [0x2002, high, low, ..] => Some(from(high, low)),
+        _ => None,
+    }
+}
+
+/// Checks the host written in a webhook URL: `localhost` and internal IP
+/// literals are refused. Hostnames are checked again once resolved, at send
+/// time.
+pub fn check_host(url: &Url) -> Result<(), &'static str> {
+    let Some(host) = url.host_str() else {
+        return Err("URL must have a host");
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return if is_public_ip(ip) { Ok(()) } else { Err("URL must not point at an internal address") };
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        return Err("URL must not point at an internal address");
+    }
+    Ok(())
+}
+
+/// Resolver for the webhook client that refuses hostnames with an internal
+/// address, so the connection goes to the address that was checked.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+                return Err(format!("{} resolves to {}", name.as_str(), addr.ip()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Client for webhook requests. Redirects are not followed, since the target
+/// of a redirect was never checked, and proxies are bypassed so the resolved
+/// address is the one connected to.
+pub fn client() -> Client {
+    Client::builder()
+        .redirect(redirect::Policy::none())
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .expect("webhook client builds")
+}
+
+/// Posts the signed body and returns the response status. The URL is
+/// checked again here, as it may have been saved before the checks existed
+/// or its hostname may now resolve to an internal address.
+pub async fn send(
+    http: &Client,
+    url: &str,
+    secret: &str,
+    delivery_id: &str,
+    event: &str,
+    body: &str,
+) -> Result<u16, WebhookError> {
+    let parsed = Url::parse(url).map_err(|e| WebhookError::Blocked(e.to_string()))?;
+    check_host(&parsed).map_err(|msg| WebhookError::Blocked(msg.to_string()))?;
+    let host = parsed.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| WebhookError::Blocked(e.to_string()))?;
+    for addr in addrs {
+        if !is_public_ip(addr.ip()) {
+            return Err(WebhookError::Blocked(format!("{} resolves to {}", host, addr.ip())));
+        }
+    }
+
+    post_signed(http, url, secret, delivery_id, event, body).await
+}
+
+async fn post_signed(
+    http: &Client,
+    url: &str,
+    secret: &str,
+    delivery_id: &str,
+    event: &str,
+    body: &str,
+) -> Result<u16, WebhookError> {
+    let timestamp = Utc::now().timestamp();
+    let response = http
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-Vexis-Event", event)
+        .header("X-Vexis-Delivery", delivery_id)
+        .header("X-Vexis-Timestamp", timestamp.to_string())
+        .header("X-Vexis-Signature", format!("sha256={}", sign(secret, timestamp, body)))
+        .body(body.to_string())
+        .send()
+        .await?;
+
+    let status = response.status().as_u16();
+    if response.status().is_success() {
+        Ok(status)
+    } else {
+        Err(WebhookError::Rejected(status))
+    }
+}
+
+/// Runs a `webhook` job and records the attempt in the delivery log. Failures
+/// are returned so the job queue retries with backoff.
+pub async fn deliver(state: &AppState, job: WebhookJob) -> Result<(), String> {
+    let deliveries_col = state.db.collection::<WebhookDelivery>("webhook_deliveries");
+    let Some(delivery) = deliveries_col
+        .find_one(doc! { "_id": job.delivery_id })
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        // Expired from the log; nothing left to send
+        return Ok(());
+    };
+
+    let webhook = state
+        .db
+        .collection::<Webhook>("webhooks")
+        .find_one(doc! { "_id": delivery.webhook_id })
+        .await
+        .map_err(|e| e.to_string())?;
+    // Test events go out even while the webhook is disabled
+    let webhook = match webhook {
+        Some(w) if w.active || delivery.event == EVENT_TEST => w,
+        _ => {
+            let _ = deliveries_col
+                .update_one(
+                    doc! { "_id": job.delivery_id },
+                    doc! { "$set": { "status": "failed", "last_error": "Webhook was deleted or disabled" } },
+                )
+                .await;
+            return Ok(());
+        }
+    };
+
+    let result = send(
+        &state.webhook_http,
+        &webhook.url,
+        &webhook.secret,
+        &job.delivery_id.to_hex(),
+        &delivery.event,
+        &delivery.body,
+    )
+    .await;
+
+    let update = match &result {
+        Ok(status) => doc! {
+            "$set": {
+                "status": "delivered",
+                "response_status": *status as i32,
+                "last_error": null,
+                "delivered_at": DateTime::now(),
+            },
+            "$inc": { "attempts": 1 },
+        },
+        Err(e) => doc! {
+            "$set": {
+                "status": "failed",
+                "response_status": match e {
+                    WebhookError::Rejected(status) => Some(*status as i32),
+                    WebhookError::Http(_) | WebhookError::Blocked(_) => None,
+                },
+                "last_error": e.to_string(),
+            },
+            "$inc": { "attempts": 1 },
+        },
+    };
+    if let Err(e) = deliveries_col.update_one(doc! { "_id": job.delivery_id }, update).await {
+        eprintln!("Failed to log webhook delivery {}: {}", job.delivery_id, e);
+    }
+
+    result.map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_sign_matches_known_vector() {
+        // printf '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1_700_000_000, "{}"),
+            "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+        assert_ne!(sign("secret", 1_700_000_001, "{}"), sign("secret", 1_700_000_000, "{}"));
+    }
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    #[tokio::test]
+    async fn test_send_posts_signed_body() {
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(|State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push((headers, body));
+                    StatusCode::NO_CONTENT
+                }),
+            )
+            .route("/down", post(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let body = envelope(EVENT_CHECK_IN, &serde_json::json!({ "type": "In" }));
+        let client = Client::new();
+        let status = post_signed(&client, &format!("http://{}/hook", addr), "s3cret", "d1", EVENT_CHECK_IN, &body)
+            .await
+            .unwrap();
+        assert_eq!(status, 204);
+
+        let (headers, received_body) = received.lock().unwrap().pop().unwrap();
+        assert_eq!(received_body, body);
+        assert_eq!(headers["x-vexis-event"], EVENT_CHECK_IN);
+        assert_eq!(headers["x-vexis-delivery"], "d1");
+        let timestamp: i64 = headers["x-vexis-timestamp"].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers["x-vexis-signature"].to_str().unwrap(),
+            format!("sha256={}", sign("s3cret", timestamp, &body))
+        );
+
+        let result = post_signed(&client, &format!("http://{}/down", addr), "s3cret", "d2", EVENT_CHECK_IN, &body).await;
+        assert!(matches!(result, Err(WebhookError::Rejected(503))));
+    }
+
+    #[test]
+    fn test_internal_addresses_are_not_public() {
+        let internal = [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0",
+            "::1", "fc00::1", "fd12::1", "fe80::1", "::ffff:10.0.0.1",
+            // Shared address space (CGNAT), including a cloud metadata address
+            "100.64.0.1", "100.100.100.200", "100.127.255.254",
+            // This network, multicast and reserved
+            "0.1.2.3", "224.0.0.1", "239.255.255.250", "240.0.0.1", "255.255.255.255",
+            // Documentation and benchmarking
+            "192.0.2.1", "198.51.100.1", "203.0.113.1", "198.18.0.1", "198.19.255.255",
+            // IPv6 multicast and documentation
+            "ff02::1", "ff0e::1", "2001:db8::1",
+            // IPv4 wrapped in IPv6: compatible, 6to4 and NAT64
+            "::127.0.0.1", "::10.0.0.1", "2002:7f00:1::", "2002:a9fe:a9fe::1", "64:ff9b::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ];
+        for ip in internal {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        let public = [
+            "8.8.8.8", "172.32.0.1", "100.128.0.1", "198.20.0.1", "2606:4700::1111", "::ffff:8.8.8.8",
+            "2002:808:808::1", "64:ff9b::8.8.8.8",
+        ];
+        for ip in public {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_refuses_internal_hosts() {
+        let client = client();
+        let urls = [
+            "http://127.0.0.1:9/hook",
+            "http://localhost:9/hook",
+            "http://[::1]:9/hook",
+            "http://169.254.169.254/",
+        ];
+        for url in urls {
+            let result = send(&client, url, "s3cret", "d1", EVENT_CHECK_IN, "{}").await;
+            assert!(matches!(result, Err(WebhookError::Blocked(_))), "{}", url);
+        }
+    }
+}
@@ -57,7 +57,6 @@ export default function RegisterPage() {
         email: data.email,
         identifier: data.identifier,
         password: data.password,
-        role: "user",
         lat: data.location.lat,
         long: data.location.lng,
       });